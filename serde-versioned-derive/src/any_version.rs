@@ -0,0 +1,125 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::{DeriveInput, Ident, PathArguments, Type};
+
+use crate::attr::{Kind, Version};
+
+/// Generates the enum with one variant per version, and its `WithAnyVersion` impl.
+pub fn expand(input: &DeriveInput, name: &Ident, versions: &[Version]) -> syn::Result<TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`any_version = ...` does not support generic types",
+        ));
+    }
+
+    for version in versions {
+        if let Some(cfg) = &version.cfg {
+            return Err(syn::Error::new_spanned(
+                cfg,
+                "`#[cfg(...)]` versions are not supported with `any_version = ...`",
+            ));
+        }
+    }
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let variants = versions
+        .iter()
+        .map(|version| match version.kind {
+            // Chained versions are named after their shape rather than the
+            // nested `Chain<..>` marker.
+            Kind::Chain => variant_ident(&version.shape),
+            _ => variant_ident(&version.marker),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let markers: Vec<&Type> = versions.iter().map(|version| &version.marker).collect();
+    let shapes: Vec<&Type> = versions.iter().map(|version| &version.shape).collect();
+    let doc = format!("Any version of [`{ident}`], deserialized but not yet converted.");
+
+    Ok(quote! {
+        #[doc = #doc]
+        #vis enum #name {
+            Current(#ident),
+            #(#variants(#shapes),)*
+        }
+
+        impl<'de> ::serde_versioned::__private::serde::Deserialize<'de> for #name {
+            fn deserialize<D>(d: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                let content = ::serde_versioned::__private::deserialize_content(d)?;
+                if let ::std::result::Result::Ok(v) =
+                    ::serde_versioned::__private::from_content::<#ident, D::Error>(&content)
+                {
+                    return ::std::result::Result::Ok(#name::Current(v));
+                }
+                #(
+                    if let ::std::result::Result::Ok(v) =
+                        ::serde_versioned::__private::from_content::<#shapes, D::Error>(&content)
+                    {
+                        return ::std::result::Result::Ok(#name::#variants(v));
+                    }
+                )*
+                ::std::result::Result::Err(::serde_versioned::__private::no_matching_version())
+            }
+        }
+
+        impl #name {
+            /// Converts the data into the current version.
+            #vis fn convert(self) -> ::std::result::Result<#ident, ::serde_versioned::ConvertError> {
+                <#ident as ::serde_versioned::WithAnyVersion>::from_any_version(self)
+            }
+        }
+
+        impl ::serde_versioned::WithAnyVersion for #ident {
+            type AnyVersion = #name;
+            type Error = ::serde_versioned::ConvertError;
+
+            fn from_any_version(v: Self::AnyVersion) -> ::std::result::Result<Self, Self::Error> {
+                match v {
+                    #name::Current(v) => ::std::result::Result::Ok(v),
+                    #(
+                        #name::#variants(v) => <Self as ::serde_versioned::FromVersion<
+                            ::serde_versioned::Ver<#markers>,
+                        >>::convert(v)
+                        .map_err(::serde_versioned::ConvertError::new),
+                    )*
+                }
+            }
+        }
+    })
+}
+
+/// Names the variant of a version after its marker, e.g. `Num<1>` becomes `Num1`.
+pub fn variant_ident(marker: &Type) -> syn::Result<Ident> {
+    let segment = match marker {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    };
+    let Some(segment) = segment else {
+        return Err(syn::Error::new_spanned(
+            marker,
+            "expected the version marker to be a path, e.g. `Num<1>`",
+        ));
+    };
+
+    let mut name = segment.ident.to_string();
+    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+        let args: Vec<String> = args
+            .args
+            .iter()
+            .map(|arg| {
+                arg.to_token_stream()
+                    .to_string()
+                    .chars()
+                    .filter(|c| c.is_alphanumeric() || *c == '_')
+                    .collect()
+            })
+            .collect();
+        name.push_str(&args.join("_"));
+    }
+
+    Ok(format_ident!("{}", name, span = segment.ident.span()))
+}
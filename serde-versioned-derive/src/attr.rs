@@ -0,0 +1,546 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::meta::ParseNestedMeta;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Fields, Ident, LitInt, LitStr, Meta, Path, Token, Type};
+
+/// An old version of the type, declared as `Marker = Shape`, optionally
+/// preceded by `#[cfg(...)]` to compile it out of some builds.
+pub struct Version {
+    /// The version marker, e.g. `Num<1>`.
+    pub marker: Type,
+    /// The `VersionType` the old data is deserialized into.
+    pub shape: Type,
+    /// How the version is converted into the current type.
+    pub kind: Kind,
+    /// The `#[cfg(...)]` the version is compiled with.
+    pub cfg: Option<Attribute>,
+}
+
+impl Version {
+    /// The marker listed in `Versions`, which is `Gated` if the version has a `cfg`.
+    pub fn listed_marker(&self) -> Type {
+        let marker = &self.marker;
+        match &self.cfg {
+            Some(cfg) => {
+                let predicate = cfg.meta.require_list().ok().map(|list| &list.tokens);
+                syn::parse_quote!(::serde_versioned::Gated<#marker, { ::core::cfg!(#predicate) }>)
+            }
+            None => marker.clone(),
+        }
+    }
+
+    /// Fails if the version has a `cfg`, for lists that don't support them.
+    pub fn reject_cfg(&self, list: &str) -> syn::Result<()> {
+        match &self.cfg {
+            Some(cfg) => Err(syn::Error::new_spanned(
+                cfg,
+                format!("`#[cfg(...)]` is only supported in `from(...)`, not in `{list}(...)`"),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How an old version is converted into the current type.
+#[derive(PartialEq)]
+pub enum Kind {
+    /// With the `From`/`TryFrom` impls of the current type.
+    Convert,
+    /// With a converter generated from the change annotations.
+    Generate,
+    /// Step-wise with `Upgrade` impls, through the versions declared before it.
+    Chain,
+}
+
+impl Parse for Version {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        if let Some(attr) = attrs.iter().find(|attr| !attr.path().is_ident("cfg")) {
+            return Err(syn::Error::new_spanned(attr, "expected `#[cfg(...)]`"));
+        }
+        if let Some(attr) = attrs.get(1) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "combine the predicates into one `#[cfg(all(...))]`",
+            ));
+        }
+        if let Some(attr) = attrs.first() {
+            attr.meta.require_list()?;
+        }
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let shape = input.parse()?;
+        Ok(Version {
+            marker,
+            shape,
+            kind: Kind::Convert,
+            cfg: attrs.pop(),
+        })
+    }
+}
+
+/// A field name used by an old version, declared as `Marker = "name"`.
+pub struct Rename {
+    pub marker: Type,
+    pub name: LitStr,
+}
+
+impl Parse for Rename {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let name = input.parse()?;
+        Ok(Rename { marker, name })
+    }
+}
+
+/// A stored sample of an old version, declared as `Marker = "path"`.
+pub struct Fixture {
+    pub marker: Type,
+    /// The path of the JSON fixture, relative to the crate root.
+    pub path: LitStr,
+}
+
+impl Parse for Fixture {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let path = input.parse()?;
+        Ok(Fixture { marker, path })
+    }
+}
+
+/// The schema registry id of a version, declared as `Marker = id`.
+pub struct SchemaId {
+    pub marker: Type,
+    pub id: LitInt,
+}
+
+impl Parse for SchemaId {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let id = input.parse()?;
+        Ok(SchemaId { marker, id })
+    }
+}
+
+/// An enum variant that no longer exists, declared as
+/// `Marker = Variant(Fields) => converter`.
+pub struct Removed {
+    pub marker: Type,
+    pub attrs: Vec<Attribute>,
+    pub ident: Ident,
+    pub fields: Fields,
+    /// Called with the fields of the old variant, returns the converted value.
+    pub converter: Path,
+}
+
+impl Parse for Removed {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+        let fields = if input.peek(syn::token::Brace) {
+            Fields::Named(input.parse()?)
+        } else if input.peek(syn::token::Paren) {
+            Fields::Unnamed(input.parse()?)
+        } else {
+            Fields::Unit
+        };
+        input.parse::<Token![=>]>()?;
+        let converter = input.parse()?;
+        Ok(Removed {
+            marker,
+            attrs,
+            ident,
+            fields,
+            converter,
+        })
+    }
+}
+
+/// A struct field that only exists in an old version, declared as
+/// `Marker = name: Type => converter`.
+pub struct Dropped {
+    pub marker: Type,
+    pub attrs: Vec<Attribute>,
+    pub ident: Ident,
+    pub ty: Type,
+    /// Called with the current value when downgrading, returns the old field.
+    pub converter: Path,
+}
+
+impl Parse for Dropped {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let marker = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let converter = input.parse()?;
+        Ok(Dropped {
+            marker,
+            attrs,
+            ident,
+            ty,
+            converter,
+        })
+    }
+}
+
+/// The parsed `#[versioned(...)]` attributes of the deriving type.
+#[derive(Default)]
+pub struct Container {
+    /// The old versions, in the order they should be attempted.
+    pub versions: Vec<Version>,
+    /// The enum variants that only exist in old versions.
+    pub removed: Vec<Removed>,
+    /// The struct fields that only exist in old versions.
+    pub dropped: Vec<Dropped>,
+    /// The generated versions the current type can be converted back into.
+    pub downgrade: Vec<Type>,
+    /// The foreign type mirrored by the deriving newtype.
+    pub remote: Option<Type>,
+    /// The name of the enum to generate with one variant per version.
+    pub any_version: Option<Ident>,
+    /// The fixtures to generate compatibility tests for.
+    pub fixtures: Vec<Fixture>,
+    /// The `Fallback` marker attempted after every old version.
+    pub fallback: Option<Type>,
+    /// Whether the value is checked with its `Validate` impl after deserializing.
+    pub validate: bool,
+    /// The marker of the current version, for reading and writing version tags.
+    pub current: Option<Type>,
+    /// The `EnvelopeLayout` of the type.
+    pub envelope: Option<TokenStream>,
+    /// The schema registry ids of the versions.
+    pub schema_ids: Vec<SchemaId>,
+}
+
+impl Container {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut container = Container::default();
+
+        for attr in attrs {
+            if !attr.path().is_ident("versioned") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("from") {
+                    container.versions.extend(parse_list::<Version>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("generate") {
+                    for version in parse_list::<Version>(&meta)? {
+                        version.reject_cfg("generate")?;
+                        container.versions.push(Version {
+                            kind: Kind::Generate,
+                            ..version
+                        });
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("chain") {
+                    // Each shape is upgraded into the one declared before it,
+                    // the first one into the current type.
+                    let mut next: Type = syn::parse_quote!(::serde_versioned::Current);
+                    for shape in parse_list::<Type>(&meta)? {
+                        let marker: Type = syn::parse_quote!(::serde_versioned::Chain<#shape, #next>);
+                        container.versions.push(Version {
+                            marker: marker.clone(),
+                            shape,
+                            kind: Kind::Chain,
+                            cfg: None,
+                        });
+                        next = marker;
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("removed") {
+                    container.removed.extend(parse_list::<Removed>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("dropped") {
+                    container.dropped.extend(parse_list::<Dropped>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("downgrade") {
+                    container.downgrade.extend(parse_list::<Type>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("remote") {
+                    let remote: LitStr = meta.value()?.parse()?;
+                    container.remote = Some(remote.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("any_version") {
+                    container.any_version = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("fixtures") {
+                    container.fixtures.extend(parse_list::<Fixture>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("fallback") {
+                    let provider = match meta.input.peek(Token![=]) {
+                        true => Some(meta.value()?.parse()?),
+                        false => None,
+                    };
+                    container.fallback = Some(fallback_marker(provider));
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    container.validate = true;
+                    Ok(())
+                } else if meta.path.is_ident("current") {
+                    container.current = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("envelope") {
+                    container.envelope = Some(parse_envelope(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("schema_ids") {
+                    container.schema_ids.extend(parse_list::<SchemaId>(&meta)?);
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unsupported versioned attribute, expected `from(...)`, `generate(...)`, `chain(...)`, `removed(...)`, `dropped(...)`, `downgrade(...)`, `remote = \"...\"`, `any_version = ...`, `fixtures(...)`, `fallback`, `validate`, `current = ...`, `envelope(...)` or `schema_ids(...)`",
+                    ))
+                }
+            })?;
+        }
+
+        Ok(container)
+    }
+}
+
+/// The `#[versioned(from(...), rename_from(...), fallback, label = ...)]` attributes of a field,
+/// handled by the `#[versioned]` attribute macro.
+#[derive(Default)]
+pub struct FieldVersions {
+    /// The old versions of the field, in the order they should be attempted.
+    pub versions: Vec<Version>,
+    /// The names the field had in old versions.
+    pub renames: Vec<Rename>,
+    /// The `Fallback` marker attempted after every old version.
+    pub fallback: Option<Type>,
+    /// The label of the `FromVersion` impls converting the old versions.
+    pub label: Option<Type>,
+    /// Whether the field is read as any version of its `DeserializeVersioned` type.
+    pub nested: Option<Path>,
+}
+
+impl FieldVersions {
+    /// Parses and removes the `from(...)`, `rename_from(...)`, `fallback`, `label` and `nested`
+    /// entries of the field's `#[versioned(...)]` attributes. Other entries are left for the derive.
+    pub fn take_from_attrs(attrs: &mut Vec<Attribute>) -> syn::Result<Self> {
+        let mut field = FieldVersions::default();
+        let mut remaining = Vec::with_capacity(attrs.len());
+
+        for attr in attrs.drain(..) {
+            if !attr.path().is_ident("versioned") {
+                remaining.push(attr);
+                continue;
+            }
+
+            let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            let mut kept = Punctuated::<Meta, Token![,]>::new();
+            for meta in metas {
+                match &meta {
+                    Meta::List(list) if list.path.is_ident("from") => {
+                        let versions = list
+                            .parse_args_with(Punctuated::<Version, Token![,]>::parse_terminated)?;
+                        field.versions.extend(versions);
+                    }
+                    Meta::List(list) if list.path.is_ident("rename_from") => {
+                        let renames = list
+                            .parse_args_with(Punctuated::<Rename, Token![,]>::parse_terminated)?;
+                        field.renames.extend(renames);
+                    }
+                    Meta::Path(path) if path.is_ident("fallback") => {
+                        field.fallback = Some(fallback_marker(None));
+                    }
+                    Meta::NameValue(value) if value.path.is_ident("fallback") => {
+                        let provider = syn::parse2(value.value.to_token_stream())?;
+                        field.fallback = Some(fallback_marker(Some(provider)));
+                    }
+                    Meta::Path(path) if path.is_ident("nested") => {
+                        field.nested = Some(path.clone());
+                    }
+                    Meta::NameValue(value) if value.path.is_ident("label") => {
+                        field.label = Some(syn::parse2(value.value.to_token_stream())?);
+                    }
+                    _ => kept.push(meta),
+                }
+            }
+
+            if !kept.is_empty() {
+                remaining.push(syn::parse_quote!(#[versioned(#kept)]));
+            }
+        }
+
+        *attrs = remaining;
+        Ok(field)
+    }
+}
+
+/// The change annotations of a field or enum variant, describing how it
+/// differed in old versions.
+#[derive(Default)]
+pub struct FieldChanges {
+    /// The versions in which the field or variant did not exist yet.
+    pub added: Vec<Type>,
+    /// The versions in which the field or variant had a different name.
+    pub renamed: Vec<Rename>,
+    /// The versions in which the field, or the payload of a newtype variant,
+    /// had a different type.
+    pub changed: Vec<Version>,
+}
+
+impl FieldChanges {
+    pub fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut changes = FieldChanges::default();
+
+        for attr in attrs {
+            if !attr.path().is_ident("versioned") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("added") {
+                    changes.added.extend(parse_list::<Type>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("renamed") {
+                    changes.renamed.extend(parse_list::<Rename>(&meta)?);
+                    Ok(())
+                } else if meta.path.is_ident("changed") {
+                    for version in parse_list::<Version>(&meta)? {
+                        version.reject_cfg("changed")?;
+                        changes.changed.push(version);
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("from")
+                    || meta.path.is_ident("rename_from")
+                    || meta.path.is_ident("fallback")
+                    || meta.path.is_ident("nested")
+                    || meta.path.is_ident("label")
+                {
+                    Err(meta.error(
+                        "versioned fields require `#[serde_versioned::versioned]` on the type",
+                    ))
+                } else {
+                    Err(meta.error(
+                        "unsupported versioned attribute, expected `added(...)`, `renamed(...)` or `changed(...)`",
+                    ))
+                }
+            })?;
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Parses `envelope(tag = "...", content = "...")`, `envelope(wrapper)` or
+/// `envelope(internal, tag = "...")` into an `EnvelopeLayout`.
+fn parse_envelope(meta: &ParseNestedMeta) -> syn::Result<TokenStream> {
+    let mut tag: Option<LitStr> = None;
+    let mut content: Option<LitStr> = None;
+    let mut wrapper = false;
+    let mut internal = false;
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("tag") {
+            tag = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("content") {
+            content = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("wrapper") {
+            wrapper = true;
+        } else if meta.path.is_ident("internal") {
+            internal = true;
+        } else {
+            return Err(meta.error(
+                "unsupported envelope attribute, expected `tag = \"...\"`, `content = \"...\"`, `wrapper` or `internal`",
+            ));
+        }
+        Ok(())
+    })?;
+
+    let conflict = match (wrapper, internal) {
+        (true, true) => Some("`wrapper` and `internal` can't be combined"),
+        (true, false) if tag.is_some() || content.is_some() => {
+            Some("a `wrapper` envelope has no `tag` or `content`")
+        }
+        (false, true) if content.is_some() => Some("an `internal` envelope has no `content`"),
+        _ => None,
+    };
+    if let Some(message) = conflict {
+        return Err(meta.error(message));
+    }
+
+    let tag = tag.unwrap_or_else(|| LitStr::new("version", meta.path.span()));
+    let content = content.unwrap_or_else(|| LitStr::new("data", meta.path.span()));
+    Ok(match (wrapper, internal) {
+        (true, _) => quote!(::serde_versioned::EnvelopeLayout::Wrapper),
+        (_, true) => quote!(::serde_versioned::EnvelopeLayout::Internal { tag: #tag }),
+        _ => quote! {
+            ::serde_versioned::EnvelopeLayout::Adjacent { tag: #tag, content: #content }
+        },
+    })
+}
+
+/// The marker of `fallback` or `fallback = Provider`.
+fn fallback_marker(provider: Option<Type>) -> Type {
+    let provider = provider.unwrap_or_else(|| syn::parse_quote!(::serde_versioned::DefaultOf));
+    syn::parse_quote!(::serde_versioned::Fallback<#provider>)
+}
+
+fn parse_list<T: Parse>(meta: &ParseNestedMeta) -> syn::Result<Punctuated<T, Token![,]>> {
+    let content;
+    syn::parenthesized!(content in meta.input);
+    Punctuated::parse_terminated(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn envelope(attr: Attribute) -> Result<String, String> {
+        Container::from_attrs(&[attr])
+            .map(|container| container.envelope.unwrap().to_string().replace(' ', ""))
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_envelope() {
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(tag = "v"))])),
+            Ok(r#"::serde_versioned::EnvelopeLayout::Adjacent{tag:"v",content:"data"}"#.into())
+        );
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(internal, tag = "v"))])),
+            Ok(r#"::serde_versioned::EnvelopeLayout::Internal{tag:"v"}"#.into())
+        );
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(wrapper))])),
+            Ok("::serde_versioned::EnvelopeLayout::Wrapper".into())
+        );
+    }
+
+    #[test]
+    fn test_envelope_conflicts() {
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(wrapper, internal))])),
+            Err("`wrapper` and `internal` can't be combined".into())
+        );
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(wrapper, tag = "v"))])),
+            Err("a `wrapper` envelope has no `tag` or `content`".into())
+        );
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(content = "d", wrapper))])),
+            Err("a `wrapper` envelope has no `tag` or `content`".into())
+        );
+        assert_eq!(
+            envelope(parse_quote!(#[versioned(envelope(internal, content = "d"))])),
+            Err("an `internal` envelope has no `content`".into())
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, LitStr};
+
+use crate::attr;
+use crate::order;
+use crate::versioned::MAX_OLD_VERSIONS;
+
+pub fn expand(args: TokenStream, mut input: DeriveInput) -> syn::Result<TokenStream> {
+    if !args.is_empty() {
+        return Err(syn::Error::new_spanned(args, "expected `#[versioned]`"));
+    }
+
+    let fields: Vec<&mut syn::Field> = match &mut input.data {
+        Data::Struct(data) => data.fields.iter_mut().collect(),
+        Data::Enum(data) => data
+            .variants
+            .iter_mut()
+            .flat_map(|variant| variant.fields.iter_mut())
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "versioned fields are not supported on unions",
+            ))
+        }
+    };
+
+    let mut checks = Vec::new();
+    for field in fields {
+        let attr::FieldVersions {
+            versions,
+            renames,
+            fallback,
+            label,
+            nested,
+        } = attr::FieldVersions::take_from_attrs(&mut field.attrs)?;
+
+        let mut aliases: Vec<String> = Vec::new();
+        for rename in &renames {
+            let marker = &rename.marker;
+            checks.push(quote_spanned! {marker.span()=>
+                ::serde_versioned::__private::assert_marker::<#marker>();
+            });
+
+            let alias = rename.name.value();
+            if !aliases.contains(&alias) {
+                let name = &rename.name;
+                field.attrs.push(syn::parse_quote!(#[serde(alias = #name)]));
+                aliases.push(alias);
+            }
+        }
+
+        if let Some(nested) = nested {
+            if !versions.is_empty() || fallback.is_some() || label.is_some() {
+                return Err(syn::Error::new_spanned(
+                    nested,
+                    "`nested` reads the versions of the field's type and can't be combined with `from(...)`, `fallback` or `label`",
+                ));
+            }
+            field.attrs.push(
+                syn::parse_quote!(#[serde(deserialize_with = "::serde_versioned::deserialize")]),
+            );
+            continue;
+        }
+
+        if versions.is_empty() && fallback.is_none() {
+            continue;
+        }
+
+        if versions.len() + usize::from(fallback.is_some()) > MAX_OLD_VERSIONS {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                format!("at most {MAX_OLD_VERSIONS} old versions are supported"),
+            ));
+        }
+
+        order::check_newest_first(versions.iter().map(|version| &version.marker))?;
+
+        let markers = versions
+            .iter()
+            .map(|version| version.listed_marker())
+            .chain(fallback);
+        let label = label.unwrap_or_else(|| syn::parse_quote!(()));
+        let path = quote! {
+            ::serde_versioned::LabeledVersions::<
+                #label,
+                ::serde_versioned::Ver<::serde_versioned::Current>,
+                #(::serde_versioned::Ver<#markers>,)*
+            >::deserialize
+        };
+        let path = LitStr::new(&path.to_string(), field.ty.span());
+        field
+            .attrs
+            .push(syn::parse_quote!(#[serde(deserialize_with = #path)]));
+
+        let ty = &field.ty;
+        checks.extend(versions.iter().map(|version| {
+            let marker = &version.marker;
+            let shape = &version.shape;
+            let cfg = &version.cfg;
+            quote_spanned! {marker.span()=>
+                #cfg
+                ::serde_versioned::__private::assert_from_version::<#ty, #marker, #label, #shape>();
+            }
+        }));
+    }
+
+    if checks.is_empty() {
+        return Ok(quote!(#input));
+    }
+
+    let (impl_generics, _, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #input
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check_versions #impl_generics () #where_clause {
+                #(#checks)*
+            }
+        };
+    })
+}
@@ -0,0 +1,95 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, LitStr};
+
+use crate::any_version::variant_ident;
+use crate::attr::{Fixture, Version};
+use crate::legacy::same;
+
+/// Generates a test per fixture, checking that it still deserializes as the
+/// `VersionType` of its version, converts, and is accepted when probing.
+pub fn expand(
+    input: &DeriveInput,
+    versions: &[Version],
+    fixtures: &[Fixture],
+) -> syn::Result<TokenStream> {
+    if fixtures.is_empty() {
+        return Ok(TokenStream::new());
+    }
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`fixtures(...)` does not support generic types",
+        ));
+    }
+
+    let ident = &input.ident;
+    let mut tests = TokenStream::new();
+    for fixture in fixtures {
+        let marker = &fixture.marker;
+        let Some(version) = versions
+            .iter()
+            .find(|version| same(&version.marker, marker))
+        else {
+            return Err(syn::Error::new_spanned(
+                marker,
+                "version is not listed in `from(...)` or `generate(...)`",
+            ));
+        };
+
+        let shape = &version.shape;
+        let cfg = &version.cfg;
+        let path = &fixture.path;
+        let name = format_ident!(
+            "{}_fixture_{}",
+            snake_case(&ident.to_string()),
+            snake_case(&variant_ident(marker)?.to_string())
+        );
+        let describe = |what: &str| {
+            LitStr::new(
+                &format!("{} fixture {} failed to {what}", ident, path.value()),
+                path.span(),
+            )
+        };
+        let (deserialize, convert, probe) = (
+            describe("deserialize"),
+            describe("convert"),
+            describe("probe"),
+        );
+
+        tests.extend(quote! {
+            #[cfg(test)]
+            #cfg
+            #[test]
+            fn #name() {
+                let fixture = ::std::include_str!(::std::concat!(
+                    ::std::env!("CARGO_MANIFEST_DIR"),
+                    "/",
+                    #path
+                ));
+
+                let v: #shape = ::serde_versioned::__private::serde_json::from_str(fixture).expect(#deserialize);
+                <#ident as ::serde_versioned::FromVersion<::serde_versioned::Ver<#marker>>>::convert(v)
+                    .expect(#convert);
+
+                let mut d = ::serde_versioned::__private::serde_json::Deserializer::from_str(fixture);
+                <#ident as ::serde_versioned::DeserializeVersioned>::deserialize_any_version(&mut d)
+                    .expect(#probe);
+            }
+        });
+    }
+
+    Ok(tests)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
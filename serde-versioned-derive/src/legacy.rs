@@ -0,0 +1,482 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Data, DeriveInput, Fields, Ident, Index, Meta, Token, Type, Variant, Visibility,
+};
+
+use crate::attr::{Container, Dropped, FieldChanges, Kind, Version};
+
+/// Generates the `VersionType` of every `generate(...)` version along with its
+/// `FromVersion` impl, based on the change annotations of the fields and variants,
+/// and for the `downgrade(...)` versions an `IntoVersion` impl.
+pub fn expand(input: &DeriveInput, container: &Container) -> syn::Result<TokenStream> {
+    let (versions, removed, dropped) =
+        (&container.versions, &container.removed, &container.dropped);
+    let generate: Vec<&Version> = versions
+        .iter()
+        .filter(|version| version.kind == Kind::Generate)
+        .collect();
+
+    let data = match &input.data {
+        Data::Struct(data) => Shape::Struct(Item::new(&data.fields)?),
+        Data::Enum(data) => Shape::Enum(
+            data.variants
+                .iter()
+                .map(|variant| {
+                    Ok((
+                        variant,
+                        FieldChanges::from_attrs(&variant.attrs)?,
+                        Item::new(&variant.fields)?,
+                    ))
+                })
+                .collect::<syn::Result<_>>()?,
+        ),
+        Data::Union(_) if generate.is_empty() => return Ok(TokenStream::new()),
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`generate(...)` is not supported on unions",
+            ))
+        }
+    };
+
+    let mut markers: Vec<&Type> = removed.iter().map(|removed| &removed.marker).collect();
+    markers.extend(dropped.iter().map(|dropped| &dropped.marker));
+    markers.extend(&container.downgrade);
+    match &data {
+        Shape::Struct(item) => markers.extend(item.markers()),
+        Shape::Enum(variants) => {
+            for (variant, changes, item) in variants {
+                if let Some(change) = changes.changed.first() {
+                    if !is_newtype(&variant.fields) {
+                        return Err(syn::Error::new_spanned(
+                            &change.marker,
+                            "`changed(...)` on a variant requires a newtype variant",
+                        ));
+                    }
+                }
+                markers.extend(changes.markers());
+                markers.extend(item.markers());
+            }
+        }
+    }
+    if !removed.is_empty() && matches!(data, Shape::Struct(_)) {
+        return Err(syn::Error::new_spanned(
+            &removed[0].ident,
+            "`removed(...)` is only supported on enums",
+        ));
+    }
+    match (&data, dropped.first(), container.downgrade.first()) {
+        (Shape::Enum(_), Some(dropped), _) => {
+            return Err(syn::Error::new_spanned(
+                &dropped.ident,
+                "`dropped(...)` is only supported on structs",
+            ))
+        }
+        (Shape::Enum(_), _, Some(marker)) => {
+            return Err(syn::Error::new_spanned(
+                marker,
+                "`downgrade(...)` is only supported on structs",
+            ))
+        }
+        (Shape::Struct(item), Some(dropped), _) if !matches!(item.fields, Fields::Named(_)) => {
+            return Err(syn::Error::new_spanned(
+                &dropped.ident,
+                "`dropped(...)` requires named fields",
+            ))
+        }
+        _ => {}
+    }
+    for marker in markers {
+        if !generate.iter().any(|version| same(&version.marker, marker)) {
+            return Err(syn::Error::new_spanned(
+                marker,
+                "version is not listed in `generate(...)`",
+            ));
+        }
+    }
+
+    if generate.is_empty() {
+        return Ok(TokenStream::new());
+    }
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "`generate(...)` does not support generic types",
+        ));
+    }
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let serde_attrs = serde_attrs(&input.attrs);
+
+    let mut tokens = TokenStream::new();
+    for version in &generate {
+        let marker = &version.marker;
+        let shape = shape_ident(&version.shape)?;
+
+        let downgrade = container
+            .downgrade
+            .iter()
+            .any(|downgrade| same(downgrade, marker));
+        let (definition, conversion) = match &data {
+            Shape::Struct(item) => {
+                let dropped: Vec<&Dropped> = dropped
+                    .iter()
+                    .filter(|dropped| same(&dropped.marker, marker))
+                    .collect();
+                let legacy = item.legacy(marker, vis, &dropped);
+                if downgrade {
+                    let construct = &legacy.downgrade;
+                    tokens.extend(quote! {
+                        impl ::serde_versioned::IntoVersion<::serde_versioned::Ver<#marker>> for #ident {
+                            type VersionType = #shape;
+                            type Error = ::serde_versioned::ConvertError;
+
+                            fn convert(&self) -> ::std::result::Result<#shape, Self::Error> {
+                                ::std::result::Result::Ok(#shape #construct)
+                            }
+                        }
+                    });
+                }
+                let (body, pattern, construct) = (&legacy.body, &legacy.pattern, &legacy.construct);
+                let semi = match item.fields {
+                    Fields::Named(_) => None,
+                    _ => Some(quote!(;)),
+                };
+                (
+                    quote!(#vis struct #shape #body #semi),
+                    quote! {
+                        let #shape #pattern = v;
+                        ::std::result::Result::Ok(#ident #construct)
+                    },
+                )
+            }
+            Shape::Enum(variants) => {
+                let mut definitions = Vec::new();
+                let mut arms = Vec::new();
+                for (variant, changes, item) in variants {
+                    if changes.added.iter().any(|added| same(added, marker)) {
+                        continue;
+                    }
+
+                    let name = &variant.ident;
+                    let change = changes
+                        .changed
+                        .iter()
+                        .find(|change| same(&change.marker, marker));
+                    let attrs = legacy_attrs(&variant.attrs, changes, marker, change.is_some());
+                    let (body, pattern, construct) = match change {
+                        Some(change) => {
+                            let payload = &change.shape;
+                            (
+                                quote!((#payload)),
+                                quote!((payload)),
+                                quote!(
+                                    (::std::convert::TryFrom::try_from(payload)
+                                        .map_err(::serde_versioned::ConvertError::new)?)
+                                ),
+                            )
+                        }
+                        None => {
+                            let legacy = item.legacy(marker, vis, &[]);
+                            (legacy.body, legacy.pattern, legacy.construct)
+                        }
+                    };
+
+                    definitions.push(quote!(#(#attrs)* #name #body));
+                    arms.push(quote! {
+                        #shape::#name #pattern => ::std::result::Result::Ok(#ident::#name #construct)
+                    });
+                }
+
+                for removed in removed
+                    .iter()
+                    .filter(|removed| same(&removed.marker, marker))
+                {
+                    let name = &removed.ident;
+                    let attrs = &removed.attrs;
+                    let fields = &removed.fields;
+                    let bindings = bindings(fields);
+                    let pattern = match fields {
+                        Fields::Named(_) => quote!({ #(#bindings),* }),
+                        Fields::Unnamed(_) => quote!((#(#bindings),*)),
+                        Fields::Unit => TokenStream::new(),
+                    };
+                    let converter = &removed.converter;
+
+                    definitions.push(quote!(#(#attrs)* #name #fields));
+                    arms.push(quote! {
+                        #shape::#name #pattern => #converter(#(#bindings),*)
+                            .map_err(::serde_versioned::ConvertError::new)
+                    });
+                }
+
+                (
+                    quote!(#vis enum #shape { #(#definitions,)* }),
+                    quote!(match v { #(#arms,)* }),
+                )
+            }
+        };
+
+        let serialize = downgrade.then(|| quote!(::serde_versioned::__private::serde::Serialize,));
+        tokens.extend(quote! {
+            #[derive(#serialize ::serde_versioned::__private::serde::Deserialize)]
+            #[serde(crate = "::serde_versioned::__private::serde")]
+            #(#serde_attrs)*
+            #definition
+
+            impl ::serde_versioned::FromVersion<::serde_versioned::Ver<#marker>> for #ident {
+                type VersionType = #shape;
+                type Error = ::serde_versioned::ConvertError;
+
+                fn convert(v: Self::VersionType) -> ::std::result::Result<Self, Self::Error> {
+                    #conversion
+                }
+            }
+        });
+    }
+
+    Ok(tokens)
+}
+
+enum Shape<'a> {
+    Struct(Item<'a>),
+    Enum(Vec<(&'a Variant, FieldChanges, Item<'a>)>),
+}
+
+/// The fields of a struct or enum variant along with their change annotations.
+struct Item<'a> {
+    fields: &'a Fields,
+    changes: Vec<FieldChanges>,
+}
+
+/// The fields of a struct or enum variant in an old version.
+struct Legacy {
+    /// The field definitions, e.g. `{ a: u32 }` or `(u32)`.
+    body: TokenStream,
+    /// Destructures the old fields into bindings.
+    pattern: TokenStream,
+    /// Builds the current fields from the bindings.
+    construct: TokenStream,
+    /// Builds the old fields from `self`.
+    downgrade: TokenStream,
+}
+
+impl<'a> Item<'a> {
+    fn new(fields: &'a Fields) -> syn::Result<Self> {
+        let changes = fields
+            .iter()
+            .map(|field| {
+                let changes = FieldChanges::from_attrs(&field.attrs)?;
+                if field.ident.is_none() {
+                    if let Some(rename) = changes.renamed.first() {
+                        return Err(syn::Error::new_spanned(
+                            &rename.name,
+                            "`renamed(...)` requires a named field",
+                        ));
+                    }
+                }
+                Ok(changes)
+            })
+            .collect::<syn::Result<_>>()?;
+        Ok(Item { fields, changes })
+    }
+
+    fn markers(&self) -> impl Iterator<Item = &Type> {
+        self.changes.iter().flat_map(FieldChanges::markers)
+    }
+
+    fn legacy(&self, marker: &Type, vis: &Visibility, dropped: &[&Dropped]) -> Legacy {
+        let mut definitions = Vec::new();
+        let mut bound = Vec::new();
+        let mut values = Vec::new();
+        let mut old_names = Vec::new();
+        let mut old_values = Vec::new();
+
+        for (i, ((field, changes), binding)) in self
+            .fields
+            .iter()
+            .zip(&self.changes)
+            .zip(bindings(self.fields))
+            .enumerate()
+        {
+            if changes.added.iter().any(|added| same(added, marker)) {
+                values.push(quote!(::std::default::Default::default()));
+                continue;
+            }
+
+            let change = changes
+                .changed
+                .iter()
+                .find(|change| same(&change.marker, marker));
+            let attrs = legacy_attrs(&field.attrs, changes, marker, change.is_some());
+
+            let vis = &field.vis;
+            let ty = change.map_or(&field.ty, |change| &change.shape);
+            definitions.push(match &field.ident {
+                Some(name) => quote!(#(#attrs)* #vis #name: #ty),
+                None => quote!(#(#attrs)* #vis #ty),
+            });
+
+            values.push(match change {
+                Some(_) => quote! {
+                    ::std::convert::TryFrom::try_from(#binding)
+                        .map_err(::serde_versioned::ConvertError::new)?
+                },
+                None => quote!(#binding),
+            });
+            let member = match &field.ident {
+                Some(name) => quote!(#name),
+                None => Index::from(i).into_token_stream(),
+            };
+            let current = quote!(::std::clone::Clone::clone(&self.#member));
+            old_values.push(match change {
+                Some(_) => quote! {
+                    ::std::convert::TryFrom::try_from(#current)
+                        .map_err(::serde_versioned::ConvertError::new)?
+                },
+                None => current,
+            });
+            old_names.push(binding.clone());
+            bound.push(binding);
+        }
+
+        for dropped in dropped {
+            let Dropped {
+                attrs,
+                ident,
+                ty,
+                converter,
+                ..
+            } = dropped;
+            definitions.push(quote!(#(#attrs)* #vis #ident: #ty));
+            old_names.push(ident.clone());
+            old_values.push(quote!(#converter(self)));
+        }
+        let rest = (!dropped.is_empty()).then(|| quote!(..));
+
+        match self.fields {
+            Fields::Named(_) => {
+                let names = self.fields.iter().map(|field| &field.ident);
+                Legacy {
+                    body: quote!({ #(#definitions,)* }),
+                    pattern: quote!({ #(#bound,)* #rest }),
+                    construct: quote!({ #(#names: #values,)* }),
+                    downgrade: quote!({ #(#old_names: #old_values,)* }),
+                }
+            }
+            Fields::Unnamed(_) => Legacy {
+                body: quote!((#(#definitions,)*)),
+                pattern: quote!((#(#bound,)*)),
+                construct: quote!((#(#values,)*)),
+                downgrade: quote!((#(#old_values,)*)),
+            },
+            Fields::Unit => Legacy {
+                body: TokenStream::new(),
+                pattern: TokenStream::new(),
+                construct: TokenStream::new(),
+                downgrade: TokenStream::new(),
+            },
+        }
+    }
+}
+
+impl FieldChanges {
+    fn markers(&self) -> impl Iterator<Item = &Type> {
+        self.added
+            .iter()
+            .chain(self.renamed.iter().map(|rename| &rename.marker))
+            .chain(self.changed.iter().map(|change| &change.marker))
+    }
+}
+
+/// The variable names the fields are bound to when destructuring.
+fn bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => ident.clone(),
+            None => format_ident!("field{}", i),
+        })
+        .collect()
+}
+
+/// The serde attributes to use in an old version: those of the current
+/// definition, with the old name if it was different, and without `with`,
+/// `serialize_with` and `deserialize_with` if the type changed.
+fn legacy_attrs(
+    attrs: &[Attribute],
+    changes: &FieldChanges,
+    marker: &Type,
+    changed: bool,
+) -> Vec<TokenStream> {
+    let rename = changes
+        .renamed
+        .iter()
+        .find(|rename| same(&rename.marker, marker));
+    let dropped = |meta: &Meta| {
+        let path = meta.path();
+        (rename.is_some() && path.is_ident("rename"))
+            || (changed
+                && ["with", "serialize_with", "deserialize_with"]
+                    .iter()
+                    .any(|key| path.is_ident(key)))
+    };
+
+    let mut legacy: Vec<_> = attrs
+        .iter()
+        .filter(|attr| is_serde_attr(attr))
+        .filter_map(|attr| {
+            let Ok(metas) = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            else {
+                return Some(attr.to_token_stream());
+            };
+            let kept: Vec<_> = metas.iter().filter(|meta| !dropped(meta)).collect();
+            (!kept.is_empty()).then(|| quote!(#[serde(#(#kept),*)]))
+        })
+        .collect();
+    if let Some(rename) = rename {
+        let name = &rename.name;
+        legacy.push(quote!(#[serde(rename = #name)]));
+    }
+    legacy
+}
+
+fn serde_attrs(attrs: &[Attribute]) -> Vec<TokenStream> {
+    attrs
+        .iter()
+        .filter(|attr| is_serde_attr(attr))
+        .map(ToTokens::to_token_stream)
+        .collect()
+}
+
+fn is_serde_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("serde")
+}
+
+fn is_newtype(fields: &Fields) -> bool {
+    matches!(fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1)
+}
+
+fn shape_ident(shape: &Type) -> syn::Result<&Ident> {
+    if let Type::Path(path) = shape {
+        if path.qself.is_none() {
+            if let Some(ident) = path.path.get_ident() {
+                return Ok(ident);
+            }
+        }
+    }
+
+    Err(syn::Error::new_spanned(
+        shape,
+        "expected the name of the type to generate",
+    ))
+}
+
+pub fn same(a: &Type, b: &Type) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
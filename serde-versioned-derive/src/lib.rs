@@ -0,0 +1,101 @@
+//! Derive macros for `serde-versioned`. Use the re-exports from the main crate.
+
+mod any_version;
+mod attr;
+mod fields;
+mod fixtures;
+mod legacy;
+mod order;
+mod versioned;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Implements `FromVersion` for every declared old version, using the type's
+/// `From`/`TryFrom` impls as converters, and implements `DeserializeVersioned`.
+///
+/// Versions listed in `generate(...)` instead get their `VersionType` generated
+/// from the `added(...)`, `renamed(...)` and `changed(...)` annotations on the
+/// fields and enum variants. Enum variants that were split or dropped are
+/// declared with `removed(Marker = Variant(..) => converter)`. Struct fields
+/// that only exist in an old version are declared with
+/// `dropped(Marker = name: Type => converter)`. With `downgrade(Marker, ...)`
+/// the current type also implements `IntoVersion` for those generated versions,
+/// deriving each dropped field from the current value with its converter.
+///
+/// An entry of `from(...)` preceded by `#[cfg(...)]`, e.g.
+/// `from(#[cfg(feature = "legacy")] Num<1> = PointV1)`, is only attempted in
+/// builds matching the predicate, so its shape and converter can be compiled
+/// out along with it. It's listed as `Gated` in `Versions`.
+///
+/// Versions listed in `chain(V2, V1, ...)` are upgraded step-wise with `Upgrade`
+/// impls, `V1` into `V2` and `V2` into the current type.
+///
+/// With `remote = "Type"` the deriving type is a newtype mirror of a foreign
+/// type, and gets a `deserialize` function returning the foreign type for use
+/// with `#[serde(deserialize_with = "Mirror::deserialize")]`.
+///
+/// With `any_version = Name` an enum `Name` is generated with a `Current`
+/// variant and one variant per old version, named after its marker, e.g.
+/// `Num<1>` becomes `Num1`. It deserializes without converting the data.
+///
+/// With `fixtures(Marker = "path/to/fixture.json", ...)` a `#[cfg(test)]` test
+/// is generated per fixture, checking that it still deserializes as the
+/// `VersionType` of its version and converts. The tests use `serde_json`, and
+/// need the `json` feature of `serde_versioned`.
+///
+/// With `fallback` data matching no version deserializes as `Default::default()`,
+/// and with `fallback = Provider` as the value of a `FallbackValue` provider.
+///
+/// Versions with orderable markers, such as `Num<2>` or `Sem<1, 2, 0>`, must be
+/// listed newest first, as they are attempted in the order they are listed.
+///
+/// With `current = Marker` the current version gets a version tag, so the type
+/// can be read and written as an `Envelope` or `BinaryEnvelope`, or framed
+/// with `Framing`. The envelope's field names are set with
+/// `envelope(tag = "v", content = "payload")`, and `envelope(wrapper)` keys
+/// the data by the tag instead, e.g. `{"v3": ...}`. With `envelope(internal)` the
+/// tag is a field of the data itself, e.g. `{"version": "v3", "name": ...}`.
+/// A `wrapper` envelope takes no `tag` or `content`, and an `internal` one no
+/// `content`.
+/// A field of type `VersionStamp<Self>` is always written as the current tag,
+/// and only reads data with that tag.
+/// With `schema_ids(Num<3> = 101, ...)` it can be written in the Confluent wire
+/// format with the schema registry id of the version.
+///
+/// With `validate` the value is checked with its `Validate` impl, whichever
+/// version it was deserialized from.
+#[proc_macro_derive(Versioned, attributes(versioned))]
+pub fn derive_versioned(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    versioned::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Rewrites `#[versioned(from(Marker = Shape, ...))]` field attributes into the
+/// matching `#[serde(deserialize_with = "...")]` and checks at compile time that
+/// the field type implements `FromVersion` for every listed version.
+///
+/// `#[versioned(rename_from(Marker = "old_name", ...))]` also accepts the field
+/// under the names it had in old versions. The old names are serde aliases, so
+/// they are accepted in data of any version; for names that belong to a single
+/// version, generate its shape with `renamed(...)` in `#[derive(Versioned)]`.
+///
+/// `#[versioned(fallback)]` or `#[versioned(fallback = Provider)]` replaces a
+/// field value matching no version instead of failing the whole record.
+///
+/// `#[versioned(label = Label)]` converts the old versions with their
+/// `FromVersion<V, Label>` impls, so fields of the same type can migrate differently.
+///
+/// `#[versioned(nested)]` reads the field as any version of its own
+/// `#[derive(Versioned)]` type, e.g. in the `VersionType` of an old version,
+/// so old shapes nesting older shapes only declare the versions of each type
+/// once instead of one shape per combination.
+#[proc_macro_attribute]
+pub fn versioned(args: TokenStream, input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    fields::expand(args.into(), input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
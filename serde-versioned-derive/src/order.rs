@@ -0,0 +1,179 @@
+use quote::ToTokens;
+use syn::{Expr, GenericArgument, Lit, PathArguments, Type};
+
+/// Checks that the orderable markers, such as `Num<2>` and `Sem<1, 2, 0>`, are
+/// declared newest first, as they are attempted in declaration order.
+/// Markers are only compared with markers of the same kind.
+pub fn check_newest_first<'a>(markers: impl IntoIterator<Item = &'a Type>) -> syn::Result<()> {
+    let keyed: Vec<(&Type, Key)> = markers
+        .into_iter()
+        .filter_map(|marker| Some((marker, key(marker)?)))
+        .collect();
+
+    for (i, (marker, key)) in keyed.iter().enumerate() {
+        let older = keyed[..i]
+            .iter()
+            .find(|(_, earlier)| earlier.kind == key.kind && earlier.ordinal <= key.ordinal);
+        if let Some((older, _)) = older {
+            return Err(syn::Error::new_spanned(
+                marker,
+                format!(
+                    "versions must be declared newest first, `{}` is declared after `{}`",
+                    compact(marker),
+                    compact(older),
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Orders markers of the same kind.
+struct Key {
+    kind: String,
+    ordinal: Vec<u128>,
+}
+
+fn key(marker: &Type) -> Option<Key> {
+    let Type::Path(path) = marker else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    let args: Vec<&GenericArgument> = args.args.iter().collect();
+
+    let kind = segment.ident.to_string();
+    let (kind, ordinal) = match (kind.as_str(), args.as_slice()) {
+        ("Num" | "Epoch", [n]) => (kind, vec![int(n)?]),
+        ("Date", [y, m, d]) => (kind, vec![int(y)?, int(m)?, int(d)?]),
+        ("Sem", [a, b, c]) => (kind, vec![int(a)?, int(b)?, int(c)?, u128::MAX]),
+        ("Sem", [a, b, c, pre]) => (kind, vec![int(a)?, int(b)?, int(c)?, pre_release(pre)?]),
+        // Each namespace is ordered on its own.
+        ("Ns", [GenericArgument::Type(ns), n]) => (format!("Ns<{}>", compact(ns)), vec![int(n)?]),
+        _ => return None,
+    };
+    Some(Key { kind, ordinal })
+}
+
+/// The value of an integer literal argument.
+fn int(arg: &GenericArgument) -> Option<u128> {
+    match arg {
+        GenericArgument::Const(Expr::Lit(lit)) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Orders the pre-release argument of `Sem` below the release.
+fn pre_release(arg: &GenericArgument) -> Option<u128> {
+    let GenericArgument::Type(Type::Path(path)) = arg else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let rank = match segment.ident.to_string().as_str() {
+        "Release" => return Some(u128::MAX),
+        "Alpha" => 0,
+        "Beta" => 1,
+        "Rc" => 2,
+        _ => return None,
+    };
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            Some(rank << 32 | int(&args.args[0])?)
+        }
+        _ => None,
+    }
+}
+
+fn compact(tokens: &impl ToTokens) -> String {
+    tokens.to_token_stream().to_string().replace(' ', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn check(markers: &[Type]) -> Result<(), String> {
+        check_newest_first(markers).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_key() {
+        let ordinal = |marker: Type| key(&marker).map(|key| (key.kind, key.ordinal));
+
+        assert_eq!(ordinal(parse_quote!(Num<2>)), Some(("Num".into(), vec![2])));
+        assert_eq!(
+            ordinal(parse_quote!(serde_versioned::Date<2024, 3, 1>)),
+            Some(("Date".into(), vec![2024, 3, 1]))
+        );
+        assert_eq!(
+            ordinal(parse_quote!(Sem<1, 2, 0>)),
+            ordinal(parse_quote!(Sem<1, 2, 0, Release>))
+        );
+        assert_eq!(
+            ordinal(parse_quote!(Sem<1, 2, 0, Rc<1>>)),
+            Some(("Sem".into(), vec![1, 2, 0, 2 << 32 | 1]))
+        );
+        assert_eq!(
+            ordinal(parse_quote!(Ns<Billing, 3>)),
+            Some(("Ns<Billing>".into(), vec![3]))
+        );
+        assert_eq!(ordinal(parse_quote!(Uuid<7>)), None);
+        assert_eq!(ordinal(parse_quote!(Num<N>)), None);
+        assert_eq!(ordinal(parse_quote!(OldString)), None);
+    }
+
+    #[test]
+    fn test_check_newest_first() {
+        assert_eq!(check(&[parse_quote!(Num<2>), parse_quote!(Num<1>)]), Ok(()));
+        assert_eq!(
+            check(&[parse_quote!(Num<1>), parse_quote!(Num<2>)]),
+            Err(
+                "versions must be declared newest first, `Num<2>` is declared after `Num<1>`"
+                    .into()
+            )
+        );
+        assert_eq!(
+            check(&[
+                parse_quote!(Sem<1, 2, 0>),
+                parse_quote!(Sem<1, 2, 0, Rc<1>>)
+            ]),
+            Ok(())
+        );
+        assert_eq!(
+            check(&[parse_quote!(Sem<1, 2, 0, Beta<1>>), parse_quote!(Sem<1, 10, 0>)]),
+            Err(
+                "versions must be declared newest first, `Sem<1,10,0>` is declared after `Sem<1,2,0,Beta<1>>`"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_unorderable_markers_are_skipped() {
+        assert_eq!(
+            check(&[
+                parse_quote!(Uuid<1>),
+                parse_quote!(Num<2>),
+                parse_quote!(Uuid<9>),
+                parse_quote!(OldString),
+                parse_quote!(Date<2024, 1, 1>),
+                parse_quote!(Num<1>),
+                parse_quote!(Date<2023, 12, 31>),
+            ]),
+            Ok(())
+        );
+        // Markers of different kinds aren't compared.
+        assert_eq!(
+            check(&[parse_quote!(Num<1>), parse_quote!(Epoch<2>)]),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Member, Type};
+
+use crate::any_version;
+use crate::attr::{Container, Kind, SchemaId};
+use crate::fixtures;
+use crate::legacy;
+use crate::order;
+
+/// `Versions` has 32 slots and the current version takes one of them.
+pub const MAX_OLD_VERSIONS: usize = 31;
+
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::from_attrs(&input.attrs)?;
+
+    let fallbacks = usize::from(container.fallback.is_some());
+    if container.versions.len() + fallbacks > MAX_OLD_VERSIONS {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("at most {MAX_OLD_VERSIONS} old versions are supported"),
+        ));
+    }
+
+    order::check_newest_first(container.versions.iter().map(|version| &version.marker))?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause.predicates.push(
+        syn::parse_quote!(Self: for<'de> ::serde_versioned::__private::serde::Deserialize<'de>),
+    );
+
+    let legacy = legacy::expand(&input, &container)?;
+    let remote = match &container.remote {
+        Some(remote) => expand_remote(&input, remote)?,
+        None => TokenStream::new(),
+    };
+    let any_version = match &container.any_version {
+        Some(name) => any_version::expand(&input, name, &container.versions)?,
+        None => TokenStream::new(),
+    };
+    let fixtures = fixtures::expand(&input, &container.versions, &container.fixtures)?;
+
+    let from_versions = container
+        .versions
+        .iter()
+        .filter(|version| version.kind == Kind::Convert);
+    let from_versions = from_versions.map(|version| {
+        let marker = &version.marker;
+        let shape = &version.shape;
+        let cfg = &version.cfg;
+        quote! {
+            #cfg
+            impl #impl_generics ::serde_versioned::FromVersion<::serde_versioned::Ver<#marker>>
+                for #ident #ty_generics #where_clause
+            {
+                type VersionType = #shape;
+                type Error = <Self as ::std::convert::TryFrom<#shape>>::Error;
+
+                fn convert(v: Self::VersionType) -> ::std::result::Result<Self, Self::Error> {
+                    <Self as ::std::convert::TryFrom<#shape>>::try_from(v)
+                }
+            }
+        }
+    });
+
+    let markers = container
+        .versions
+        .iter()
+        .map(|version| version.listed_marker())
+        .chain(container.fallback.clone());
+    let (deserialize, from_slice, deserialize_detailed) = match container.validate {
+        true => (
+            quote!(deserialize_validated),
+            quote!(from_slice_validated),
+            quote!(deserialize_detailed_validated),
+        ),
+        false => (
+            quote!(deserialize),
+            quote!(from_slice),
+            quote!(deserialize_detailed),
+        ),
+    };
+    let versions = quote! {
+        ::serde_versioned::Versions<
+            ::serde_versioned::Ver<::serde_versioned::Current>,
+            #(::serde_versioned::Ver<#markers>,)*
+        >
+    };
+    let deserialize_cloned = match container.validate {
+        true => quote! {
+            ::serde_versioned::__private::validated(<#versions>::deserialize_cloned::<Self, D>(d)?)
+        },
+        false => quote!(<#versions>::deserialize_cloned(d)),
+    };
+    let deserialize_observed = match container.validate {
+        true => quote! {
+            ::serde_versioned::__private::validated(
+                <#versions>::deserialize_observed::<Self, D>(d, observer)?,
+            )
+        },
+        false => quote!(<#versions>::deserialize_observed(d, observer)),
+    };
+    let deserialize_with_policy = match container.validate {
+        true => quote! {
+            ::serde_versioned::__private::validated(
+                <#versions>::deserialize_with_policy::<Self, D>(d, policy)?,
+            )
+        },
+        false => quote!(<#versions>::deserialize_with_policy(d, policy)),
+    };
+    let deserialize_with_version = match container.validate {
+        true => quote! {
+            let (value, version) = <#versions>::deserialize_with_version::<Self, D>(d)?;
+            ::serde_versioned::__private::validated(value).map(|value| (value, version))
+        },
+        false => quote!(<#versions>::deserialize_with_version(d)),
+    };
+
+    if let (None, Some(envelope)) = (&container.current, &container.envelope) {
+        return Err(syn::Error::new_spanned(
+            envelope,
+            "`envelope(...)` requires `current = ...`",
+        ));
+    }
+    if let (None, Some(schema_id)) = (&container.current, container.schema_ids.first()) {
+        return Err(syn::Error::new_spanned(
+            &schema_id.marker,
+            "`schema_ids(...)` requires `current = ...`",
+        ));
+    }
+    // Versions are indexed from the oldest, so the current version comes last.
+    let current_index = container.versions.len() as u8;
+    let index_of = |marker: &Type| {
+        let name = quote!(#marker).to_string();
+        if container
+            .current
+            .iter()
+            .any(|current| quote!(#current).to_string() == name)
+        {
+            return Ok(current_index);
+        }
+        match container.versions.iter().position(|version| {
+            let marker = &version.marker;
+            quote!(#marker).to_string() == name
+        }) {
+            Some(position) => Ok(current_index - 1 - position as u8),
+            None => Err(syn::Error::new_spanned(
+                marker,
+                "not the current version or a version in `from(...)`",
+            )),
+        }
+    };
+    let schema_ids = container
+        .schema_ids
+        .iter()
+        .map(|SchemaId { marker, id }| {
+            let index = index_of(marker)?;
+            Ok(quote!((#id, #index)))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let current = container.current.as_ref().map(|marker| {
+        let envelope = container
+            .envelope
+            .as_ref()
+            .map(|envelope| quote!(const ENVELOPE: ::serde_versioned::EnvelopeLayout = #envelope;));
+        let schema_ids = (!schema_ids.is_empty()).then(|| {
+            quote! {
+                impl #impl_generics ::serde_versioned::SchemaIds for #ident #ty_generics #where_clause {
+                    const SCHEMA_IDS: &'static [(u32, u8)] = &[#(#schema_ids),*];
+                }
+            }
+        });
+        quote! {
+            impl #impl_generics ::serde_versioned::CurrentVersion for #ident #ty_generics #where_clause {
+                type Marker = #marker;
+                const INDEX: u8 = #current_index;
+                #envelope
+            }
+
+            #schema_ids
+        }
+    });
+    let (deserialize_tagged, deserialize_at) = match container.validate {
+        true => (
+            quote! {
+                ::serde_versioned::__private::validated(
+                    <#versions>::deserialize_tagged::<Self, D>(tag, d)?,
+                )
+            },
+            quote! {
+                ::serde_versioned::__private::validated(
+                    <#versions>::deserialize_at::<Self, D>(position.into(), d)?,
+                )
+            },
+        ),
+        false => (
+            quote!(<#versions>::deserialize_tagged(tag, d)),
+            quote!(<#versions>::deserialize_at(position.into(), d)),
+        ),
+    };
+    let deserialize_current = container.validate.then(|| {
+        quote! {
+            fn deserialize_current<'de, D>(d: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                ::serde_versioned::__private::validated(
+                    <Self as ::serde_versioned::__private::serde::Deserialize<'de>>::deserialize(d)?,
+                )
+            }
+        }
+    });
+    let deserialize_version = container.current.as_ref().map(|_| {
+        quote! {
+            fn deserialize_version<'de, D>(
+                tag: &str,
+                d: D,
+            ) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                #deserialize_tagged
+            }
+
+            fn deserialize_version_index<'de, D>(
+                index: u8,
+                d: D,
+            ) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                let ::std::option::Option::Some(position) = #current_index.checked_sub(index) else {
+                    return ::std::result::Result::Err(
+                        <D::Error as ::serde_versioned::__private::serde::de::Error>::custom(
+                            ::std::format_args!("unknown version index {index}"),
+                        ),
+                    );
+                };
+                #deserialize_at
+            }
+        }
+    });
+
+    Ok(quote! {
+        #legacy
+
+        #current
+
+        #remote
+
+        #any_version
+
+        #fixtures
+
+        #(#from_versions)*
+
+        impl #impl_generics ::serde_versioned::DeserializeVersioned
+            for #ident #ty_generics #where_clause
+        {
+            type Versions = #versions;
+
+            fn deserialize_any_version<'de, D>(d: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                <#versions>::#deserialize(d)
+            }
+
+            fn deserialize_cloned<'de, D>(d: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de> + ::std::clone::Clone,
+            {
+                #deserialize_cloned
+            }
+
+            fn deserialize_with_policy<'de, D>(
+                d: D,
+                policy: &::serde_versioned::VersionPolicy,
+            ) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                #deserialize_with_policy
+            }
+
+            fn deserialize_observed<'de, D>(
+                d: D,
+                observer: &dyn ::serde_versioned::MigrationObserver,
+            ) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                #deserialize_observed
+            }
+
+            fn deserialize_with_version<'de, D>(
+                d: D,
+            ) -> ::std::result::Result<(Self, ::serde_versioned::VersionId), D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                #deserialize_with_version
+            }
+
+            fn deserialize_detailed<'de, D>(
+                d: D,
+            ) -> ::std::result::Result<
+                ::std::result::Result<Self, ::serde_versioned::VersionErrors>,
+                D::Error,
+            >
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+            {
+                <#versions>::#deserialize_detailed(d)
+            }
+
+            fn from_slice_any_version<F: ::serde_versioned::Format>(
+                bytes: &[u8],
+            ) -> ::std::result::Result<Self, ::serde_versioned::VersionErrors> {
+                <#versions>::#from_slice::<F, Self>(bytes)
+            }
+
+            #deserialize_current
+
+            #deserialize_version
+        }
+    })
+}
+
+/// Adds a `deserialize` function to the mirror newtype that returns the remote
+/// type, matching the functions generated by `#[serde(remote = "...")]`.
+fn expand_remote(input: &DeriveInput, remote: &Type) -> syn::Result<TokenStream> {
+    let member = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => match &data.fields {
+            Fields::Named(fields) => Member::from(fields.named[0].ident.clone().unwrap()),
+            _ => Member::from(0),
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`remote = \"...\"` requires a struct with a single field of the remote type",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #vis fn deserialize<'de, D>(d: D) -> ::std::result::Result<#remote, D::Error>
+            where
+                D: ::serde_versioned::__private::serde::Deserializer<'de>,
+                Self: ::serde_versioned::DeserializeVersioned,
+            {
+                <Self as ::serde_versioned::DeserializeVersioned>::deserialize_any_version(d)
+                    .map(|mirror| mirror.#member)
+            }
+        }
+    })
+}
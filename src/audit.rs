@@ -0,0 +1,148 @@
+//! Values kept with the provenance of their conversion, for audits and replays.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::content::{Content, ContentRefDeserializer};
+use crate::{DeserializeVersioned, VersionId};
+
+/// A value read from any version, with the version it was read as and the
+/// data it was read from, e.g. where the provenance of converted data must be kept.
+/// Serializes as the original data, so it can be stored or replayed as it was received.
+#[derive(Debug, Clone)]
+pub struct Audited<T> {
+    value: T,
+    version: VersionId,
+    original: Content<'static>,
+}
+
+impl<T> Audited<T> {
+    /// The converted value
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The version the data was read as
+    pub fn version(&self) -> &VersionId {
+        &self.version
+    }
+
+    /// The data as it was received, before it was converted
+    pub fn original(&self) -> &Content<'static> {
+        &self.original
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    pub fn into_parts(self) -> (T, VersionId, Content<'static>) {
+        (self.value, self.version, self.original)
+    }
+}
+
+impl<'de, T: DeserializeVersioned> Deserialize<'de> for Audited<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let original = Content::deserialize(d)?;
+        let (value, version) =
+            T::deserialize_with_version(ContentRefDeserializer::<D::Error>::new(&original))?;
+        Ok(Audited {
+            value,
+            version,
+            original: original.into_owned(),
+        })
+    }
+}
+
+impl<T> Serialize for Audited<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.original.serialize(s)
+    }
+}
+
+/// A value read from any version, with the version it was read as, e.g. to
+/// re-verify records imported from an old version. Unlike `Audited`, the data
+/// isn't kept, and it serializes as the value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migrated<T> {
+    value: T,
+    source_version: VersionId,
+}
+
+impl<T> Migrated<T> {
+    /// The version the data was read as
+    pub fn source_version(&self) -> &VersionId {
+        &self.source_version
+    }
+
+    /// Whether the data was in an old version and converted
+    pub fn was_migrated(&self) -> bool {
+        !self.source_version.is_current()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> std::ops::Deref for Migrated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'de, T: DeserializeVersioned> Deserialize<'de> for Migrated<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (value, source_version) = T::deserialize_with_version(d)?;
+        Ok(Migrated {
+            value,
+            source_version,
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for Migrated<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::tests::Adjacent;
+
+    #[test]
+    fn test_audited() {
+        let old = r#"{"reading":"3"}"#;
+        let audited: Audited<Adjacent> = serde_json::from_str(old).unwrap();
+        assert_eq!(audited.value(), &Adjacent { value: 3 });
+        assert_eq!(audited.version().marker(), "Num<1>");
+        assert_eq!(serde_json::to_string(&audited).unwrap(), old);
+
+        let (value, version, original) = audited.into_parts();
+        assert_eq!(value, Adjacent { value: 3 });
+        assert!(!version.is_current());
+        assert_eq!(
+            original,
+            Content::Map(vec![(
+                Content::String("reading".to_owned()),
+                Content::String("3".to_owned())
+            )])
+        );
+    }
+
+    #[test]
+    fn test_migrated() {
+        let migrated: Migrated<Adjacent> = serde_json::from_str(r#"{"reading":"3"}"#).unwrap();
+        assert!(migrated.was_migrated());
+        assert_eq!(migrated.source_version().tag(), "v1");
+        assert_eq!(*migrated, Adjacent { value: 3 });
+        assert_eq!(serde_json::to_string(&migrated).unwrap(), r#"{"value":3}"#);
+
+        let current: Migrated<Adjacent> = serde_json::from_str(r#"{"value":3}"#).unwrap();
+        assert!(!current.was_migrated());
+        assert_eq!(current.into_inner(), Adjacent { value: 3 });
+    }
+}
@@ -0,0 +1,575 @@
+//! Byte-stable serialization, with map keys sorted and floats normalized, so
+//! payloads can be signed, hashed or deduplicated.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::ser::{self, Serialize, Serializer};
+
+use crate::content::Content;
+
+/// A value serialized canonically: maps and structs with their entries sorted
+/// by key, `-0.0` written as `0.0` and non-finite floats rejected, so equal
+/// values encode to the same bytes in any deterministic format.
+/// For an older version, wrap the value it converts into, e.g.
+/// `Canonical(&IntoVersion::<Ver<Num<2>>>::convert(&value)?)`.
+pub struct Canonical<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Canonical<'_, T> {
+    fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        to_canonical(self.0)
+            .map_err(ser::Error::custom)?
+            .serialize(s)
+    }
+}
+
+/// The value as canonical `Content`, see `Canonical`.
+pub fn to_canonical<T: Serialize + ?Sized>(value: &T) -> Result<Content<'static>> {
+    value.serialize(ContentSerializer)
+}
+
+/// Why a value can't be serialized canonically.
+#[derive(Debug)]
+pub struct CanonicalError(String);
+
+impl fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CanonicalError {}
+
+impl ser::Error for CanonicalError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CanonicalError(msg.to_string())
+    }
+}
+
+fn float(f: f64) -> Result<f64> {
+    match f.is_finite() {
+        true => Ok(f + 0.0),
+        false => Err(CanonicalError(format!(
+            "{f} has no canonical representation"
+        ))),
+    }
+}
+
+/// Orders keys by kind, then by value.
+fn cmp(a: &Content, b: &Content) -> Ordering {
+    fn rank(c: &Content) -> u8 {
+        match c {
+            Content::Bool(_) => 0,
+            Content::U8(_) | Content::U16(_) | Content::U32(_) | Content::U64(_) => 1,
+            Content::I8(_) | Content::I16(_) | Content::I32(_) | Content::I64(_) => 2,
+            Content::F32(_) | Content::F64(_) => 3,
+            Content::Char(_) | Content::String(_) | Content::Str(_) => 4,
+            Content::ByteBuf(_) | Content::Bytes(_) => 5,
+            Content::None | Content::Some(_) => 6,
+            Content::Unit => 7,
+            Content::Newtype(_) => 8,
+            Content::Seq(_) => 9,
+            Content::Map(_) => 10,
+        }
+    }
+
+    fn unsigned(c: &Content) -> u64 {
+        match *c {
+            Content::U8(n) => n.into(),
+            Content::U16(n) => n.into(),
+            Content::U32(n) => n.into(),
+            Content::U64(n) => n,
+            _ => 0,
+        }
+    }
+
+    fn signed(c: &Content) -> i64 {
+        match *c {
+            Content::I8(n) => n.into(),
+            Content::I16(n) => n.into(),
+            Content::I32(n) => n.into(),
+            Content::I64(n) => n,
+            _ => 0,
+        }
+    }
+
+    fn text(c: &Content) -> String {
+        match c {
+            Content::Char(ch) => ch.to_string(),
+            _ => c.as_str().unwrap_or_default().to_owned(),
+        }
+    }
+
+    fn bytes<'a>(c: &'a Content) -> &'a [u8] {
+        match c {
+            Content::ByteBuf(b) => b,
+            Content::Bytes(b) => b,
+            _ => &[],
+        }
+    }
+
+    fn all(a: &[Content], b: &[Content]) -> Ordering {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| cmp(a, b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    }
+
+    match (a, b) {
+        _ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+        (Content::Bool(a), Content::Bool(b)) => a.cmp(b),
+        (Content::F32(_) | Content::F64(_), _) => {
+            let as_f64 = |c: &Content| match *c {
+                Content::F32(f) => f64::from(f),
+                Content::F64(f) => f,
+                _ => 0.0,
+            };
+            as_f64(a).total_cmp(&as_f64(b))
+        }
+        (Content::Some(a), Content::Some(b)) | (Content::Newtype(a), Content::Newtype(b)) => {
+            cmp(a, b)
+        }
+        (Content::None, Content::Some(_)) => Ordering::Less,
+        (Content::Some(_), Content::None) => Ordering::Greater,
+        (Content::Seq(a), Content::Seq(b)) => all(a, b),
+        (Content::Map(a), Content::Map(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((ak, av), (bk, bv))| cmp(ak, bk).then_with(|| cmp(av, bv)))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => match rank(a) {
+            1 => unsigned(a).cmp(&unsigned(b)),
+            2 => signed(a).cmp(&signed(b)),
+            4 => text(a).cmp(&text(b)),
+            5 => bytes(a).cmp(bytes(b)),
+            _ => Ordering::Equal,
+        },
+    }
+}
+
+fn sorted(mut entries: Vec<(Content<'static>, Content<'static>)>) -> Content<'static> {
+    entries.sort_by(|(a, _), (b, _)| cmp(a, b));
+    Content::Map(entries)
+}
+
+/// Serializes a value into `Content`, as a self-describing format would read it.
+struct ContentSerializer;
+
+type Result<T> = std::result::Result<T, CanonicalError>;
+
+impl Serializer for ContentSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Content<'static>> {
+        Ok(Content::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Content<'static>> {
+        Ok(Content::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Content<'static>> {
+        Ok(Content::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Content<'static>> {
+        Ok(Content::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Content<'static>> {
+        Ok(Content::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Content<'static>> {
+        Ok(Content::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Content<'static>> {
+        Ok(Content::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Content<'static>> {
+        Ok(Content::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Content<'static>> {
+        Ok(Content::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Content<'static>> {
+        float(v.into()).map(|_| Content::F32(v + 0.0))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Content<'static>> {
+        float(v).map(Content::F64)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Content<'static>> {
+        Ok(Content::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Content<'static>> {
+        Ok(Content::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Content<'static>> {
+        Ok(Content::ByteBuf(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Content<'static>> {
+        Ok(Content::None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Content<'static>> {
+        value.serialize(self).map(|v| Content::Some(Box::new(v)))
+    }
+
+    fn serialize_unit(self) -> Result<Content<'static>> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Content<'static>> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Content<'static>> {
+        Ok(Content::Str(variant))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Content<'static>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Content<'static>> {
+        Ok(Content::Map(vec![(
+            Content::Str(variant),
+            value.serialize(self)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            variant: None,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            elements: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            entries: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+}
+
+/// Wraps the data of a variant as a map keyed by its name, as JSON does.
+fn variant(variant: Option<&'static str>, data: Content<'static>) -> Content<'static> {
+    match variant {
+        Some(variant) => Content::Map(vec![(Content::Str(variant), data)]),
+        None => data,
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    elements: Vec<Content<'static>>,
+}
+
+impl SeqSerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.elements.push(value.serialize(ContentSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Content<'static>> {
+        Ok(variant(self.variant, Content::Seq(self.elements)))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    entries: Vec<(Content<'static>, Content<'static>)>,
+    key: Option<Content<'static>>,
+}
+
+impl MapSerializer {
+    fn finish(self) -> Result<Content<'static>> {
+        Ok(variant(self.variant, sorted(self.entries)))
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(ContentSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let Some(key) = self.key.take() else {
+            return Err(ser::Error::custom(
+                "serialize_value called before serialize_key",
+            ));
+        };
+        self.entries
+            .push((key, value.serialize(ContentSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entries
+            .push((Content::Str(key), value.serialize(ContentSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Content<'static>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entries
+            .push((Content::Str(key), value.serialize(ContentSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content<'static>> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct Point {
+        y: f64,
+        x: f64,
+        tags: HashMap<String, u32>,
+    }
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Circle { r: f32, center: (i32, i32) },
+        Line(u8, u8),
+    }
+
+    #[test]
+    fn test_sorted() {
+        let point = Point {
+            y: -0.0,
+            x: 1.5,
+            tags: HashMap::from([
+                ("b".to_owned(), 2),
+                ("a".to_owned(), 1),
+                ("c".to_owned(), 3),
+            ]),
+        };
+        let json = serde_json::to_string(&Canonical(&point)).unwrap();
+        assert_eq!(json, r#"{"tags":{"a":1,"b":2,"c":3},"x":1.5,"y":0.0}"#);
+
+        let circle = Shape::Circle {
+            r: -0.0,
+            center: (1, -1),
+        };
+        let json = serde_json::to_string(&Canonical(&circle)).unwrap();
+        assert_eq!(json, r#"{"Circle":{"center":[1,-1],"r":0.0}}"#);
+        let json = serde_json::to_string(&Canonical(&Shape::Line(2, 1))).unwrap();
+        assert_eq!(json, r#"{"Line":[2,1]}"#);
+    }
+
+    #[test]
+    fn test_key_order() {
+        let mixed = [
+            (Content::Str("a"), Content::Unit),
+            (Content::I8(-1), Content::Unit),
+            (Content::U64(10), Content::Unit),
+            (Content::U8(2), Content::Unit),
+            (Content::Bool(true), Content::Unit),
+            (Content::Char('b'), Content::Unit),
+        ];
+        let Content::Map(entries) = sorted(mixed.to_vec()) else {
+            unreachable!()
+        };
+        let keys: Vec<_> = entries.into_iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            [
+                Content::Bool(true),
+                Content::U8(2),
+                Content::U64(10),
+                Content::I8(-1),
+                Content::Str("a"),
+                Content::Char('b'),
+            ]
+        );
+        assert_eq!(
+            cmp(&Content::None, &Content::Some(Box::new(Content::Unit))),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp(
+                &Content::Seq(vec![Content::U8(1)]),
+                &Content::Seq(vec![Content::U8(1), Content::U8(0)])
+            ),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_non_finite() {
+        for f in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let err = to_canonical(&f).unwrap_err();
+            assert!(err.to_string().ends_with("has no canonical representation"));
+        }
+        assert!(to_canonical(&[1.0, f64::NAN]).is_err());
+        assert!(serde_json::to_string(&Canonical(&Some(f32::NAN))).is_err());
+        assert!(matches!(
+            to_canonical(&-0.0f64).unwrap(),
+            Content::F64(f) if f.is_sign_positive()
+        ));
+    }
+}
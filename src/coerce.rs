@@ -0,0 +1,208 @@
+//! Versions for the usual drift of hand-written config files, where values
+//! used to be written as strings.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{FromVersion, Ver, VersionInfo};
+
+/// A version where the value was written as a string, e.g. `"8080"`,
+/// parsed with `FromStr`.
+pub struct Quoted;
+
+impl<T, Label> FromVersion<Ver<Quoted>, Label> for T
+where
+    for<'a> T: serde::Deserialize<'a>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type VersionType = String;
+    type Error = T::Err;
+
+    fn convert(v: String) -> Result<Self, Self::Error> {
+        v.trim().parse()
+    }
+}
+
+impl VersionInfo for Quoted {
+    fn name() -> Cow<'static, str> {
+        "quoted".into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+/// A version where a bool was written as `"yes"` or `"no"`, or `"on"`, `"off"`,
+/// `"true"`, `"false"`, `"y"` or `"n"`, in any case.
+pub struct YesNo;
+
+impl<Label> FromVersion<Ver<YesNo>, Label> for bool {
+    type VersionType = String;
+    type Error = CoerceError;
+
+    fn convert(v: String) -> Result<Self, Self::Error> {
+        match v.trim().to_ascii_lowercase().as_str() {
+            "yes" | "y" | "on" | "true" => Ok(true),
+            "no" | "n" | "off" | "false" => Ok(false),
+            _ => Err(CoerceError::new("a boolean", &v)),
+        }
+    }
+}
+
+impl VersionInfo for YesNo {
+    fn name() -> Cow<'static, str> {
+        "yes/no".into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+/// A version where a duration was written as a string of whole amounts with
+/// units, e.g. `"5m"`, `"1h30m"` or `"250ms"`.
+/// The units are `d`, `h`, `m`, `s`, `ms`, `us` and `ns`.
+pub struct HumanDuration;
+
+impl<Label> FromVersion<Ver<HumanDuration>, Label> for Duration {
+    type VersionType = String;
+    type Error = CoerceError;
+
+    fn convert(v: String) -> Result<Self, Self::Error> {
+        parse_duration(v.trim()).ok_or_else(|| CoerceError::new("a duration", &v))
+    }
+}
+
+impl VersionInfo for HumanDuration {
+    fn name() -> Cow<'static, str> {
+        "duration".into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+fn parse_duration(mut s: &str) -> Option<Duration> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut total = Duration::ZERO;
+    while !s.is_empty() {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let amount: u64 = s[..digits].parse().ok()?;
+        s = &s[digits..];
+        let unit = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let part = match &s[..unit] {
+            "d" => Duration::from_secs(amount.checked_mul(24 * 60 * 60)?),
+            "h" => Duration::from_secs(amount.checked_mul(60 * 60)?),
+            "m" => Duration::from_secs(amount.checked_mul(60)?),
+            "s" => Duration::from_secs(amount),
+            "ms" => Duration::from_millis(amount),
+            "us" => Duration::from_micros(amount),
+            "ns" => Duration::from_nanos(amount),
+            _ => return None,
+        };
+        s = &s[unit..];
+        total = total.checked_add(part)?;
+    }
+    Some(total)
+}
+
+/// The error of a string that doesn't hold the value its version expects.
+#[derive(Debug)]
+pub struct CoerceError {
+    expected: &'static str,
+    found: String,
+}
+
+impl CoerceError {
+    fn new(expected: &'static str, found: &str) -> Self {
+        CoerceError {
+            expected,
+            found: found.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for CoerceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found `{}`", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for CoerceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Current, Versions};
+
+    fn quoted(json: &str) -> Result<(u16, String), serde_json::Error> {
+        let d = &mut serde_json::Deserializer::from_str(json);
+        Versions::<Ver<Current>, Ver<Quoted>>::deserialize_with_version(d)
+            .map(|(value, version)| (value, version.tag().to_owned()))
+    }
+
+    fn yes_no(json: &str) -> Result<(bool, String), serde_json::Error> {
+        let d = &mut serde_json::Deserializer::from_str(json);
+        Versions::<Ver<Current>, Ver<Quoted>, Ver<YesNo>>::deserialize_with_version(d)
+            .map(|(value, version)| (value, version.tag().to_owned()))
+    }
+
+    #[test]
+    fn test_quoted() {
+        assert_eq!(quoted("8080").unwrap(), (8080, "current".into()));
+        assert_eq!(quoted(r#"" 8080 ""#).unwrap(), (8080, "quoted".into()));
+        assert!(quoted(r#""80800""#).is_err());
+        assert_eq!(
+            <u16 as FromVersion<Ver<Quoted>>>::convert("port".into())
+                .unwrap_err()
+                .to_string(),
+            "invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_yes_no() {
+        assert_eq!(yes_no("true").unwrap(), (true, "current".into()));
+        // `bool` is `FromStr` too, so "true" and "false" are `Quoted`.
+        assert_eq!(yes_no(r#""false""#).unwrap(), (false, "quoted".into()));
+        for (text, value) in [
+            ("Yes", true),
+            ("ON", true),
+            ("y", true),
+            ("no", false),
+            ("Off", false),
+        ] {
+            let json = format!(r#""{text}""#);
+            assert_eq!(yes_no(&json).unwrap(), (value, "yes/no".into()));
+        }
+        assert_eq!(
+            <bool as FromVersion<Ver<YesNo>>>::convert("maybe".into())
+                .unwrap_err()
+                .to_string(),
+            "expected a boolean, found `maybe`"
+        );
+    }
+
+    #[test]
+    fn test_human_duration() {
+        let parse = |s: &str| <Duration as FromVersion<Ver<HumanDuration>>>::convert(s.into());
+        assert_eq!(parse("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse(" 1h30m ").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse("2d").unwrap(), Duration::from_secs(2 * 24 * 60 * 60));
+        assert_eq!(parse("1s250ms").unwrap(), Duration::from_millis(1250));
+        assert_eq!(parse("10us5ns").unwrap(), Duration::from_nanos(10_005));
+        for invalid in ["", "5", "m", "5x", "1.5h", "99999999999999999999d"] {
+            assert_eq!(
+                parse(invalid).unwrap_err().to_string(),
+                format!("expected a duration, found `{invalid}`")
+            );
+        }
+    }
+}
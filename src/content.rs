@@ -0,0 +1,834 @@
+//! A buffered representation of any self-describing value, so the same input
+//! can be deserialized once per version without depending on serde's private
+//! `Content` type.
+
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess,
+    SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// A value buffered from a deserializer.
+/// Strings and bytes borrow from the input when the format allows it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content<'de> {
+    Bool(bool),
+
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+
+    F32(f32),
+    F64(f64),
+
+    Char(char),
+    String(String),
+    Str(&'de str),
+    ByteBuf(Vec<u8>),
+    Bytes(&'de [u8]),
+
+    None,
+    Some(Box<Content<'de>>),
+
+    Unit,
+    Newtype(Box<Content<'de>>),
+    Seq(Vec<Content<'de>>),
+    Map(Vec<(Content<'de>, Content<'de>)>),
+}
+
+impl<'de> Content<'de> {
+    /// The string, if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Content::String(s) => Some(s),
+            Content::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Copies any borrowed strings and bytes, detaching the value from the input.
+    pub fn into_owned(self) -> Content<'static> {
+        match self {
+            Content::Bool(b) => Content::Bool(b),
+            Content::U8(n) => Content::U8(n),
+            Content::U16(n) => Content::U16(n),
+            Content::U32(n) => Content::U32(n),
+            Content::U64(n) => Content::U64(n),
+            Content::I8(n) => Content::I8(n),
+            Content::I16(n) => Content::I16(n),
+            Content::I32(n) => Content::I32(n),
+            Content::I64(n) => Content::I64(n),
+            Content::F32(f) => Content::F32(f),
+            Content::F64(f) => Content::F64(f),
+            Content::Char(c) => Content::Char(c),
+            Content::String(s) => Content::String(s),
+            Content::Str(s) => Content::String(s.to_owned()),
+            Content::ByteBuf(b) => Content::ByteBuf(b),
+            Content::Bytes(b) => Content::ByteBuf(b.to_owned()),
+            Content::None => Content::None,
+            Content::Some(v) => Content::Some(Box::new(v.into_owned())),
+            Content::Unit => Content::Unit,
+            Content::Newtype(v) => Content::Newtype(Box::new(v.into_owned())),
+            Content::Seq(v) => Content::Seq(v.into_iter().map(Content::into_owned).collect()),
+            Content::Map(v) => Content::Map(
+                v.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The value of `key`, if this is a map with that string key.
+    pub fn get(&self, key: &str) -> Option<&Content<'de>> {
+        match self {
+            Content::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+impl Content<'_> {
+    #[cold]
+    fn unexpected(&self) -> Unexpected<'_> {
+        match *self {
+            Content::Bool(b) => Unexpected::Bool(b),
+            Content::U8(n) => Unexpected::Unsigned(n as u64),
+            Content::U16(n) => Unexpected::Unsigned(n as u64),
+            Content::U32(n) => Unexpected::Unsigned(n as u64),
+            Content::U64(n) => Unexpected::Unsigned(n),
+            Content::I8(n) => Unexpected::Signed(n as i64),
+            Content::I16(n) => Unexpected::Signed(n as i64),
+            Content::I32(n) => Unexpected::Signed(n as i64),
+            Content::I64(n) => Unexpected::Signed(n),
+            Content::F32(f) => Unexpected::Float(f as f64),
+            Content::F64(f) => Unexpected::Float(f),
+            Content::Char(c) => Unexpected::Char(c),
+            Content::String(ref s) => Unexpected::Str(s),
+            Content::Str(s) => Unexpected::Str(s),
+            Content::ByteBuf(ref b) => Unexpected::Bytes(b),
+            Content::Bytes(b) => Unexpected::Bytes(b),
+            Content::None | Content::Some(_) => Unexpected::Option,
+            Content::Unit => Unexpected::Unit,
+            Content::Newtype(_) => Unexpected::NewtypeStruct,
+            Content::Seq(_) => Unexpected::Seq,
+            Content::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Content<'de> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(ContentVisitor)
+    }
+}
+
+/// Writes the value back out, e.g. to quarantine data matching no version.
+impl Serialize for Content<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Content::Bool(b) => s.serialize_bool(*b),
+            Content::U8(n) => s.serialize_u8(*n),
+            Content::U16(n) => s.serialize_u16(*n),
+            Content::U32(n) => s.serialize_u32(*n),
+            Content::U64(n) => s.serialize_u64(*n),
+            Content::I8(n) => s.serialize_i8(*n),
+            Content::I16(n) => s.serialize_i16(*n),
+            Content::I32(n) => s.serialize_i32(*n),
+            Content::I64(n) => s.serialize_i64(*n),
+            Content::F32(f) => s.serialize_f32(*f),
+            Content::F64(f) => s.serialize_f64(*f),
+            Content::Char(c) => s.serialize_char(*c),
+            Content::String(v) => s.serialize_str(v),
+            Content::Str(v) => s.serialize_str(v),
+            Content::ByteBuf(v) => s.serialize_bytes(v),
+            Content::Bytes(v) => s.serialize_bytes(v),
+            Content::None => s.serialize_none(),
+            Content::Some(v) => s.serialize_some(&**v),
+            Content::Unit => s.serialize_unit(),
+            Content::Newtype(v) => s.serialize_newtype_struct("", &**v),
+            Content::Seq(v) => {
+                let mut seq = s.serialize_seq(Some(v.len()))?;
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+                seq.end()
+            }
+            Content::Map(v) => {
+                let mut map = s.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct ContentVisitor;
+
+macro_rules! visit_primitive {
+    ($($method: ident($ty: ty) => $variant: ident,)*) => {
+        $(
+            fn $method<E: de::Error>(self, v: $ty) -> Result<Self::Value, E> {
+                Ok(Content::$variant(v))
+            }
+        )*
+    };
+}
+
+impl<'de> Visitor<'de> for ContentVisitor {
+    type Value = Content<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    visit_primitive! {
+        visit_bool(bool) => Bool,
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_u64(u64) => U64,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_i64(i64) => I64,
+        visit_f32(f32) => F32,
+        visit_f64(f64) => F64,
+        visit_char(char) => Char,
+        visit_string(String) => String,
+        visit_borrowed_str(&'de str) => Str,
+        visit_byte_buf(Vec<u8>) => ByteBuf,
+        visit_borrowed_bytes(&'de [u8]) => Bytes,
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Content::String(v.to_owned()))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Content::ByteBuf(v.to_owned()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Content::Unit)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Content::None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        Content::deserialize(d).map(|v| Content::Some(Box::new(v)))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        Content::deserialize(d).map(|v| Content::Newtype(Box::new(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // Don't trust the size hint with more than a small preallocation.
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Content::Seq(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Content::Map(entries))
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, _data: A) -> Result<Self::Value, A::Error> {
+        Err(de::Error::custom(
+            "versioned data does not support enum input",
+        ))
+    }
+}
+
+/// Deserializes a buffered `Content` by reference, so it can be tried against
+/// every version.
+pub struct ContentRefDeserializer<'a, 'de, E> {
+    content: &'a Content<'de>,
+    path: Option<&'a Path<'a, 'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E> ContentRefDeserializer<'a, 'de, E> {
+    pub fn new(content: &'a Content<'de>) -> Self {
+        ContentRefDeserializer::at(content, None)
+    }
+
+    fn at(content: &'a Content<'de>, path: Option<&'a Path<'a, 'de>>) -> Self {
+        ContentRefDeserializer {
+            content,
+            path,
+            err: PhantomData,
+        }
+    }
+}
+
+impl<E> Clone for ContentRefDeserializer<'_, '_, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for ContentRefDeserializer<'_, '_, E> {}
+
+impl<'de, E: de::Error> ContentRefDeserializer<'_, 'de, E> {
+    #[cold]
+    fn invalid_type(self, exp: &dyn de::Expected) -> E {
+        de::Error::invalid_type(self.content.unexpected(), exp)
+    }
+
+    fn deserialize_integer<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::U8(v) => visitor.visit_u8(v),
+            Content::U16(v) => visitor.visit_u16(v),
+            Content::U32(v) => visitor.visit_u32(v),
+            Content::U64(v) => visitor.visit_u64(v),
+            Content::I8(v) => visitor.visit_i8(v),
+            Content::I16(v) => visitor.visit_i16(v),
+            Content::I32(v) => visitor.visit_i32(v),
+            Content::I64(v) => visitor.visit_i64(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_float<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::F32(v) => visitor.visit_f32(v),
+            Content::F64(v) => visitor.visit_f64(v),
+            _ => self.deserialize_integer(visitor),
+        }
+    }
+}
+
+fn visit_seq_ref<'a, 'de, V, E>(
+    items: &'a [Content<'de>],
+    path: Option<&'a Path<'a, 'de>>,
+    visitor: V,
+) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    let mut seq = SeqRefAccess {
+        items: items.iter(),
+        index: 0,
+        path,
+        prefixed: Cell::new(false),
+        err: PhantomData,
+    };
+    let value = visitor.visit_seq(&mut seq)?;
+    match seq.items.len() {
+        0 => Ok(value),
+        remaining => Err(de::Error::invalid_length(
+            seq.index + remaining,
+            &ExpectedLen(seq.index, "elements in sequence"),
+        )),
+    }
+}
+
+fn visit_map_ref<'a, 'de, V, E>(
+    entries: &'a [(Content<'de>, Content<'de>)],
+    path: Option<&'a Path<'a, 'de>>,
+    visitor: V,
+) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    let mut map = MapRefAccess {
+        entries: entries.iter(),
+        value: None,
+        count: 0,
+        path,
+        prefixed: Cell::new(false),
+        err: PhantomData,
+    };
+    let value = visitor.visit_map(&mut map)?;
+    match map.entries.len() {
+        0 => Ok(value),
+        remaining => Err(de::Error::invalid_length(
+            map.count + remaining,
+            &ExpectedLen(map.count, "elements in map"),
+        )),
+    }
+}
+
+struct ExpectedLen(usize, &'static str);
+
+impl de::Expected for ExpectedLen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, self.1)
+    }
+}
+
+struct SeqRefAccess<'a, 'de, E> {
+    items: std::slice::Iter<'a, Content<'de>>,
+    index: usize,
+    path: Option<&'a Path<'a, 'de>>,
+    prefixed: Cell<bool>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E: de::Error> SeqAccess<'de> for SeqRefAccess<'_, 'de, E> {
+    type Error = E;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, E> {
+        let Some(item) = self.items.next() else {
+            return Ok(None);
+        };
+        let path = Path::new(self.path, Segment::Index(self.index), &self.prefixed);
+        self.index += 1;
+        seed.deserialize(ContentRefDeserializer::at(item, Some(&path)))
+            .map(Some)
+            .map_err(|err| path.prefix(err))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapRefAccess<'a, 'de, E> {
+    entries: std::slice::Iter<'a, (Content<'de>, Content<'de>)>,
+    value: Option<(&'a Content<'de>, &'a Content<'de>)>,
+    count: usize,
+    path: Option<&'a Path<'a, 'de>>,
+    prefixed: Cell<bool>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E: de::Error> MapAccess<'de> for MapRefAccess<'_, 'de, E> {
+    type Error = E;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, E> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.count += 1;
+        self.value = Some((key, value));
+        let path = Path::new(self.path, Segment::Key(key), &self.prefixed);
+        seed.deserialize(ContentRefDeserializer::at(key, Some(&path)))
+            .map(Some)
+            .map_err(|err| path.prefix(err))
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, E> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("MapAccess::next_value called before next_key");
+        let path = Path::new(self.path, Segment::Key(key), &self.prefixed);
+        seed.deserialize(ContentRefDeserializer::at(value, Some(&path)))
+            .map_err(|err| path.prefix(err))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// The location of a nested value, as a chain of segments up to the root,
+/// e.g. `settings.profiles[3].timeout`.
+struct Path<'a, 'de> {
+    parent: Option<&'a Path<'a, 'de>>,
+    segment: Segment<'a, 'de>,
+    /// Set once an error from this value is given its path, so the
+    /// enclosing values pass it on unchanged.
+    prefixed: &'a Cell<bool>,
+}
+
+enum Segment<'a, 'de> {
+    Index(usize),
+    Key(&'a Content<'de>),
+}
+
+impl<'a, 'de> Path<'a, 'de> {
+    fn new(
+        parent: Option<&'a Path<'a, 'de>>,
+        segment: Segment<'a, 'de>,
+        prefixed: &'a Cell<bool>,
+    ) -> Self {
+        prefixed.set(false);
+        Path {
+            parent,
+            segment,
+            prefixed,
+        }
+    }
+
+    /// Prefixes an error with the path to the value it was raised for,
+    /// unless a value nested in it already did.
+    #[cold]
+    fn prefix<E: de::Error>(&self, err: E) -> E {
+        if self.prefixed.get() {
+            return err;
+        }
+        let mut segments = Vec::new();
+        let mut path = Some(self);
+        while let Some(p) = path {
+            p.prefixed.set(true);
+            segments.push(&p.segment);
+            path = p.parent;
+        }
+        let mut message = String::new();
+        for (i, segment) in segments.into_iter().rev().enumerate() {
+            match segment {
+                Segment::Index(index) => message.push_str(&format!("[{index}]")),
+                Segment::Key(key) if i == 0 => message.push_str(&KeySegment(key).to_string()),
+                Segment::Key(key) => message.push_str(&format!(".{}", KeySegment(key))),
+            }
+        }
+        E::custom(format_args!("{message}: {err}"))
+    }
+}
+
+/// A map key as a segment of an error path, e.g. `timeout`.
+struct KeySegment<'a, 'de>(&'a Content<'de>);
+
+impl fmt::Display for KeySegment<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Content::String(s) => f.write_str(s),
+            Content::Str(s) => f.write_str(s),
+            Content::Char(c) => write!(f, "{c}"),
+            Content::Bool(b) => write!(f, "{b}"),
+            Content::U8(n) => write!(f, "{n}"),
+            Content::U16(n) => write!(f, "{n}"),
+            Content::U32(n) => write!(f, "{n}"),
+            Content::U64(n) => write!(f, "{n}"),
+            Content::I8(n) => write!(f, "{n}"),
+            Content::I16(n) => write!(f, "{n}"),
+            Content::I32(n) => write!(f, "{n}"),
+            Content::I64(n) => write!(f, "{n}"),
+            _ => f.write_str("?"),
+        }
+    }
+}
+
+macro_rules! forward_to {
+    ($target: ident: $($method: ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+                self.$target(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for ContentRefDeserializer<'_, 'de, E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Bool(v) => visitor.visit_bool(v),
+            Content::U8(v) => visitor.visit_u8(v),
+            Content::U16(v) => visitor.visit_u16(v),
+            Content::U32(v) => visitor.visit_u32(v),
+            Content::U64(v) => visitor.visit_u64(v),
+            Content::I8(v) => visitor.visit_i8(v),
+            Content::I16(v) => visitor.visit_i16(v),
+            Content::I32(v) => visitor.visit_i32(v),
+            Content::I64(v) => visitor.visit_i64(v),
+            Content::F32(v) => visitor.visit_f32(v),
+            Content::F64(v) => visitor.visit_f64(v),
+            Content::Char(v) => visitor.visit_char(v),
+            Content::String(ref v) => visitor.visit_str(v),
+            Content::Str(v) => visitor.visit_borrowed_str(v),
+            Content::ByteBuf(ref v) => visitor.visit_bytes(v),
+            Content::Bytes(v) => visitor.visit_borrowed_bytes(v),
+            Content::Unit => visitor.visit_unit(),
+            Content::None => visitor.visit_none(),
+            Content::Some(ref v) => visitor.visit_some(ContentRefDeserializer::at(v, self.path)),
+            Content::Newtype(ref v) => {
+                visitor.visit_newtype_struct(ContentRefDeserializer::at(v, self.path))
+            }
+            Content::Seq(ref v) => visit_seq_ref(v, self.path, visitor),
+            Content::Map(ref v) => visit_map_ref(v, self.path, visitor),
+        }
+    }
+
+    forward_to! { deserialize_integer:
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64,
+        deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64,
+    }
+
+    forward_to! { deserialize_float: deserialize_f32, deserialize_f64, }
+
+    forward_to! { deserialize_str: deserialize_string, }
+
+    forward_to! { deserialize_bytes: deserialize_byte_buf, }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Bool(v) => visitor.visit_bool(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Char(v) => visitor.visit_char(v),
+            Content::String(ref v) => visitor.visit_str(v),
+            Content::Str(v) => visitor.visit_borrowed_str(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::String(ref v) => visitor.visit_str(v),
+            Content::Str(v) => visitor.visit_borrowed_str(v),
+            Content::ByteBuf(ref v) => visitor.visit_bytes(v),
+            Content::Bytes(v) => visitor.visit_borrowed_bytes(v),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Seq(ref v) => visit_seq_ref(v, self.path, visitor),
+            _ => self.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::None => visitor.visit_none(),
+            Content::Some(ref v) => visitor.visit_some(ContentRefDeserializer::at(v, self.path)),
+            Content::Unit => visitor.visit_unit(),
+            // Formats like JSON don't mark present optional values.
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Unit => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Newtype(ref v) => {
+                visitor.visit_newtype_struct(ContentRefDeserializer::at(v, self.path))
+            }
+            // Formats like JSON don't mark newtype structs.
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Seq(ref v) => visit_seq_ref(v, self.path, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Map(ref v) => visit_map_ref(v, self.path, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        match *self.content {
+            Content::Seq(ref v) => visit_seq_ref(v, self.path, visitor),
+            Content::Map(ref v) => visit_map_ref(v, self.path, visitor),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        let (variant, value) = match *self.content {
+            // Enums are encoded as maps with a single key, as in JSON.
+            Content::Map(ref entries) if entries.len() == 1 => (&entries[0].0, Some(&entries[0].1)),
+            Content::Map(_) => {
+                return Err(de::Error::invalid_value(
+                    Unexpected::Map,
+                    &"map with a single key",
+                ))
+            }
+            ref s @ (Content::String(_) | Content::Str(_)) => (s, None),
+            ref other => {
+                return Err(de::Error::invalid_type(
+                    other.unexpected(),
+                    &"string or map",
+                ))
+            }
+        };
+
+        visitor.visit_enum(EnumRefDeserializer {
+            variant,
+            value,
+            path: self.path,
+            err: PhantomData,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match *self.content {
+            Content::U8(v) => visitor.visit_u8(v),
+            Content::U64(v) => visitor.visit_u64(v),
+            _ => self.deserialize_str(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_unit()
+    }
+}
+
+impl<'a, 'de, E: de::Error> IntoDeserializer<'de, E> for &'a Content<'de> {
+    type Deserializer = ContentRefDeserializer<'a, 'de, E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ContentRefDeserializer::new(self)
+    }
+}
+
+struct EnumRefDeserializer<'a, 'de, E> {
+    variant: &'a Content<'de>,
+    value: Option<&'a Content<'de>>,
+    path: Option<&'a Path<'a, 'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'a, 'de, E: de::Error> EnumAccess<'de> for EnumRefDeserializer<'a, 'de, E> {
+    type Error = E;
+    type Variant = VariantRefDeserializer<'a, 'de, E>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), E> {
+        let variant = VariantRefDeserializer {
+            value: self.value,
+            path: self.path,
+            err: PhantomData,
+        };
+        seed.deserialize(ContentRefDeserializer::at(self.variant, self.path))
+            .map(|v| (v, variant))
+    }
+}
+
+struct VariantRefDeserializer<'a, 'de, E> {
+    value: Option<&'a Content<'de>>,
+    path: Option<&'a Path<'a, 'de>>,
+    err: PhantomData<E>,
+}
+
+impl<'de, E: de::Error> VariantAccess<'de> for VariantRefDeserializer<'_, 'de, E> {
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        match self.value {
+            Some(value) => Deserialize::deserialize(ContentRefDeserializer::at(value, self.path)),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, E> {
+        match self.value {
+            Some(value) => seed.deserialize(ContentRefDeserializer::at(value, self.path)),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Some(Content::Seq(v)) => visit_seq_ref(v, self.path, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"tuple variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        match self.value {
+            Some(Content::Map(v)) => visit_map_ref(v, self.path, visitor),
+            Some(Content::Seq(v)) => visit_seq_ref(v, self.path, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"struct variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
@@ -0,0 +1,896 @@
+//! Explicitly tagged data, written as `{"version": "v3", "data": ...}`,
+//! `{"v3": ...}` or `{"version": "v3", ...}`, so reading dispatches on the tag
+//! instead of attempting every version, or prefixed by the index of the
+//! version in formats without maps.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeStruct, SerializeTuple, Serializer};
+
+use crate::content::{Content, ContentRefDeserializer};
+use crate::{CurrentVersion, DeserializeVersioned, IntoVersion, Ver, VersionErrors, VersionInfo};
+
+/// A value written with the tag of its current version, and read by
+/// deserializing the data as the version its tag names.
+/// Data after the tag is read straight from the input, without buffering.
+/// The layout is given by `CurrentVersion::ENVELOPE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope<T>(pub T);
+
+impl<T> Envelope<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// A value written as its current version's index followed by the data, for
+/// formats that aren't self-describing, such as bincode or postcard.
+/// Read by deserializing the data as the version with that index, without probing.
+/// The index is written as a `u8`, see `DeserializeVersioned::deserialize_version_index`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryEnvelope<T>(pub T);
+
+impl<T> BinaryEnvelope<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Serialize + CurrentVersion> Serialize for BinaryEnvelope<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut envelope = s.serialize_tuple(2)?;
+        envelope.serialize_element(&T::INDEX)?;
+        envelope.serialize_element(&self.0)?;
+        envelope.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for BinaryEnvelope<T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_tuple(2, BinaryVisitor(PhantomData))
+    }
+}
+
+struct BinaryVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for BinaryVisitor<T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    type Value = BinaryEnvelope<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a version index followed by the data")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let Some(index) = seq.next_element::<u8>()? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        let data = match index == T::INDEX {
+            true => seq.next_element_seed(CurrentSeed(PhantomData))?,
+            false => seq.next_element_seed(IndexSeed(index, PhantomData))?,
+        };
+        match data {
+            Some(data) => Ok(BinaryEnvelope(data)),
+            None => Err(de::Error::invalid_length(1, &self)),
+        }
+    }
+}
+
+/// Writes and reads a field as a `BinaryEnvelope`, for formats that aren't
+/// self-describing, with `#[serde(with = "serde_versioned::binary")]`.
+pub mod binary {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::BinaryEnvelope;
+    use crate::{CurrentVersion, DeserializeVersioned};
+
+    pub fn serialize<T, S>(value: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + CurrentVersion,
+        S: Serializer,
+    {
+        (T::INDEX, value).serialize(s)
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+        D: Deserializer<'de>,
+    {
+        BinaryEnvelope::deserialize(d).map(BinaryEnvelope::into_inner)
+    }
+}
+
+/// Deserializes the data as the current version.
+pub(crate) struct CurrentSeed<T>(pub(crate) PhantomData<T>);
+
+impl<'de, T: Deserialize<'de> + DeserializeVersioned> DeserializeSeed<'de> for CurrentSeed<T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        T::deserialize_current(d)
+    }
+}
+
+/// Deserializes the data as the old version with the index.
+pub(crate) struct IndexSeed<T>(pub(crate) u8, pub(crate) PhantomData<T>);
+
+impl<'de, T: DeserializeVersioned> DeserializeSeed<'de> for IndexSeed<T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        T::deserialize_version_index(self.0, d)
+    }
+}
+
+/// Deserializes the data from any version.
+pub(crate) struct AnyVersionSeed<T>(pub(crate) PhantomData<T>);
+
+impl<'de, T: DeserializeVersioned> DeserializeSeed<'de> for AnyVersionSeed<T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        T::deserialize_any_version(d)
+    }
+}
+
+/// How an `Envelope` combines the version tag with the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeLayout {
+    /// The tag and the data next to each other, e.g. `{"version": "v3", "data": ...}`
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+    /// The data keyed by the tag, e.g. `{"v3": ...}`
+    Wrapper,
+    /// The tag as a field of the data, e.g. `{"version": "v3", "name": ...}`.
+    /// The data must be a struct or map.
+    Internal { tag: &'static str },
+}
+
+impl<T: Serialize + CurrentVersion> Serialize for Envelope<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_envelope(&self.0, s)
+    }
+}
+
+fn serialize_envelope<T, S>(value: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize + CurrentVersion + ?Sized,
+    S: Serializer,
+{
+    match T::ENVELOPE {
+        EnvelopeLayout::Adjacent { tag, content } => {
+            let mut envelope = s.serialize_struct("Envelope", 2)?;
+            envelope.serialize_field(tag, &T::Marker::name())?;
+            envelope.serialize_field(content, value)?;
+            envelope.end()
+        }
+        EnvelopeLayout::Wrapper => {
+            let mut envelope = s.serialize_map(Some(1))?;
+            envelope.serialize_entry(&T::Marker::name(), value)?;
+            envelope.end()
+        }
+        EnvelopeLayout::Internal { tag } => value.serialize(TaggedSerializer {
+            tag,
+            version: &T::Marker::name(),
+            inner: s,
+        }),
+    }
+}
+
+/// Writes and reads a field as an `Envelope`, tagged with its current version,
+/// with `#[serde(with = "serde_versioned::tagged")]`.
+pub mod tagged {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Envelope;
+    use crate::{CurrentVersion, DeserializeVersioned};
+
+    pub fn serialize<T, S>(value: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + CurrentVersion,
+        S: Serializer,
+    {
+        super::serialize_envelope(value, s)
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+        D: Deserializer<'de>,
+    {
+        Envelope::deserialize(d).map(Envelope::into_inner)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Envelope<T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_map(EnvelopeVisitor(T::ENVELOPE, PhantomData))
+            .map(Envelope)
+    }
+}
+
+/// Reads the tag, then the data only as the version it names.
+/// The data is buffered only if it comes before the tag, or for an internal tag.
+struct EnvelopeVisitor<T>(EnvelopeLayout, PhantomData<T>);
+
+/// The data of an adjacently tagged envelope.
+enum Data<'de, T> {
+    /// Read after the tag.
+    Read(T),
+    /// Buffered, as it came before the tag.
+    Buffered(Content<'de>),
+}
+
+impl<'de, T> Visitor<'de> for EnvelopeVisitor<T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            EnvelopeLayout::Adjacent { tag, content } => {
+                write!(f, "a map with `{tag}` and `{content}`")
+            }
+            EnvelopeLayout::Wrapper => f.write_str("a map with a single version"),
+            EnvelopeLayout::Internal { tag } => write!(f, "a map with `{tag}`"),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (tag_key, content_key) = match self.0 {
+            EnvelopeLayout::Adjacent { tag, content } => (tag, content),
+            EnvelopeLayout::Wrapper => {
+                let Some(tag) = map.next_key::<Tag>()? else {
+                    return Err(de::Error::invalid_length(0, &self));
+                };
+                let data = map.next_value_seed(TagSeed(&tag.0, PhantomData))?;
+                if map.next_key::<de::IgnoredAny>()?.is_some() {
+                    return Err(de::Error::invalid_length(2, &self));
+                }
+                return Ok(data);
+            }
+            EnvelopeLayout::Internal { tag: tag_key } => {
+                let mut tag = None;
+                let mut fields = Vec::new();
+                while let Some(key) = map.next_key::<Content<'de>>()? {
+                    if key.as_str() == Some(tag_key) && tag.is_none() {
+                        tag = Some(map.next_value::<Tag>()?.0);
+                    } else {
+                        fields.push((key, map.next_value()?));
+                    }
+                }
+                let Some(tag) = tag else {
+                    return Err(de::Error::missing_field(tag_key));
+                };
+                let data = Content::Map(fields);
+                return TagSeed(&tag, PhantomData).deserialize(ContentRefDeserializer::new(&data));
+            }
+        };
+
+        let mut tag = None;
+        let mut data = None;
+        while let Some(key) = map.next_key::<Cow<'de, str>>()? {
+            if key == tag_key && tag.is_none() {
+                tag = Some(map.next_value::<Tag>()?.0);
+            } else if key == content_key && data.is_none() {
+                data = Some(match &tag {
+                    Some(tag) => Data::Read(map.next_value_seed(TagSeed(tag, PhantomData))?),
+                    None => Data::Buffered(map.next_value()?),
+                });
+            } else if key == tag_key || key == content_key {
+                return Err(de::Error::custom(format_args!("duplicate field `{key}`")));
+            } else {
+                map.next_value::<de::IgnoredAny>()?;
+            }
+        }
+        match (tag, data) {
+            (Some(_), Some(Data::Read(data))) => Ok(data),
+            (Some(tag), Some(Data::Buffered(data))) => {
+                TagSeed(&tag, PhantomData).deserialize(ContentRefDeserializer::new(&data))
+            }
+            (None, _) => Err(de::Error::missing_field(tag_key)),
+            (_, None) => Err(de::Error::missing_field(content_key)),
+        }
+    }
+}
+
+/// Deserializes the data as the version with the tag.
+struct TagSeed<'a, T>(&'a str, PhantomData<T>);
+
+impl<'de, T> DeserializeSeed<'de> for TagSeed<'_, T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        match T::Marker::accepts(self.0) {
+            true => T::deserialize_current(d),
+            false => T::deserialize_version(self.0, d),
+        }
+    }
+}
+
+/// A version tag, written as a string or a number.
+struct Tag(String);
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct TagVisitor;
+
+        impl Visitor<'_> for TagVisitor {
+            type Value = Tag;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a version tag")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+                Ok(Tag(v.to_owned()))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tag, E> {
+                Ok(Tag(v.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tag, E> {
+                Ok(Tag(v.to_string()))
+            }
+        }
+
+        d.deserialize_any(TagVisitor)
+    }
+}
+
+/// A value written as an `Envelope`, and read either as an `Envelope` or, for
+/// untagged data written before it was tagged, from any version.
+/// Derefs to the value, so whole documents can be versioned without field
+/// attributes, e.g. as a request body. Data is untagged if it lacks the tag
+/// field, or for `EnvelopeLayout::Wrapper`, if it isn't a map with a single entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VersionStamped<T>(pub T);
+
+impl<T> VersionStamped<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for VersionStamped<T> {
+    fn from(value: T) -> Self {
+        VersionStamped(value)
+    }
+}
+
+impl<T> std::ops::Deref for VersionStamped<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> std::ops::DerefMut for VersionStamped<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize + CurrentVersion> Serialize for VersionStamped<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_envelope(&self.0, s)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for VersionStamped<T>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let content = Content::deserialize(d)?;
+        let tagged = match (T::ENVELOPE, &content) {
+            (EnvelopeLayout::Adjacent { tag, .. } | EnvelopeLayout::Internal { tag }, _) => {
+                content.get(tag).is_some()
+            }
+            (EnvelopeLayout::Wrapper, Content::Map(entries)) => entries.len() == 1,
+            (EnvelopeLayout::Wrapper, _) => false,
+        };
+        let d = ContentRefDeserializer::<D::Error>::new(&content);
+        match tagged {
+            true => Envelope::deserialize(d).map(|envelope| VersionStamped(envelope.0)),
+            false => T::deserialize_any_version(d).map(VersionStamped),
+        }
+    }
+}
+
+/// A value written as both its current version and the old version `V`,
+/// keyed by their tags, e.g. `{"v3": {...}, "v2": {...}}`, so that during a
+/// migration window readers of either version find the data they understand.
+/// Read from the current version if present, or else the first entry that
+/// converts, so the entries needn't be in any order.
+pub struct DualWrite<T, V> {
+    value: T,
+    previous: PhantomData<V>,
+}
+
+impl<T, V> DualWrite<T, V> {
+    pub fn new(value: T) -> Self {
+        DualWrite {
+            value,
+            previous: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: fmt::Debug, V> fmt::Debug for DualWrite<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DualWrite").field(&self.value).finish()
+    }
+}
+
+impl<T: Clone, V> Clone for DualWrite<T, V> {
+    fn clone(&self) -> Self {
+        DualWrite::new(self.value.clone())
+    }
+}
+
+impl<T: PartialEq, V> PartialEq for DualWrite<T, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T, V> Serialize for DualWrite<T, V>
+where
+    T: Serialize + CurrentVersion + IntoVersion<Ver<V>>,
+    V: VersionInfo,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        struct Previous<'a, T, V>(&'a T, PhantomData<V>);
+
+        impl<T: IntoVersion<Ver<V>>, V> Serialize for Previous<'_, T, V> {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize_versioned(s)
+            }
+        }
+
+        let mut envelope = s.serialize_map(Some(2))?;
+        envelope.serialize_entry(&T::Marker::name(), &self.value)?;
+        envelope.serialize_entry(&V::name(), &Previous(&self.value, PhantomData::<V>))?;
+        envelope.end()
+    }
+}
+
+impl<'de, T, V> Deserialize<'de> for DualWrite<T, V>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_map(DualWriteVisitor(PhantomData))
+    }
+}
+
+struct DualWriteVisitor<T, V>(PhantomData<(T, V)>);
+
+impl<'de, T, V> Visitor<'de> for DualWriteVisitor<T, V>
+where
+    T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+{
+    type Value = DualWrite<T, V>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of versions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(tag) = map.next_key::<Tag>()? {
+            if T::Marker::accepts(&tag.0) {
+                let value = map.next_value_seed(CurrentSeed(PhantomData))?;
+                while map
+                    .next_entry::<de::IgnoredAny, de::IgnoredAny>()?
+                    .is_some()
+                {}
+                return Ok(DualWrite::new(value));
+            }
+            entries.push((tag.0, map.next_value::<Content<'de>>()?));
+        }
+        let mut errors = VersionErrors::default();
+        for (tag, data) in &entries {
+            match T::deserialize_version(tag, ContentRefDeserializer::<A::Error>::new(data)) {
+                Ok(value) => return Ok(DualWrite::new(value)),
+                Err(err) => errors.errors.push((tag.clone(), err.to_string())),
+            }
+        }
+        match errors.is_empty() {
+            true => Err(de::Error::invalid_length(0, &self)),
+            false => Err(de::Error::custom(errors)),
+        }
+    }
+}
+
+/// A field written as the tag of the current version of `T`, and read only
+/// if it names that version, e.g. `schema_version: VersionStamp<Self>`, so the
+/// tag is never written by hand. Older data whose stamp names another version
+/// is then rejected by the current version, and read as the version it names.
+pub struct VersionStamp<T>(PhantomData<fn() -> T>);
+
+impl<T> VersionStamp<T> {
+    pub const fn new() -> Self {
+        VersionStamp(PhantomData)
+    }
+}
+
+impl<T> Default for VersionStamp<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for VersionStamp<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for VersionStamp<T> {}
+
+impl<T> PartialEq for VersionStamp<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for VersionStamp<T> {}
+
+impl<T: CurrentVersion> fmt::Debug for VersionStamp<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VersionStamp({})", T::Marker::name())
+    }
+}
+
+impl<T: CurrentVersion> Serialize for VersionStamp<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        T::Marker::name().serialize(s)
+    }
+}
+
+impl<'de, T: CurrentVersion> Deserialize<'de> for VersionStamp<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let Tag(tag) = Tag::deserialize(d)?;
+        match T::Marker::accepts(&tag) {
+            true => Ok(VersionStamp::new()),
+            false => Err(de::Error::custom(format_args!(
+                "expected version `{}`, found `{tag}`",
+                T::Marker::name()
+            ))),
+        }
+    }
+}
+
+/// Serializes a struct or map with the version tag as its first field.
+struct TaggedSerializer<'a, S> {
+    tag: &'static str,
+    version: &'a str,
+    inner: S,
+}
+
+impl<S: Serializer> TaggedSerializer<'_, S> {
+    fn unsupported(what: &str) -> S::Error {
+        ser::Error::custom(format_args!(
+            "an internally tagged envelope requires a struct or map, found {what}"
+        ))
+    }
+}
+
+macro_rules! unsupported {
+    ($($method: ident($($ty: ty),*) => $what: literal,)*) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<S::Ok, S::Error> {
+                Err(Self::unsupported($what))
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for TaggedSerializer<'_, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = ser::Impossible<S::Ok, S::Error>;
+    type SerializeTuple = ser::Impossible<S::Ok, S::Error>;
+    type SerializeTupleStruct = ser::Impossible<S::Ok, S::Error>;
+    type SerializeTupleVariant = ser::Impossible<S::Ok, S::Error>;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = ser::Impossible<S::Ok, S::Error>;
+
+    unsupported! {
+        serialize_bool(bool) => "a bool",
+        serialize_i8(i8) => "an integer",
+        serialize_i16(i16) => "an integer",
+        serialize_i32(i32) => "an integer",
+        serialize_i64(i64) => "an integer",
+        serialize_u8(u8) => "an integer",
+        serialize_u16(u16) => "an integer",
+        serialize_u32(u32) => "an integer",
+        serialize_u64(u64) => "an integer",
+        serialize_f32(f32) => "a float",
+        serialize_f64(f64) => "a float",
+        serialize_char(char) => "a char",
+        serialize_str(&str) => "a string",
+        serialize_bytes(&[u8]) => "bytes",
+        serialize_none() => "an option",
+        serialize_unit() => "a unit",
+        serialize_unit_struct(&'static str) => "a unit struct",
+        serialize_unit_variant(&'static str, u32, &'static str) => "an enum",
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Err(Self::unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        Err(Self::unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        Err(Self::unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<S::SerializeMap, S::Error> {
+        let mut map = self.inner.serialize_map(len.map(|len| len + 1))?;
+        map.serialize_entry(self.tag, self.version)?;
+        Ok(map)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStruct, S::Error> {
+        let mut state = self.inner.serialize_struct(name, len + 1)?;
+        state.serialize_field(self.tag, self.version)?;
+        Ok(state)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        Err(Self::unsupported("an enum"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Current, FromVersion, Num, VersionId, Versions};
+
+    /// The first version of the readings, `Num<1>`, before `Num<2>`.
+    #[derive(serde::Deserialize)]
+    pub(crate) struct ReadingV1 {
+        reading: String,
+    }
+
+    macro_rules! reading {
+        ($name: ident, $layout: expr) => {
+            #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+            pub(crate) struct $name {
+                pub(crate) value: u32,
+            }
+
+            impl FromVersion<Ver<Num<1>>> for $name {
+                type VersionType = ReadingV1;
+                type Error = std::num::ParseIntError;
+
+                fn convert(v: ReadingV1) -> Result<Self, Self::Error> {
+                    Ok($name {
+                        value: v.reading.parse()?,
+                    })
+                }
+            }
+
+            impl DeserializeVersioned for $name {
+                type Versions = Versions<Ver<Current>, Ver<Num<1>>>;
+
+                fn deserialize_any_version<'de, D: Deserializer<'de>>(
+                    d: D,
+                ) -> Result<Self, D::Error> {
+                    Self::Versions::deserialize(d)
+                }
+
+                fn deserialize_with_version<'de, D: Deserializer<'de>>(
+                    d: D,
+                ) -> Result<(Self, VersionId), D::Error> {
+                    Self::Versions::deserialize_with_version(d)
+                }
+
+                fn deserialize_version<'de, D: Deserializer<'de>>(
+                    tag: &str,
+                    d: D,
+                ) -> Result<Self, D::Error> {
+                    Self::Versions::deserialize_tagged(tag, d)
+                }
+
+                fn deserialize_version_index<'de, D: Deserializer<'de>>(
+                    index: u8,
+                    d: D,
+                ) -> Result<Self, D::Error> {
+                    match Self::INDEX.checked_sub(index) {
+                        Some(position) => Self::Versions::deserialize_at(position.into(), d),
+                        None => Err(de::Error::custom("unknown version index")),
+                    }
+                }
+            }
+
+            impl CurrentVersion for $name {
+                type Marker = Num<2>;
+                const INDEX: u8 = 1;
+                const ENVELOPE: EnvelopeLayout = $layout;
+            }
+        };
+    }
+
+    reading!(
+        Adjacent,
+        EnvelopeLayout::Adjacent {
+            tag: "v",
+            content: "data",
+        }
+    );
+    reading!(Wrapped, EnvelopeLayout::Wrapper);
+    reading!(Internal, EnvelopeLayout::Internal { tag: "v" });
+
+    fn read<T>(json: &str) -> Result<T, serde_json::Error>
+    where
+        T: for<'de> Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+    {
+        serde_json::from_str::<Envelope<T>>(json).map(Envelope::into_inner)
+    }
+
+    #[test]
+    fn test_adjacent() {
+        let value = Adjacent { value: 3 };
+        let json = serde_json::to_string(&Envelope(Adjacent { value: 3 })).unwrap();
+        assert_eq!(json, r#"{"v":"v2","data":{"value":3}}"#);
+        assert_eq!(read::<Adjacent>(&json).unwrap(), value);
+
+        // The data is buffered if it comes before the tag.
+        let old = r#"{"data": {"reading": "3"}, "other": 1, "v": 1}"#;
+        assert_eq!(read::<Adjacent>(old).unwrap(), value);
+        assert!(read::<Adjacent>(r#"{"v": "v1", "data": {"value": 3}}"#).is_err());
+        assert!(read::<Adjacent>(r#"{"v": "v3", "data": {"value": 3}}"#).is_err());
+        assert!(read::<Adjacent>(r#"{"v": "v2", "v": "v2", "data": {}}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("duplicate field `v`"));
+        assert!(read::<Adjacent>(r#"{"data": {"value": 3}}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("missing field `v`"));
+    }
+
+    #[test]
+    fn test_wrapper() {
+        let value = Wrapped { value: 3 };
+        let json = serde_json::to_string(&Envelope(Wrapped { value: 3 })).unwrap();
+        assert_eq!(json, r#"{"v2":{"value":3}}"#);
+        assert_eq!(read::<Wrapped>(&json).unwrap(), value);
+        assert_eq!(
+            read::<Wrapped>(r#"{"v1": {"reading": "3"}}"#).unwrap(),
+            value
+        );
+        assert!(read::<Wrapped>(r#"{"v2": {"value": 3}, "v1": {"reading": "3"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_internal() {
+        let value = Internal { value: 3 };
+        let json = serde_json::to_string(&Envelope(Internal { value: 3 })).unwrap();
+        assert_eq!(json, r#"{"v":"v2","value":3}"#);
+        assert_eq!(read::<Internal>(&json).unwrap(), value);
+        assert_eq!(
+            read::<Internal>(r#"{"reading": "3", "v": "v1"}"#).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_binary_envelope() {
+        let value = Adjacent { value: 3 };
+        let json = serde_json::to_string(&BinaryEnvelope(Adjacent { value: 3 })).unwrap();
+        assert_eq!(json, r#"[1,{"value":3}]"#);
+        let read = |json| serde_json::from_str::<BinaryEnvelope<Adjacent>>(json);
+        assert_eq!(read(&json).unwrap().into_inner(), value);
+        assert_eq!(
+            read(r#"[0, {"reading": "3"}]"#).unwrap().into_inner(),
+            value
+        );
+        assert!(read(r#"[0, {"value": 3}]"#).is_err());
+        assert!(read(r#"[2, {"value": 3}]"#).is_err());
+        assert!(read("[1]").is_err());
+    }
+
+    #[test]
+    fn test_version_stamped() {
+        let read = |json| {
+            serde_json::from_str::<VersionStamped<Adjacent>>(json).map(VersionStamped::into_inner)
+        };
+        assert_eq!(
+            read(r#"{"v": "v2", "data": {"value": 3}}"#).unwrap().value,
+            3
+        );
+        assert_eq!(read(r#"{"reading": "3"}"#).unwrap().value, 3);
+        assert_eq!(read(r#"{"value": 3}"#).unwrap().value, 3);
+    }
+
+    #[test]
+    fn test_version_stamp() {
+        let stamp = VersionStamp::<Adjacent>::new();
+        assert_eq!(format!("{stamp:?}"), "VersionStamp(v2)");
+        assert_eq!(serde_json::to_string(&stamp).unwrap(), r#""v2""#);
+        assert!(serde_json::from_str::<VersionStamp<Adjacent>>("2").is_ok());
+        let error = serde_json::from_str::<VersionStamp<Adjacent>>(r#""v1""#).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("expected version `v2`, found `v1`"));
+    }
+}
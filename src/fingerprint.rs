@@ -0,0 +1,460 @@
+//! Fingerprints of the serde shape of a type, traced by deserializing it from a
+//! deserializer that records every request instead of reading input.
+
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+
+use serde::de::value::StrDeserializer;
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+
+/// Recursive types are traced up to this depth.
+const MAX_DEPTH: usize = 16;
+
+/// Enums are traced up to this many variants in total.
+const MAX_PASSES: usize = 1024;
+
+/// Computes a 64-bit fingerprint of the serde shape of `T`, to pin as the
+/// `N` of a `Fingerprint<N>` version.
+///
+/// The fingerprint covers the field names and types, but not the type names.
+/// Enums contribute the names and shapes of all their variants.
+pub fn fingerprint<T: for<'de> Deserialize<'de>>() -> u64 {
+    // The first pass takes the first variant of every enum, and each further
+    // pass another variant of one enum, until every variant was traced.
+    let mut targets = vec![None];
+    let mut traced = HashSet::new();
+    let mut hash = FNV_OFFSET;
+    let mut passes = 0;
+    while let Some(target) = targets.get(passes).cloned() {
+        let mut pass = Pass {
+            trace: String::new(),
+            target,
+            enums: Vec::new(),
+        };
+        // Types rejecting the placeholder values still leave the trace up to that point.
+        let _ = T::deserialize(Tracer {
+            pass: &mut pass,
+            path: Vec::new(),
+            depth: 0,
+        });
+        if passes > 0 {
+            hash = fnv1a(hash, b"|");
+        }
+        hash = fnv1a(hash, pass.trace.as_bytes());
+        passes += 1;
+
+        for (path, variants) in pass.enums {
+            if traced.insert(path.clone()) {
+                let more = (1..variants).map(|variant| Some((path.clone(), variant)));
+                targets.extend(more.take(MAX_PASSES.saturating_sub(targets.len())));
+            }
+        }
+    }
+    hash
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// The 64-bit FNV-1a hash, stable across platforms and releases.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where a value is in the traced type, as the fields, elements and enum
+/// variants leading to it.
+type Path = Vec<String>;
+
+/// One deserialization of the type, tracing one variant of every enum.
+struct Pass {
+    trace: String,
+    /// The variant of the enum at the path to trace, if not the first.
+    target: Option<(Path, usize)>,
+    /// The paths of the enums traced, with their number of variants.
+    enums: Vec<(Path, usize)>,
+}
+
+impl Pass {
+    /// The variant to trace of the enum at the path: the target, the variant
+    /// leading to the target, or else the first.
+    fn variant(&mut self, path: &Path, variants: usize) -> usize {
+        self.enums.push((path.clone(), variants));
+        let Some((target, variant)) = &self.target else {
+            return 0;
+        };
+        if target == path {
+            return *variant;
+        }
+        match target.strip_prefix(path.as_slice()) {
+            Some([next, ..]) => next
+                .strip_prefix('#')
+                .and_then(|variant| variant.parse().ok())
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+struct Tracer<'a> {
+    pass: &'a mut Pass,
+    path: Path,
+    depth: usize,
+}
+
+impl Tracer<'_> {
+    /// The tracer of a value nested in this one, e.g. an optional value.
+    fn nested(&mut self) -> Tracer<'_> {
+        self.child(None)
+    }
+
+    /// The tracer of a value at the segment of the path, e.g. a field.
+    fn child(&mut self, segment: Option<String>) -> Tracer<'_> {
+        let mut path = self.path.clone();
+        path.extend(segment);
+        Tracer {
+            pass: self.pass,
+            path,
+            depth: self.depth + 1,
+        }
+    }
+
+    fn exhausted(&self) -> bool {
+        self.depth >= MAX_DEPTH
+    }
+}
+
+macro_rules! trace_primitive {
+    ($($method: ident => $visit: ident($($value: expr)?),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.pass.trace.push_str(&stringify!($method)["deserialize_".len()..]);
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Tracer<'_> {
+    type Error = Error;
+
+    trace_primitive! {
+        deserialize_any => visit_unit(),
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i8(0),
+        deserialize_i16 => visit_i16(0),
+        deserialize_i32 => visit_i32(0),
+        deserialize_i64 => visit_i64(0),
+        deserialize_i128 => visit_i128(0),
+        deserialize_u8 => visit_u8(0),
+        deserialize_u16 => visit_u16(0),
+        deserialize_u32 => visit_u32(0),
+        deserialize_u64 => visit_u64(0),
+        deserialize_u128 => visit_u128(0),
+        deserialize_f32 => visit_f32(0.0),
+        deserialize_f64 => visit_f64(0.0),
+        deserialize_char => visit_char('\0'),
+        deserialize_str => visit_borrowed_str(""),
+        deserialize_string => visit_borrowed_str(""),
+        deserialize_bytes => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf => visit_borrowed_bytes(&[]),
+        deserialize_unit => visit_unit(),
+        deserialize_identifier => visit_borrowed_str(""),
+        deserialize_ignored_any => visit_unit(),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        self.pass.trace.push_str("option(");
+        let res = match self.exhausted() {
+            true => visitor.visit_none(),
+            false => visitor.visit_some(self.nested()),
+        };
+        self.pass.trace.push(')');
+        res
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.pass.trace.push_str("newtype(");
+        let res = visitor.visit_newtype_struct(self.nested());
+        self.pass.trace.push(')');
+        res
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        self.pass.trace.push_str("seq(");
+        let len = usize::from(!self.exhausted());
+        let res = visitor.visit_seq(Elements {
+            tracer: self.nested(),
+            index: 0,
+            len,
+        });
+        self.pass.trace.push(')');
+        res
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.pass.trace.push_str("tuple(");
+        let res = visitor.visit_seq(Elements {
+            tracer: self.nested(),
+            index: 0,
+            len,
+        });
+        self.pass.trace.push(')');
+        res
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        self.pass.trace.push_str("map(");
+        let len = usize::from(!self.exhausted());
+        let res = visitor.visit_map(Entries {
+            tracer: self.nested(),
+            keys: &[],
+            index: 0,
+            len,
+        });
+        self.pass.trace.push(')');
+        res
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.pass.trace.push('{');
+        let res = visitor.visit_map(Entries {
+            tracer: self.nested(),
+            keys: fields,
+            index: 0,
+            len: fields.len(),
+        });
+        self.pass.trace.push('}');
+        res
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let _ = write!(self.pass.trace, "enum[{}](", variants.join(","));
+        let index = self.pass.variant(&self.path, variants.len());
+        let res = match variants.get(index) {
+            Some(variant) => visitor.visit_enum(Variant {
+                tracer: self.child(Some(format!("#{index}"))),
+                variant,
+            }),
+            None => Err(de::Error::custom("enum without variants")),
+        };
+        self.pass.trace.push(')');
+        res
+    }
+}
+
+/// The elements of a sequence or tuple.
+struct Elements<'a> {
+    tracer: Tracer<'a>,
+    index: usize,
+    len: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        self.index += 1;
+        self.tracer.pass.trace.push(',');
+        let element = self.tracer.child(Some((self.index - 1).to_string()));
+        seed.deserialize(element).map(Some)
+    }
+}
+
+/// The entries of a map, keyed by the field names for structs.
+struct Entries<'a> {
+    tracer: Tracer<'a>,
+    keys: &'static [&'static str],
+    index: usize,
+    len: usize,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        self.index += 1;
+        self.tracer.pass.trace.push(',');
+        match self.keys.get(self.index - 1) {
+            Some(key) => {
+                self.tracer.pass.trace.push_str(key);
+                let key: StrDeserializer<'_, Error> = key.into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            _ => seed
+                .deserialize(self.tracer.child(Some("key".into())))
+                .map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        self.tracer.pass.trace.push(':');
+        let key = self.keys.get(self.index - 1).map_or("value", |key| key);
+        seed.deserialize(self.tracer.child(Some(key.into())))
+    }
+}
+
+/// The traced variant of an enum.
+struct Variant<'a> {
+    tracer: Tracer<'a>,
+    variant: &'static str,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = Error;
+    type Variant = Tracer<'a>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Tracer<'a>), Error> {
+        let variant: StrDeserializer<'_, Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, self.tracer))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Tracer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum Small {
+        A(u8),
+        B(String),
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum Wide {
+        A(u8),
+        B(u64, u64),
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum Outer {
+        Plain,
+        Nested { inner: Small },
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum OuterWide {
+        Plain,
+        Nested { inner: Wide },
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum Expr {
+        Lit(u8),
+        Add(Box<Expr>, Box<Expr>),
+    }
+
+    #[test]
+    fn test_later_variants() {
+        assert_ne!(fingerprint::<Small>(), fingerprint::<Wide>());
+        assert_ne!(fingerprint::<Outer>(), fingerprint::<OuterWide>());
+        assert_eq!(fingerprint::<Small>(), fingerprint::<Small>());
+        assert_ne!(fingerprint::<Expr>(), 0);
+    }
+
+    #[test]
+    fn test_unchanged_without_enums() {
+        // A single pass hashes the trace alone.
+        assert_eq!(fingerprint::<u32>(), fnv1a(FNV_OFFSET, b"u32"));
+        assert_eq!(
+            fingerprint::<Option<u8>>(),
+            fnv1a(FNV_OFFSET, b"option(u8)")
+        );
+    }
+}
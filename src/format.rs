@@ -0,0 +1,44 @@
+//! Serde data formats, for payloads that are nested in a frame as bytes.
+
+use std::fmt::Display;
+
+use serde::de::DeserializeSeed;
+use serde::Serialize;
+
+/// A serde data format, implemented for the format crate you use, e.g. with
+/// `rmp_serde::to_vec` and `rmp_serde::Deserializer::from_read_ref`.
+pub trait Format {
+    type Error: Display;
+
+    /// Serializes the value to bytes.
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    /// Deserializes the bytes with the seed.
+    fn from_slice_seed<'de, S: DeserializeSeed<'de>>(
+        bytes: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, Self::Error>;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// JSON, for the tests of the modules reading a `Format`.
+    pub(crate) struct Json;
+
+    impl Format for Json {
+        type Error = serde_json::Error;
+
+        fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            serde_json::to_vec(value)
+        }
+
+        fn from_slice_seed<'de, S: DeserializeSeed<'de>>(
+            bytes: &'de [u8],
+            seed: S,
+        ) -> Result<S::Value, Self::Error> {
+            seed.deserialize(&mut serde_json::Deserializer::from_slice(bytes))
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Data framed as `MAGIC + version id + payload`, so files written by old
+//! releases, or records produced with old schemas, are recognized and read as
+//! the version they were written with.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{CurrentSeed, IndexSeed};
+use crate::format::Format;
+use crate::{CurrentVersion, DeserializeVersioned, SchemaIds};
+
+/// Writes and reads values framed by magic bytes and the index of their
+/// version, with the payload encoded with `F`.
+/// The index is written as an unsigned LEB128 varint, see
+/// `DeserializeVersioned::deserialize_version_index`.
+#[derive(Debug, Clone, Copy)]
+pub struct Framing<F> {
+    magic: &'static [u8],
+    format: PhantomData<F>,
+}
+
+impl<F: Format> Framing<F> {
+    /// Frames with the magic bytes identifying your files, e.g. `b"MYAPP"`.
+    pub const fn new(magic: &'static [u8]) -> Self {
+        Framing {
+            magic,
+            format: PhantomData,
+        }
+    }
+
+    /// Writes the value as its current version.
+    pub fn write<T, W>(&self, mut writer: W, value: &T) -> Result<(), FrameError<F::Error>>
+    where
+        T: Serialize + CurrentVersion,
+        W: Write,
+    {
+        let payload = F::to_vec(value).map_err(FrameError::Format)?;
+        writer.write_all(self.magic)?;
+        write_varint(&mut writer, T::INDEX.into())?;
+        writer.write_all(&payload)?;
+        Ok(())
+    }
+
+    /// Reads a value written as any version, up to the end of the reader.
+    pub fn read<T, R>(&self, mut reader: R) -> Result<T, FrameError<F::Error>>
+    where
+        T: for<'de> Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+        R: Read,
+    {
+        let mut magic = vec![0; self.magic.len()];
+        match reader.read_exact(&mut magic) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(FrameError::Magic),
+            res => res?,
+        }
+        if magic != self.magic {
+            return Err(FrameError::Magic);
+        }
+
+        let index = u8::try_from(read_varint(&mut reader)?).map_err(|_| FrameError::VersionId)?;
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload)?;
+
+        let value = match index == T::INDEX {
+            true => F::from_slice_seed(&payload, CurrentSeed::<T>(PhantomData)),
+            false => F::from_slice_seed(&payload, IndexSeed(index, PhantomData)),
+        };
+        value.map_err(FrameError::Format)
+    }
+}
+
+/// The magic byte of the Confluent wire format.
+const CONFLUENT_MAGIC: u8 = 0;
+
+/// Writes and reads values in the Confluent wire format of Kafka records: a
+/// zero byte and the big-endian schema id, followed by the payload encoded with `F`.
+/// Schema ids are mapped to versions by `SchemaIds`.
+#[derive(Debug, Clone, Copy)]
+pub struct Confluent<F>(PhantomData<F>);
+
+impl<F: Format> Confluent<F> {
+    /// Writes the value with the schema id of its current version.
+    pub fn to_vec<T: Serialize + SchemaIds>(value: &T) -> Result<Vec<u8>, FrameError<F::Error>> {
+        let Some(&(schema_id, _)) = T::SCHEMA_IDS.iter().find(|(_, index)| *index == T::INDEX)
+        else {
+            return Err(FrameError::Unregistered);
+        };
+        let mut bytes = vec![CONFLUENT_MAGIC];
+        bytes.extend_from_slice(&schema_id.to_be_bytes());
+        bytes.extend(F::to_vec(value).map_err(FrameError::Format)?);
+        Ok(bytes)
+    }
+
+    /// Reads a value written with the schema id of any version.
+    pub fn from_slice<'de, T>(bytes: &'de [u8]) -> Result<T, FrameError<F::Error>>
+    where
+        T: Deserialize<'de> + DeserializeVersioned + SchemaIds,
+    {
+        let Some((&CONFLUENT_MAGIC, bytes)) = bytes.split_first() else {
+            return Err(FrameError::Magic);
+        };
+        let Some((schema_id, payload)) = bytes.split_first_chunk::<4>() else {
+            return Err(FrameError::VersionId);
+        };
+        let schema_id = u32::from_be_bytes(*schema_id);
+        let Some(&(_, index)) = T::SCHEMA_IDS.iter().find(|(id, _)| *id == schema_id) else {
+            return Err(FrameError::SchemaId(schema_id));
+        };
+
+        let value = match index == T::INDEX {
+            true => F::from_slice_seed(payload, CurrentSeed::<T>(PhantomData)),
+            false => F::from_slice_seed(payload, IndexSeed(index, PhantomData)),
+        };
+        value.map_err(FrameError::Format)
+    }
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut n: u128) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read, E>(reader: &mut R) -> Result<u128, FrameError<E>> {
+    let mut n = 0;
+    for shift in (0..128).step_by(7) {
+        let mut byte = [0];
+        match reader.read_exact(&mut byte) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(FrameError::VersionId)
+            }
+            res => res?,
+        }
+        let bits = u128::from(byte[0] & 0x7f);
+        if bits << shift >> shift != bits {
+            return Err(FrameError::VersionId);
+        }
+        n |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(FrameError::VersionId)
+}
+
+/// The error of reading or writing framed data.
+#[derive(Debug)]
+pub enum FrameError<E> {
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// The data doesn't start with the magic bytes.
+    Magic,
+    /// The version id is truncated or too large.
+    VersionId,
+    /// No version is registered with the schema id.
+    SchemaId(u32),
+    /// The current version has no schema id.
+    Unregistered,
+    /// Encoding or decoding the payload failed.
+    Format(E),
+}
+
+impl<E> From<io::Error> for FrameError<E> {
+    fn from(e: io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for FrameError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Io(e) => e.fmt(f),
+            FrameError::Magic => f.write_str("the data doesn't start with the magic bytes"),
+            FrameError::VersionId => f.write_str("invalid version id"),
+            FrameError::SchemaId(id) => write!(f, "unknown schema id {id}"),
+            FrameError::Unregistered => f.write_str("the current version has no schema id"),
+            FrameError::Format(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for FrameError<E> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::tests::Adjacent;
+    use crate::format::tests::Json;
+
+    impl SchemaIds for Adjacent {
+        const SCHEMA_IDS: &'static [(u32, u8)] = &[(41, 0), (42, 1)];
+    }
+
+    const FRAMING: Framing<Json> = Framing::new(b"TEST");
+
+    #[test]
+    fn test_framing() {
+        let mut bytes = Vec::new();
+        FRAMING.write(&mut bytes, &Adjacent { value: 3 }).unwrap();
+        assert_eq!(bytes, b"TEST\x01{\"value\":3}");
+        let value: Adjacent = FRAMING.read(bytes.as_slice()).unwrap();
+        assert_eq!(value, Adjacent { value: 3 });
+
+        let old = b"TEST\x00{\"reading\":\"3\"}";
+        let value: Adjacent = FRAMING.read(old.as_slice()).unwrap();
+        assert_eq!(value, Adjacent { value: 3 });
+
+        let res = FRAMING.read::<Adjacent, _>(b"NOPE\x01{}".as_slice());
+        assert!(matches!(res, Err(FrameError::Magic)));
+        let res = FRAMING.read::<Adjacent, _>(b"TE".as_slice());
+        assert!(matches!(res, Err(FrameError::Magic)));
+        let res = FRAMING.read::<Adjacent, _>(b"TEST\x02{}".as_slice());
+        assert!(matches!(res, Err(FrameError::Format(_))));
+    }
+
+    #[test]
+    fn test_varint() {
+        for n in [0, 1, 127, 128, 300, u128::from(u64::MAX), u128::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, n).unwrap();
+            assert_eq!(read_varint::<_, ()>(&mut bytes.as_slice()).unwrap(), n);
+        }
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, 300).unwrap();
+        assert_eq!(bytes, [0xac, 0x02]);
+
+        // Truncated, and overflowing 128 bits.
+        let res = read_varint::<_, ()>(&mut [0x80].as_slice());
+        assert!(matches!(res, Err(FrameError::VersionId)));
+        let mut bytes = [0xff; 19];
+        bytes[18] = 0x7f;
+        let res = read_varint::<_, ()>(&mut bytes.as_slice());
+        assert!(matches!(res, Err(FrameError::VersionId)));
+
+        // An index that doesn't fit in a `u8`.
+        let res = FRAMING.read::<Adjacent, _>(b"TEST\x80\x02{}".as_slice());
+        assert!(matches!(res, Err(FrameError::VersionId)));
+    }
+
+    #[test]
+    fn test_confluent() {
+        let bytes = Confluent::<Json>::to_vec(&Adjacent { value: 3 }).unwrap();
+        assert_eq!(bytes, b"\x00\x00\x00\x00\x2a{\"value\":3}");
+        let value: Adjacent = Confluent::<Json>::from_slice(&bytes).unwrap();
+        assert_eq!(value, Adjacent { value: 3 });
+
+        let old = b"\x00\x00\x00\x00\x29{\"reading\":\"3\"}";
+        let value: Adjacent = Confluent::<Json>::from_slice(old).unwrap();
+        assert_eq!(value, Adjacent { value: 3 });
+
+        let res = Confluent::<Json>::from_slice::<Adjacent>(b"\x00\x00\x00\x00\x2b{}");
+        assert!(matches!(res, Err(FrameError::SchemaId(43))));
+        let res = Confluent::<Json>::from_slice::<Adjacent>(b"\x01\x00\x00\x00\x2a{}");
+        assert!(matches!(res, Err(FrameError::Magic)));
+        let res = Confluent::<Json>::from_slice::<Adjacent>(b"\x00\x00\x2a");
+        assert!(matches!(res, Err(FrameError::VersionId)));
+        assert_eq!(
+            FrameError::<serde_json::Error>::SchemaId(43).to_string(),
+            "unknown schema id 43"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! A JSON fast path, probing versions by re-parsing the raw JSON instead of
+//! buffering it as `Content`, and upgrades of untyped JSON values,
+//! newline-delimited JSON streams and JSON files.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use serde::de::DeserializeSeed;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::{DeserializeVersioned, Format, VersionErrors};
+
+/// The JSON format of serde_json, rejecting trailing characters.
+#[derive(Debug, Clone, Copy)]
+pub struct Json;
+
+impl Format for Json {
+    type Error = serde_json::Error;
+
+    fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn from_slice_seed<'de, S: DeserializeSeed<'de>>(
+        bytes: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        let mut d = serde_json::Deserializer::from_slice(bytes);
+        let value = seed.deserialize(&mut d)?;
+        d.end()?;
+        Ok(value)
+    }
+}
+
+/// Deserializes the raw JSON from any version, parsing it again for each
+/// version attempted. Parsing it is usually cheaper than allocating a
+/// `Content` tree, especially when most data is in the current version.
+pub fn from_raw_value<T: DeserializeVersioned>(raw: &RawValue) -> Result<T, VersionErrors> {
+    T::from_slice_any_version::<Json>(raw.get().as_bytes())
+}
+
+/// Deserializes the JSON value from any version, attempting each version on
+/// the value itself instead of buffering a copy.
+pub fn from_value<T: DeserializeVersioned>(value: &Value) -> Result<T, serde_json::Error> {
+    T::deserialize_cloned(value)
+}
+
+/// Upgrades the JSON value from any version to the current version, e.g. in
+/// admin tools and repair scripts working on untyped JSON.
+pub fn upgrade_value<T>(value: &Value) -> Result<Value, serde_json::Error>
+where
+    T: DeserializeVersioned + Serialize,
+{
+    serde_json::to_value(from_value::<T>(value)?)
+}
+
+/// Upgrades newline-delimited JSON from any version, writing each record in
+/// the current version on its own line, e.g. to migrate a large export file
+/// without holding it in memory. Blank lines are skipped.
+///
+/// Records that don't match any version are left out of the output and passed
+/// to `on_error` with their line number, counting from 1.
+/// Returns the number of records written, or the first error reading or writing.
+pub fn upgrade_lines<T, R, W>(
+    mut reader: R,
+    mut writer: W,
+    mut on_error: impl FnMut(u64, VersionErrors),
+) -> io::Result<u64>
+where
+    T: DeserializeVersioned + Serialize,
+    R: BufRead,
+    W: Write,
+{
+    let mut line = String::new();
+    let mut number = 0;
+    let mut written = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        number += 1;
+        let record = line.trim();
+        if record.is_empty() {
+            continue;
+        }
+        match T::from_slice_any_version::<Json>(record.as_bytes()) {
+            Ok(value) => {
+                serde_json::to_writer(&mut writer, &value)?;
+                writer.write_all(b"\n")?;
+                written += 1;
+            }
+            Err(errors) => on_error(number, errors),
+        }
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Upgrades the JSON file, or every `.json` file in the directory and its
+/// subdirectories, in place to the current version, e.g. in a small migration
+/// binary for ops. Each file is written to a temporary file next to it, then
+/// renamed over it. Files already in the current version are left alone, as
+/// told by `DeserializeVersioned::deserialize_with_version`.
+///
+/// Files that don't match any version are left as they were and passed to
+/// `on_error`. Returns the number of files upgraded, or the first error
+/// reading or writing.
+pub fn upgrade_path<T>(
+    path: &Path,
+    mut on_error: impl FnMut(&Path, VersionErrors),
+) -> io::Result<u64>
+where
+    T: DeserializeVersioned + Serialize,
+{
+    upgrade_path_with::<T>(path, &mut on_error)
+}
+
+fn upgrade_path_with<T>(
+    path: &Path,
+    on_error: &mut dyn FnMut(&Path, VersionErrors),
+) -> io::Result<u64>
+where
+    T: DeserializeVersioned + Serialize,
+{
+    if !path.is_dir() {
+        return upgrade_file::<T>(path, on_error);
+    }
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut upgraded = 0;
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "json") {
+            upgraded += upgrade_path_with::<T>(&entry, on_error)?;
+        }
+    }
+    Ok(upgraded)
+}
+
+fn upgrade_file<T>(path: &Path, on_error: &mut dyn FnMut(&Path, VersionErrors)) -> io::Result<u64>
+where
+    T: DeserializeVersioned + Serialize,
+{
+    let bytes = fs::read(path)?;
+    let mut d = serde_json::Deserializer::from_slice(&bytes);
+    let value = match T::deserialize_with_version(&mut d).and_then(|res| d.end().map(|()| res)) {
+        Ok((_, version)) if version.is_current() => return Ok(0),
+        Ok((value, _)) => value,
+        // Also reached by types that don't report which version they matched.
+        Err(_) => match T::from_slice_any_version::<Json>(&bytes) {
+            Ok(value) => value,
+            Err(errors) => {
+                on_error(path, errors);
+                return Ok(0);
+            }
+        },
+    };
+    let mut upgraded = serde_json::to_vec_pretty(&value)?;
+    upgraded.push(b'\n');
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, &upgraded)?;
+    fs::rename(&tmp, path)?;
+    Ok(1)
+}
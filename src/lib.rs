@@ -1,3 +1,40 @@
+mod audit;
+mod canonical;
+mod coerce;
+mod content;
+mod envelope;
+mod fingerprint;
+mod format;
+mod frame;
+#[cfg(feature = "json")]
+pub mod json;
+mod manifest;
+mod media_type;
+mod observe;
+mod record;
+mod registry;
+mod store;
+
+pub use audit::{Audited, Migrated};
+pub use canonical::{to_canonical, Canonical, CanonicalError};
+pub use coerce::{CoerceError, HumanDuration, Quoted, YesNo};
+pub use content::Content;
+pub use envelope::{
+    binary, tagged, BinaryEnvelope, DualWrite, Envelope, EnvelopeLayout, VersionStamp,
+    VersionStamped,
+};
+pub use fingerprint::fingerprint;
+pub use format::Format;
+pub use frame::{Confluent, FrameError, Framing};
+pub use manifest::{Manifest, ManifestErrors, ManifestVersion, VersionList};
+pub use media_type::MediaTypes;
+pub use observe::{
+    set_observer, DeprecationLog, MigrationObserver, MigrationStats, StatsSnapshot, VersionSnapshot,
+};
+pub use record::{from_query, from_record, RecordDeserializer};
+pub use registry::Registry;
+pub use store::{migrate_store, read_repair, MigrateError, Progress, RepairError, Store};
+
 /// A version id for your data.
 /// Uses `u128` to be able to store a UUID.
 pub struct Uuid<const N: u128>;
@@ -6,7 +43,115 @@ pub struct Uuid<const N: u128>;
 pub struct Num<const N: u32>;
 
 /// A semantic version number for your data.
-pub struct Sem<const A: u64, const B: u64, const C: u64>;
+/// Versions cut from a release candidate add the pre-release, e.g. `Sem<1, 2, 0, Rc<1>>`.
+pub struct Sem<const A: u64, const B: u64, const C: u64, P: PreRelease = Release>(
+    std::marker::PhantomData<P>,
+);
+
+/// Every semantic version with major version `A`, e.g. `SemMajor<1>` for `1.x.y`.
+/// For tags from producers whose exact version varies while the shape doesn't.
+pub struct SemMajor<const A: u64>;
+
+/// Every semantic version with major version `A` and minor version `B`,
+/// e.g. `SemMinor<1, 2>` for `1.2.y`.
+pub struct SemMinor<const A: u64, const B: u64>;
+
+/// The pre-release of a `Sem` version.
+pub trait PreRelease {
+    /// The pre-release identifier, e.g. `rc.1`, or `None` for a release
+    fn tag() -> Option<String>;
+
+    /// Orders the pre-releases of the same version, below the release.
+    fn rank() -> u32;
+}
+
+/// A regular release, without a pre-release.
+pub struct Release;
+
+/// An alpha pre-release, `alpha.N`.
+pub struct Alpha<const N: u32>;
+
+/// A beta pre-release, `beta.N`.
+pub struct Beta<const N: u32>;
+
+/// A release candidate, `rc.N`.
+pub struct Rc<const N: u32>;
+
+impl PreRelease for Release {
+    fn tag() -> Option<String> {
+        None
+    }
+
+    fn rank() -> u32 {
+        u32::MAX
+    }
+}
+
+macro_rules! pre_release {
+    ($($ident: ident = $tag: literal, $rank: literal;)*) => {
+        $(
+            impl<const N: u32> PreRelease for $ident<N> {
+                fn tag() -> Option<String> {
+                    Some(format!(concat!($tag, ".{}"), N))
+                }
+
+                fn rank() -> u32 {
+                    $rank << 24 | N.min(0xff_ffff)
+                }
+            }
+        )*
+    };
+}
+
+pre_release! {
+    Alpha = "alpha", 0;
+    Beta = "beta", 1;
+    Rc = "rc", 2;
+}
+
+/// A version of your data identified by its release date, e.g. `Date<2024, 3, 1>`.
+pub struct Date<const Y: u32, const M: u32, const D: u32>;
+
+/// A version of your data identified by the time it was deployed,
+/// in seconds since the Unix epoch, e.g. `Epoch<1700000000>`.
+pub struct Epoch<const T: u64>;
+
+impl<const T: u64> Epoch<T> {
+    /// Whether data produced at `produced_at`, in seconds since the Unix epoch,
+    /// predates this version, i.e. is of an older one.
+    pub fn is_before(produced_at: u64) -> bool {
+        produced_at < T
+    }
+
+    /// Like `is_before`, for a `SystemTime`.
+    pub fn is_before_time(produced_at: std::time::SystemTime) -> bool {
+        match produced_at.duration_since(std::time::UNIX_EPOCH) {
+            Ok(since) => Self::is_before(since.as_secs()),
+            Err(_) => true,
+        }
+    }
+}
+
+/// A version of your data identified by the fingerprint of its shape,
+/// computed with `fingerprint`, e.g. `Fingerprint<0x1b7c5e0f3a29d4c8>`.
+pub struct Fingerprint<const N: u64>;
+
+/// A version number within a namespace, e.g. `Ns<Billing, 3>`, so versions
+/// contributed by different crates can't collide. Declare namespaces with `version_name!`.
+pub struct Ns<N: VersionName, const V: u32>(std::marker::PhantomData<N>);
+
+/// A named version for your data, e.g. `Named<Billing2023>`.
+/// Declare names with `version_name!`, as string const generics aren't stable.
+pub struct Named<N: VersionName>(std::marker::PhantomData<N>);
+
+/// The name of a `Named` version or the namespace of an `Ns` version.
+pub trait VersionName {
+    const NAME: &'static str;
+
+    /// Orders the version among other named versions.
+    /// Named versions are unordered by default.
+    const ORDINAL: u128 = 0;
+}
 
 /// A version for your data.
 pub struct Ver<T>(std::marker::PhantomData<T>);
@@ -14,6 +159,220 @@ pub struct Ver<T>(std::marker::PhantomData<T>);
 /// The current version
 pub struct Current;
 
+/// Describes a version marker. Every marker listed in `Versions` implements it,
+/// implement it for custom markers too.
+pub trait VersionInfo {
+    /// The tag of the version in the data, e.g. `v1` or `1.2.0`, as written in
+    /// envelopes and media types. `VersionId` has both this tag and the name of
+    /// the marker, e.g. `Num<1>`, which `VersionErrors` and `VersionPolicy` use.
+    fn name() -> std::borrow::Cow<'static, str>;
+
+    /// Orders the version among other versions of the same kind.
+    /// The current version has the highest ordinal.
+    fn ordinal() -> u128;
+
+    /// Whether a version tag read from the data, e.g. `1.2.0`, denotes this version.
+    /// Defaults to comparing it with the name.
+    fn accepts(tag: &str) -> bool {
+        tag == Self::name()
+    }
+}
+
+/// Splits a semantic version tag into its numbers, ignoring any pre-release.
+fn parse_sem(tag: &str) -> Option<(u64, u64, u64)> {
+    let core = tag.split_once('-').map_or(tag, |(core, _)| core);
+    let mut numbers = core.splitn(3, '.').map(str::parse::<u64>);
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Ok(a)), Some(Ok(b)), Some(Ok(c))) => Some((a, b, c)),
+        _ => None,
+    }
+}
+
+impl<const N: u128> VersionInfo for Uuid<N> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        let hex = format!("{N:032x}");
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+        .into()
+    }
+
+    fn ordinal() -> u128 {
+        N
+    }
+}
+
+/// Accepts both `v2` and `2` as tags.
+impl<const N: u32> VersionInfo for Num<N> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        format!("v{N}").into()
+    }
+
+    fn ordinal() -> u128 {
+        N.into()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        tag.strip_prefix('v').unwrap_or(tag).parse::<u32>() == Ok(N)
+    }
+}
+
+/// The version numbers are assumed to fit in 32 bits when ordering.
+impl<const A: u64, const B: u64, const C: u64, P: PreRelease> VersionInfo for Sem<A, B, C, P> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        match P::tag() {
+            Some(tag) => format!("{A}.{B}.{C}-{tag}").into(),
+            None => format!("{A}.{B}.{C}").into(),
+        }
+    }
+
+    fn ordinal() -> u128 {
+        let clamp = |n: u64| n.min(u32::MAX.into()) as u128;
+        clamp(A) << 96 | clamp(B) << 64 | clamp(C) << 32 | P::rank() as u128
+    }
+}
+
+impl<const Y: u32, const M: u32, const D: u32> Date<Y, M, D> {
+    const VALID: () = assert!(1 <= M && M <= 12 && 1 <= D && D <= 31, "invalid date");
+}
+
+impl<const Y: u32, const M: u32, const D: u32> VersionInfo for Date<Y, M, D> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        format!("{Y:04}-{M:02}-{D:02}").into()
+    }
+
+    fn ordinal() -> u128 {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        (Y as u128) << 16 | (M as u128) << 8 | D as u128
+    }
+}
+
+/// Named after the UTC time, e.g. `2023-11-14T22:13:20Z`. Tags are either that
+/// or the plain timestamp.
+impl<const T: u64> VersionInfo for Epoch<T> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        // Civil date from days since the epoch, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let (days, secs) = (T / 86400 + 719468, T % 86400);
+        let (era, doe) = (days / 146097, days % 146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+        .into()
+    }
+
+    fn ordinal() -> u128 {
+        T.into()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        tag.parse::<u64>() == Ok(T) || tag == Self::name()
+    }
+}
+
+/// Fingerprints are unordered.
+impl<const N: u64> VersionInfo for Fingerprint<N> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        format!("{N:016x}").into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+/// Named like `billing/v3`, ordered by the number within the namespace.
+impl<N: VersionName, const V: u32> VersionInfo for Ns<N, V> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        format!("{}/v{V}", N::NAME).into()
+    }
+
+    fn ordinal() -> u128 {
+        V.into()
+    }
+}
+
+impl<N: VersionName> VersionInfo for Named<N> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        N::NAME.into()
+    }
+
+    fn ordinal() -> u128 {
+        N::ORDINAL
+    }
+}
+
+/// Ordered after every exact version in the range.
+impl<const A: u64> VersionInfo for SemMajor<A> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        format!("{A}.x").into()
+    }
+
+    fn ordinal() -> u128 {
+        Sem::<A, { u64::MAX }, { u64::MAX }>::ordinal()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        matches!(parse_sem(tag), Some((a, _, _)) if a == A)
+    }
+}
+
+/// Ordered after every exact version in the range.
+impl<const A: u64, const B: u64> VersionInfo for SemMinor<A, B> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        format!("{A}.{B}.x").into()
+    }
+
+    fn ordinal() -> u128 {
+        Sem::<A, B, { u64::MAX }>::ordinal()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        matches!(parse_sem(tag), Some((a, b, _)) if (a, b) == (A, B))
+    }
+}
+
+impl<T: VersionInfo> VersionInfo for Ver<T> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        T::name()
+    }
+
+    fn ordinal() -> u128 {
+        T::ordinal()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        T::accepts(tag)
+    }
+}
+
+impl VersionInfo for Current {
+    fn name() -> std::borrow::Cow<'static, str> {
+        "current".into()
+    }
+
+    fn ordinal() -> u128 {
+        u128::MAX
+    }
+}
+
 /// Trait for an old deserialized value that can be converted into the current schema
 pub trait FromVersion<V, Label = ()>
 where
@@ -21,25 +380,29 @@ where
 {
     type VersionType: for<'a> serde::Deserialize<'a>;
 
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
+
     /// Converts the old data into the current type
-    fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>>;
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error>;
+
+    /// Cheaply checks the buffered data before it's deserialized as this version,
+    /// e.g. for a key only this version has. Defaults to always attempting it.
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
 
     /// Tries to deserialize the FromVersion data by reference.
+    /// Fails with the reason the data doesn't match or convert.
     fn deserialize_versioned<'de, Ds: serde::Deserializer<'de>>(
-        content: &serde::__private::de::Content<'de>,
+        content: &crate::content::Content<'de>,
     ) -> Result<Self, Ds::Error> {
-        use serde::Deserialize;
-        use serde::__private::de::ContentRefDeserializer;
-
-        if let Ok(res) =
-            Self::VersionType::deserialize(ContentRefDeserializer::<Ds::Error>::new(content))
-        {
-            return Self::convert(res).map_err(serde::de::Error::custom);
-        }
-
-        Err(serde::de::Error::custom(
-            "data did not match any version type",
-        ))
+        let res = deserialize_matched(
+            <Self as FromVersion<V, Label>>::matches,
+            content,
+            std::marker::PhantomData,
+        )?;
+        <Self as FromVersion<V, Label>>::convert(res).map_err(serde::de::Error::custom)
     }
 }
 
@@ -48,146 +411,4222 @@ where
     for<'a> Self: serde::Deserialize<'a>,
 {
     type VersionType = Self;
+    type Error = std::convert::Infallible;
 
-    fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>> {
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
         Ok(v)
     }
 }
 
-/// Different supported versions. Supports at most 10 simultaneous versions.
-/// Use `LabeledVersions` if you need to support multiple version handlers for the same type.
-#[allow(clippy::type_complexity)]
-pub struct Versions<
-    V0 = (),
-    V1 = (),
-    V2 = (),
-    V3 = (),
-    V4 = (),
-    V5 = (),
-    V6 = (),
-    V7 = (),
-    V8 = (),
-    V9 = (),
->(std::marker::PhantomData<(V0, V1, V2, V3, V4, V5, V6, V7, V8, V9)>);
+/// A version whose data has a shape of its own, unlike `Current`, `Chain`,
+/// `Fallback`, `Raw` and `Matched`. `Vec`, `Option`, `Box`, `HashMap`, `BTreeMap`
+/// and arrays of a type with such a version convert it element by element.
+pub trait ShapedVersion: VersionInfo {}
 
-macro_rules! peel {
-    (last { $last: tt, }; stack={$($stack: tt,)*}) => {
-        impl_versions!(impl { $($stack,)* } for Versions);
-    };
-    (last { $first: tt, $($versions: tt,)+ }; stack={$($stack: tt,)*}) => {
-        peel!(last { $($versions,)* }; stack={ $($stack,)* $first, });
-    };
-    (last { $first: tt, $($versions: tt,)+ }) => {
-        peel!(last { $($versions,)* }; stack={ $first,});
-    };
+impl<const N: u128> ShapedVersion for Uuid<N> {}
+impl<const N: u32> ShapedVersion for Num<N> {}
+impl<const A: u64, const B: u64, const C: u64, P: PreRelease> ShapedVersion for Sem<A, B, C, P> {}
+impl<const A: u64> ShapedVersion for SemMajor<A> {}
+impl<const A: u64, const B: u64> ShapedVersion for SemMinor<A, B> {}
+impl<const Y: u32, const M: u32, const D: u32> ShapedVersion for Date<Y, M, D> {}
+impl<const T: u64> ShapedVersion for Epoch<T> {}
+impl<const N: u64> ShapedVersion for Fingerprint<N> {}
+impl<N: VersionName, const V: u32> ShapedVersion for Ns<N, V> {}
+impl<N: VersionName> ShapedVersion for Named<N> {}
+
+impl<T, V, Label> FromVersion<Ver<V>, Label> for Vec<T>
+where
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = Vec<<T as FromVersion<Ver<V>, Label>>::VersionType>;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        v.into_iter()
+            .map(<T as FromVersion<Ver<V>, Label>>::convert)
+            .collect()
+    }
 }
 
-macro_rules! impl_versions {
-    (impl { } for Versions) => {};
-    (impl { $first: tt, } for Versions) => {};
-    (impl { $first: tt, $($versions: tt,)* } for Versions) => {
-        impl<$($versions,)*> Versions<Ver<Current>, $(Ver<$versions>,)*> {
-            pub fn deserialize<'de, R, Ds: serde::Deserializer<'de>>(d: Ds) -> Result<R, Ds::Error>
-            where
-                R: FromVersion<Ver<Current>> $(+ FromVersion<Ver<$versions>>)*,
-            {
-                use serde::Deserialize;
-                use serde::__private::de::Content;
-                let content = Content::deserialize(d)?;
-                FromVersion::<Ver<Current>>::deserialize_versioned::<Ds>(&content)
-                    $(
-                        .or_else(|_| FromVersion::<Ver<$versions>>::deserialize_versioned::<Ds>(&content))
-                    )*
-            }
-        }
+impl<T, V, Label> FromVersion<Ver<V>, Label> for Option<T>
+where
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = Option<<T as FromVersion<Ver<V>, Label>>::VersionType>;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-        peel!(last { $first, $($versions, )* });
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        v.map(<T as FromVersion<Ver<V>, Label>>::convert)
+            .transpose()
     }
 }
 
-impl_versions!(impl { V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, } for Versions);
+impl<T, V, Label> FromVersion<Ver<V>, Label> for Box<T>
+where
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = <T as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<Ver<V>, Label>>::convert(v).map(Box::new)
+    }
+}
 
-    #[test]
-    fn test_legacy() {
-        struct OldString;
-        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-        struct Field3(u64);
+impl<T, V, Label> FromVersion<Ver<V>, Label> for std::sync::Arc<T>
+where
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = <T as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-        struct New {
-            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Uuid<1>>>::deserialize")]
-            value: u32,
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<Ver<V>, Label>>::convert(v).map(std::sync::Arc::new)
+    }
+}
 
-            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<OldString>>::deserialize")]
-            value2: u32,
+impl<T, V, Label> FromVersion<Ver<V>, Label> for std::rc::Rc<T>
+where
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = <T as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Num<1>>>::deserialize")]
-            value3: Field3,
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<Ver<V>, Label>>::convert(v).map(std::rc::Rc::new)
+    }
+}
 
-            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Sem<0, 0, 1>>>::deserialize")]
-            value4: u32,
-        }
+/// Old versions are always converted to `Cow::Owned`.
+impl<'c, T, V, Label> FromVersion<Ver<V>, Label> for std::borrow::Cow<'c, T>
+where
+    T: ToOwned + ?Sized + 'c,
+    T::Owned: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = <T::Owned as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T::Owned as FromVersion<Ver<V>, Label>>::Error;
 
-        impl FromVersion<Ver<Sem<0, 0, 1>>> for u32 {
-            type VersionType = String;
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T::Owned as FromVersion<Ver<V>, Label>>::convert(v).map(std::borrow::Cow::Owned)
+    }
+}
 
-            fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>> {
-                Ok(v.parse::<u32>()? + 300)
-            }
-        }
+impl<K, T, S, V, Label> FromVersion<Ver<V>, Label> for std::collections::HashMap<K, T, S>
+where
+    K: for<'a> serde::Deserialize<'a> + Eq + std::hash::Hash,
+    T: FromVersion<Ver<V>, Label>,
+    S: std::hash::BuildHasher + Default,
+    V: ShapedVersion,
+{
+    type VersionType =
+        std::collections::HashMap<K, <T as FromVersion<Ver<V>, Label>>::VersionType, S>;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-        impl FromVersion<Ver<Uuid<1>>> for u32 {
-            type VersionType = String;
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        v.into_iter()
+            .map(|(key, value)| Ok((key, <T as FromVersion<Ver<V>, Label>>::convert(value)?)))
+            .collect()
+    }
+}
 
-            fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>> {
-                Ok(v.parse()?)
-            }
-        }
+impl<K, T, V, Label> FromVersion<Ver<V>, Label> for std::collections::BTreeMap<K, T>
+where
+    K: for<'a> serde::Deserialize<'a> + Ord,
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType =
+        std::collections::BTreeMap<K, <T as FromVersion<Ver<V>, Label>>::VersionType>;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
 
-        impl FromVersion<Ver<OldString>> for u32 {
-            type VersionType = String;
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        v.into_iter()
+            .map(|(key, value)| Ok((key, <T as FromVersion<Ver<V>, Label>>::convert(value)?)))
+            .collect()
+    }
+}
 
-            fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>> {
-                Ok(v.parse::<Self>()? + 100)
-            }
+/// For the array lengths serde deserializes, up to 32.
+impl<T, V, Label, const N: usize> FromVersion<Ver<V>, Label> for [T; N]
+where
+    for<'a> [T; N]: serde::Deserialize<'a>,
+    for<'a> [<T as FromVersion<Ver<V>, Label>>::VersionType; N]: serde::Deserialize<'a>,
+    T: FromVersion<Ver<V>, Label>,
+    V: ShapedVersion,
+{
+    type VersionType = [<T as FromVersion<Ver<V>, Label>>::VersionType; N];
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        let converted = v
+            .into_iter()
+            .map(<T as FromVersion<Ver<V>, Label>>::convert)
+            .collect::<Result<Vec<_>, _>>()?;
+        match converted.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("the array has N elements"),
         }
+    }
+}
 
-        impl FromVersion<Ver<Num<1>>> for Field3 {
-            type VersionType = String;
+/// Trait for an old deserialized value whose `VersionType` borrows from the input,
+/// e.g. `&'de str` or `Cow<'de, str>`, so probing it doesn't allocate.
+/// Implemented for every `FromVersion`.
+pub trait FromVersionBorrowed<'de, V, Label = ()>: Sized {
+    type VersionType: serde::Deserialize<'de>;
 
-            fn convert(v: Self::VersionType) -> Result<Self, Box<dyn std::error::Error>> {
-                Ok(Self(v.parse::<u64>()? + 200))
-            }
-        }
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
 
-        #[derive(serde::Serialize, serde::Deserialize)]
-        struct LegacyData {
-            value: String,
-            value2: String,
-            value3: String,
-            value4: String,
-        }
+    /// Converts the old data into the current type
+    fn convert_borrowed(v: Self::VersionType) -> Result<Self, Self::Error>;
 
-        let legacy = serde_json::to_string(&LegacyData {
-            value: String::from("100"),
-            value2: String::from("100"),
-            value3: String::from("100"),
-            value4: String::from("100"),
-        })
-        .unwrap();
+    /// Cheaply checks the buffered data before it's deserialized as this version.
+    /// See [`FromVersion::matches`].
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
 
-        assert_eq!(
-            serde_json::from_str::<New>(&legacy).unwrap(),
-            New {
-                value: 100,
-                value2: 200,
-                value3: Field3(300),
-                value4: 400,
-            }
+    /// Tries to deserialize the FromVersionBorrowed data by reference.
+    fn deserialize_versioned_borrowed<Ds: serde::Deserializer<'de>>(
+        content: &crate::content::Content<'de>,
+    ) -> Result<Self, Ds::Error> {
+        let res = deserialize_matched(
+            <Self as FromVersionBorrowed<'de, V, Label>>::matches,
+            content,
+            std::marker::PhantomData,
+        )?;
+        <Self as FromVersionBorrowed<'de, V, Label>>::convert_borrowed(res)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<'de, T, V, Label> FromVersionBorrowed<'de, V, Label> for T
+where
+    T: FromVersion<V, Label>,
+{
+    type VersionType = <T as FromVersion<V, Label>>::VersionType;
+    type Error = <T as FromVersion<V, Label>>::Error;
+
+    fn convert_borrowed(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<V, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<V, Label>>::matches(content)
+    }
+}
+
+/// Trait for an old deserialized value that is converted into the current schema
+/// with a caller-supplied context, such as lookup tables or configuration.
+/// Implemented for every `FromVersion`, ignoring the context.
+pub trait FromVersionWith<V, Ctx: ?Sized, Label = ()>
+where
+    for<'a> Self: serde::Deserialize<'a>,
+{
+    type VersionType: for<'a> serde::Deserialize<'a>;
+
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
+
+    /// Converts the old data into the current type
+    fn convert_with(v: Self::VersionType, ctx: &Ctx) -> Result<Self, Self::Error>;
+
+    /// Cheaply checks the buffered data before it's deserialized as this version.
+    /// See [`FromVersion::matches`].
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
+
+    /// Tries to deserialize the FromVersionWith data by reference.
+    fn deserialize_versioned_with<'de, Ds: serde::Deserializer<'de>>(
+        content: &crate::content::Content<'de>,
+        ctx: &Ctx,
+    ) -> Result<Self, Ds::Error> {
+        let res = deserialize_matched(
+            <Self as FromVersionWith<V, Ctx, Label>>::matches,
+            content,
+            std::marker::PhantomData,
+        )?;
+        <Self as FromVersionWith<V, Ctx, Label>>::convert_with(res, ctx)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T, V, Ctx: ?Sized, Label> FromVersionWith<V, Ctx, Label> for T
+where
+    T: FromVersion<V, Label>,
+{
+    type VersionType = <T as FromVersion<V, Label>>::VersionType;
+    type Error = <T as FromVersion<V, Label>>::Error;
+
+    fn convert_with(v: Self::VersionType, _ctx: &Ctx) -> Result<Self, Self::Error> {
+        <T as FromVersion<V, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<V, Label>>::matches(content)
+    }
+}
+
+/// Trait for an old deserialized value whose conversion can report non-fatal
+/// issues, such as lossy coercions or defaulted fields, without failing.
+/// Implemented for every `FromVersion`, reporting nothing.
+pub trait FromVersionWarn<V, Label = ()>
+where
+    for<'a> Self: serde::Deserialize<'a>,
+{
+    type VersionType: for<'a> serde::Deserialize<'a>;
+
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
+
+    /// Converts the old data into the current type, pushing any issues to `warnings`
+    fn convert_warn(v: Self::VersionType, warnings: &mut Warnings) -> Result<Self, Self::Error>;
+
+    /// Cheaply checks the buffered data before it's deserialized as this version.
+    /// See [`FromVersion::matches`].
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
+
+    /// Tries to deserialize the FromVersionWarn data by reference.
+    fn deserialize_versioned_warn<'de, Ds: serde::Deserializer<'de>>(
+        content: &crate::content::Content<'de>,
+        warnings: &mut Warnings,
+    ) -> Result<Self, Ds::Error> {
+        let res = deserialize_matched(
+            <Self as FromVersionWarn<V, Label>>::matches,
+            content,
+            std::marker::PhantomData,
+        )?;
+        <Self as FromVersionWarn<V, Label>>::convert_warn(res, warnings)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T, V, Label> FromVersionWarn<V, Label> for T
+where
+    T: FromVersion<V, Label>,
+{
+    type VersionType = <T as FromVersion<V, Label>>::VersionType;
+    type Error = <T as FromVersion<V, Label>>::Error;
+
+    fn convert_warn(v: Self::VersionType, _warnings: &mut Warnings) -> Result<Self, Self::Error> {
+        <T as FromVersion<V, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<V, Label>>::matches(content)
+    }
+}
+
+/// The non-fatal issues reported while converting a value.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    warnings: Vec<String>,
+}
+
+impl Warnings {
+    /// Reports an issue
+    pub fn push(&mut self, warning: impl std::fmt::Display) {
+        self.warnings.push(warning.to_string());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.warnings.iter().map(String::as_str)
+    }
+}
+
+/// Trait for an old value that is deserialized through a `DeserializeSeed`, so
+/// state such as interners or id maps can be injected into each version.
+/// Implemented for every `FromVersion`, ignoring the state.
+pub trait FromVersionSeed<V, State: ?Sized, Label = ()>: Sized {
+    type VersionType;
+
+    /// The seed the old data is deserialized with
+    type Seed<'s>: for<'de> serde::de::DeserializeSeed<'de, Value = Self::VersionType>
+    where
+        State: 's;
+
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
+
+    /// Creates the seed for the old data from the caller's state
+    fn seed(state: &mut State) -> Self::Seed<'_>;
+
+    /// Converts the old data into the current type
+    fn convert_seeded(v: Self::VersionType) -> Result<Self, Self::Error>;
+
+    /// Cheaply checks the buffered data before it's deserialized as this version.
+    /// See [`FromVersion::matches`].
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
+
+    /// Tries to deserialize the FromVersionSeed data by reference.
+    fn deserialize_versioned_seed<'de, Ds: serde::Deserializer<'de>>(
+        content: &crate::content::Content<'de>,
+        state: &mut State,
+    ) -> Result<Self, Ds::Error> {
+        let res = deserialize_matched(
+            <Self as FromVersionSeed<V, State, Label>>::matches,
+            content,
+            <Self as FromVersionSeed<V, State, Label>>::seed(state),
+        )?;
+        <Self as FromVersionSeed<V, State, Label>>::convert_seeded(res)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T, V, State: ?Sized, Label> FromVersionSeed<V, State, Label> for T
+where
+    T: FromVersion<V, Label>,
+{
+    type VersionType = <T as FromVersion<V, Label>>::VersionType;
+    type Seed<'s>
+        = std::marker::PhantomData<Self::VersionType>
+    where
+        State: 's;
+    type Error = <T as FromVersion<V, Label>>::Error;
+
+    fn seed(_state: &mut State) -> Self::Seed<'_> {
+        std::marker::PhantomData
+    }
+
+    fn convert_seeded(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<V, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<V, Label>>::matches(content)
+    }
+}
+
+/// Trait for an old deserialized value that is converted into the current schema
+/// asynchronously, e.g. when the conversion needs to fetch referenced records.
+/// Implemented for every `FromVersion` whose value and error are `Send`.
+pub trait FromVersionAsync<V, Label = ()>
+where
+    for<'a> Self: serde::Deserialize<'a>,
+{
+    type VersionType: for<'a> serde::Deserialize<'a>;
+
+    /// The error returned when the old data can't be converted
+    type Error: std::fmt::Display;
+
+    /// Converts the old data into the current type
+    fn convert_async(
+        v: Self::VersionType,
+    ) -> impl std::future::Future<Output = Result<Self, Self::Error>> + Send;
+
+    /// Cheaply checks the buffered data before it's deserialized as this version.
+    /// See [`FromVersion::matches`].
+    fn matches(_content: &Content<'_>) -> bool {
+        true
+    }
+}
+
+impl<T, V, Label> FromVersionAsync<V, Label> for T
+where
+    T: FromVersion<V, Label> + Send,
+    <T as FromVersion<V, Label>>::Error: Send,
+{
+    type VersionType = <T as FromVersion<V, Label>>::VersionType;
+    type Error = <T as FromVersion<V, Label>>::Error;
+
+    fn convert_async(
+        v: Self::VersionType,
+    ) -> impl std::future::Future<Output = Result<Self, Self::Error>> + Send {
+        std::future::ready(<T as FromVersion<V, Label>>::convert(v))
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<V, Label>>::matches(content)
+    }
+}
+
+/// Deserializes the old data of version `V`, returning the pending conversion.
+/// The conversion is returned as a future so only `Send` values are held across awaits.
+fn deserialize_versioned_async<'de, R, V, L, E>(
+    content: &crate::content::Content<'de>,
+) -> Result<impl std::future::Future<Output = Result<R, E>> + Send, E>
+where
+    R: FromVersionAsync<V, L>,
+    E: serde::de::Error,
+{
+    let res = deserialize_matched(
+        <R as FromVersionAsync<V, L>>::matches,
+        content,
+        std::marker::PhantomData::<<R as FromVersionAsync<V, L>>::VersionType>,
+    )?;
+    let future = <R as FromVersionAsync<V, L>>::convert_async(res);
+    Ok(async move { future.await.map_err(serde::de::Error::custom) })
+}
+
+/// Deserializes the buffered data with `seed`, unless the version's `matches`
+/// rejects it.
+fn deserialize_matched<'de, S, E>(
+    matches: fn(&Content<'_>) -> bool,
+    content: &Content<'de>,
+    seed: S,
+) -> Result<S::Value, E>
+where
+    S: serde::de::DeserializeSeed<'de>,
+    E: serde::de::Error,
+{
+    if !matches(content) {
+        return Err(unmatched());
+    }
+    seed.deserialize(crate::content::ContentRefDeserializer::new(content))
+}
+
+fn unmatched<E: serde::de::Error>() -> E {
+    E::custom("data was rejected by the version matcher")
+}
+
+/// A cheap predicate over the buffered data, used with `Matched`.
+pub trait VersionMatcher {
+    /// Whether the data can be of the version
+    fn matches(content: &Content<'_>) -> bool;
+}
+
+/// Matches strings.
+pub struct IsString;
+
+impl VersionMatcher for IsString {
+    fn matches(content: &Content<'_>) -> bool {
+        content.as_str().is_some()
+    }
+}
+
+/// Matches sequences.
+pub struct IsSeq;
+
+impl VersionMatcher for IsSeq {
+    fn matches(content: &Content<'_>) -> bool {
+        matches!(content, Content::Seq(_))
+    }
+}
+
+/// Matches maps.
+pub struct IsMap;
+
+impl VersionMatcher for IsMap {
+    fn matches(content: &Content<'_>) -> bool {
+        matches!(content, Content::Map(_))
+    }
+}
+
+/// Version `V`, only attempted when `M` matches the data,
+/// e.g. `Ver<Matched<IsString, Num<1>>>`. Converts with the `FromVersion` impl of `V`.
+pub struct Matched<M, V>(std::marker::PhantomData<(M, V)>);
+
+impl<T, M, V, Label> FromVersion<Ver<Matched<M, V>>, Label> for T
+where
+    T: FromVersion<Ver<V>, Label>,
+    M: VersionMatcher,
+{
+    type VersionType = <T as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<Ver<V>, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        M::matches(content) && <T as FromVersion<Ver<V>, Label>>::matches(content)
+    }
+}
+
+impl<M, V: VersionInfo> VersionInfo for Matched<M, V> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        V::name()
+    }
+
+    fn ordinal() -> u128 {
+        V::ordinal()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        V::accepts(tag)
+    }
+}
+
+/// Version `V` if `ON`, otherwise a placeholder that never matches, so a version
+/// can be compiled out of some builds without repeating the list, e.g.
+/// `Ver<Gated<Num<1>, { cfg!(feature = "legacy") }>>` along with
+/// `#[cfg(feature = "legacy")]` on the `FromVersion<Ver<Num<1>>>` impl and its
+/// `VersionType`.
+pub struct Gated<V, const ON: bool>(std::marker::PhantomData<V>);
+
+impl<T, V, Label> FromVersion<Ver<Gated<V, true>>, Label> for T
+where
+    T: FromVersion<Ver<V>, Label>,
+{
+    type VersionType = <T as FromVersion<Ver<V>, Label>>::VersionType;
+    type Error = <T as FromVersion<Ver<V>, Label>>::Error;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        <T as FromVersion<Ver<V>, Label>>::convert(v)
+    }
+
+    fn matches(content: &Content<'_>) -> bool {
+        <T as FromVersion<Ver<V>, Label>>::matches(content)
+    }
+}
+
+impl<T, V, Label> FromVersion<Ver<Gated<V, false>>, Label> for T
+where
+    for<'a> Self: serde::Deserialize<'a>,
+{
+    type VersionType = serde::de::IgnoredAny;
+    type Error = &'static str;
+
+    fn convert(_: Self::VersionType) -> Result<Self, Self::Error> {
+        Err("the version is compiled out of this build")
+    }
+
+    fn matches(_content: &Content<'_>) -> bool {
+        false
+    }
+}
+
+impl<V: VersionInfo, const ON: bool> VersionInfo for Gated<V, ON> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        V::name()
+    }
+
+    fn ordinal() -> u128 {
+        V::ordinal()
+    }
+
+    fn accepts(tag: &str) -> bool {
+        ON && V::accepts(tag)
+    }
+}
+
+/// A `VersionType` accepting either of two shapes, tried in order, for versions
+/// whose data was written in several shapes. Nest it for more,
+/// e.g. `OneOf<u64, OneOf<String, f64>>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OneOf<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<'de, A, B> serde::Deserialize<'de> for OneOf<A, B>
+where
+    A: serde::Deserialize<'de>,
+    B: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use crate::content::ContentRefDeserializer;
+
+        let content = Content::deserialize(d)?;
+        let first = match A::deserialize(ContentRefDeserializer::<D::Error>::new(&content)) {
+            Ok(v) => return Ok(OneOf::First(v)),
+            Err(err) => err,
+        };
+        match B::deserialize(ContentRefDeserializer::<D::Error>::new(&content)) {
+            Ok(v) => Ok(OneOf::Second(v)),
+            Err(second) => Err(serde::de::Error::custom(format_args!(
+                "data did not match any shape ({}: {first}; {}: {second})",
+                marker_name::<A>(),
+                marker_name::<B>(),
+            ))),
+        }
+    }
+}
+
+/// Invariants checked after deserializing any version, with `deserialize_validated`
+/// or `#[versioned(validate)]`.
+pub trait Validate {
+    /// The error returned when the value is invalid
+    type Error: std::fmt::Display;
+
+    /// Checks the deserialized value
+    fn validate(&self) -> Result<(), Self::Error>;
+}
+
+/// Deserializes the old data of version `V` without buffering it.
+fn deserialize_unbuffered<'de, R, V, L, Ds>(d: Ds) -> Result<R, Ds::Error>
+where
+    R: FromVersionBorrowed<'de, V, L>,
+    Ds: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    let res = <R as FromVersionBorrowed<'de, V, L>>::VersionType::deserialize(d)?;
+    <R as FromVersionBorrowed<'de, V, L>>::convert_borrowed(res).map_err(serde::de::Error::custom)
+}
+
+/// Deserializes the data as the version at `index` of a `Versions`, without
+/// buffering it, and reports the attempt to the observer.
+fn deserialize_selected<'de, R, Ds: serde::Deserializer<'de>>(
+    index: usize,
+    names: VersionNames,
+    deserialize: fn(Ds) -> Result<R, Ds::Error>,
+    d: Ds,
+) -> Result<R, Ds::Error> {
+    let name = (names.marker)();
+    if !installed_policy().permits(&name) {
+        return Err(serde::de::Error::custom(format_args!(
+            "the policy denies version {name}"
+        )));
+    }
+    let events = observe::Events::new::<R>(None);
+    events.attempt(index, names);
+    match deserialize(d) {
+        Ok(res) => {
+            events.matched(index, names);
+            Ok(res)
+        }
+        Err(err) => {
+            events.rejected(index, names, &err.to_string());
+            Err(err)
+        }
+    }
+}
+
+/// Decodes the bytes as one version, see `deserialize_unbuffered`.
+fn from_slice_unbuffered<'de, F, R, V, L>(bytes: &'de [u8]) -> Result<R, F::Error>
+where
+    F: Format,
+    R: FromVersionBorrowed<'de, V, L>,
+{
+    F::from_slice_seed(bytes, Unbuffered::<R, V, L>(std::marker::PhantomData))
+}
+
+struct Unbuffered<R, V, L>(std::marker::PhantomData<(R, V, L)>);
+
+impl<'de, R: FromVersionBorrowed<'de, V, L>, V, L> serde::de::DeserializeSeed<'de>
+    for Unbuffered<R, V, L>
+{
+    type Value = R;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<R, D::Error> {
+        deserialize_unbuffered::<R, V, L, D>(d)
+    }
+}
+
+/// A single migration step, from the shape of the previous version into `Self`.
+pub trait Upgrade<Prev>: Sized {
+    /// The error returned when the previous data can't be upgraded
+    type Error: std::fmt::Display;
+
+    /// Upgrades the previous data
+    fn upgrade(prev: Prev) -> Result<Self, Self::Error>;
+}
+
+/// A single migration step back, from `Self` into the shape of the previous version.
+/// Steps compose with `Chain` versions, so `IntoVersion<Ver<Chain<V1, Chain<V2>>>>`
+/// downgrades the current type into `V2` and `V2` into `V1`.
+pub trait Downgrade<Prev> {
+    /// The error returned when the data can't be represented in the previous shape
+    type Error: std::fmt::Display;
+
+    /// Downgrades the data
+    fn downgrade(&self) -> Result<Prev, Self::Error>;
+}
+
+/// A migration step in both directions, between the shape of the previous
+/// version and `Self`, so upgrades and downgrades are kept in one place.
+/// Implies `Upgrade` and `Downgrade`, so it is read with `Chain` versions, and
+/// written as a `Chain` version with `IntoVersion`.
+pub trait VersionLens<Prev>: Sized {
+    /// The error returned when the data can't be converted either way
+    type Error: std::fmt::Display;
+
+    /// Upgrades the previous data
+    fn upgrade(prev: Prev) -> Result<Self, Self::Error>;
+
+    /// Converts the data back into the previous shape
+    fn downgrade(&self) -> Result<Prev, Self::Error>;
+}
+
+impl<T: VersionLens<Prev>, Prev> Upgrade<Prev> for T {
+    type Error = T::Error;
+
+    fn upgrade(prev: Prev) -> Result<Self, Self::Error> {
+        <T as VersionLens<Prev>>::upgrade(prev)
+    }
+}
+
+impl<T: VersionLens<Prev>, Prev> Downgrade<Prev> for T {
+    type Error = T::Error;
+
+    fn downgrade(&self) -> Result<Prev, Self::Error> {
+        <T as VersionLens<Prev>>::downgrade(self)
+    }
+}
+
+/// A version with data of shape `Prev`, upgraded with `Upgrade` into the
+/// `VersionType` of version `Next`, and from there into the current type.
+/// Chains compose, e.g. `Ver<Chain<V1, Chain<V2>>>` upgrades `V1` into `V2`
+/// and `V2` into the current type.
+pub struct Chain<Prev, Next = Current>(std::marker::PhantomData<(Prev, Next)>);
+
+/// A terminal version that accepts any data and produces a value from `F`,
+/// so tolerant readers don't fail on data matching no version.
+/// List it last, e.g. `Versions<Ver<Current>, Ver<Num<1>>, Ver<Fallback>>`.
+pub struct Fallback<F = DefaultOf>(std::marker::PhantomData<F>);
+
+/// Provides the value a `Fallback` version produces.
+pub trait FallbackValue<T> {
+    fn fallback() -> T;
+}
+
+/// Falls back to `T::default()`.
+pub struct DefaultOf;
+
+impl<T: Default> FallbackValue<T> for DefaultOf {
+    fn fallback() -> T {
+        T::default()
+    }
+}
+
+impl<T, F, Label> FromVersion<Ver<Fallback<F>>, Label> for T
+where
+    for<'a> Self: serde::Deserialize<'a>,
+    F: FallbackValue<T>,
+{
+    type VersionType = serde::de::IgnoredAny;
+    type Error = std::convert::Infallible;
+
+    fn convert(_: Self::VersionType) -> Result<Self, Self::Error> {
+        Ok(F::fallback())
+    }
+}
+
+impl<F> VersionInfo for Fallback<F> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        "fallback".into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+/// A terminal version that accepts any data and hands it to `H` as it was
+/// read, e.g. to log, quarantine or recover unrecognized data.
+/// List it last, e.g. `Versions<Ver<Current>, Ver<Num<1>>, Ver<Raw<Quarantine>>>`.
+pub struct Raw<H>(std::marker::PhantomData<H>);
+
+/// Converts the data of a `Raw` version.
+pub trait RawHandler<T> {
+    /// The error returned when the data can't be recovered
+    type Error: std::fmt::Display;
+
+    fn handle(content: Content<'static>) -> Result<T, Self::Error>;
+}
+
+/// The buffered data of a `Raw` version, detached from the input.
+pub struct RawContent(Content<'static>);
+
+impl<'de> serde::Deserialize<'de> for RawContent {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Content::deserialize(d).map(|content| RawContent(content.into_owned()))
+    }
+}
+
+impl<T, H, Label> FromVersion<Ver<Raw<H>>, Label> for T
+where
+    for<'a> Self: serde::Deserialize<'a>,
+    H: RawHandler<T>,
+{
+    type VersionType = RawContent;
+    type Error = H::Error;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        H::handle(v.0)
+    }
+}
+
+impl<H> VersionInfo for Raw<H> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        "raw".into()
+    }
+
+    fn ordinal() -> u128 {
+        0
+    }
+}
+
+/// Named after the shape, ordered right before the version it upgrades into.
+impl<Prev, Next: VersionInfo> VersionInfo for Chain<Prev, Next> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        std::any::type_name::<Prev>().into()
+    }
+
+    fn ordinal() -> u128 {
+        Next::ordinal().saturating_sub(1)
+    }
+}
+
+impl<T, Prev, Next> FromVersion<Ver<Chain<Prev, Next>>> for T
+where
+    for<'a> Self: serde::Deserialize<'a>,
+    for<'a> Prev: serde::Deserialize<'a>,
+    T: FromVersion<Ver<Next>>,
+    <T as FromVersion<Ver<Next>>>::VersionType: Upgrade<Prev>,
+{
+    type VersionType = Prev;
+    type Error = ChainError<
+        <<T as FromVersion<Ver<Next>>>::VersionType as Upgrade<Prev>>::Error,
+        <T as FromVersion<Ver<Next>>>::Error,
+    >;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        let next = Upgrade::upgrade(v).map_err(ChainError::Upgrade)?;
+        <T as FromVersion<Ver<Next>>>::convert(next).map_err(ChainError::Convert)
+    }
+}
+
+/// Downgrades into the `VersionType` of version `Next`, and from there into `Prev`.
+impl<T, Prev, Next> IntoVersion<Ver<Chain<Prev, Next>>> for T
+where
+    T: IntoVersion<Ver<Next>>,
+    <T as IntoVersion<Ver<Next>>>::VersionType: Downgrade<Prev>,
+    Prev: serde::Serialize,
+{
+    type VersionType = Prev;
+    type Error = ChainError<
+        <<T as IntoVersion<Ver<Next>>>::VersionType as Downgrade<Prev>>::Error,
+        <T as IntoVersion<Ver<Next>>>::Error,
+    >;
+
+    fn convert(&self) -> Result<Self::VersionType, Self::Error> {
+        let next = IntoVersion::<Ver<Next>>::convert(self).map_err(ChainError::Convert)?;
+        Downgrade::downgrade(&next).map_err(ChainError::Upgrade)
+    }
+}
+
+/// The error of a `Chain` version.
+#[derive(Debug)]
+pub enum ChainError<U, C> {
+    /// Upgrading to, or downgrading from, the next version failed.
+    Upgrade(U),
+    /// Converting the next version failed.
+    Convert(C),
+}
+
+impl<U: std::fmt::Display, C: std::fmt::Display> std::fmt::Display for ChainError<U, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainError::Upgrade(e) => e.fmt(f),
+            ChainError::Convert(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Trait for a current value that can be converted into an old schema
+pub trait IntoVersion<V, Label = ()> {
+    type VersionType: serde::Serialize;
+
+    /// The error returned when the data can't be represented in the old schema
+    type Error: std::fmt::Display;
+
+    /// Converts the current data into the old type
+    fn convert(&self) -> Result<Self::VersionType, Self::Error>;
+
+    /// Serializes the data as the old type.
+    /// Use with `#[serde(serialize_with = "IntoVersion::<Ver<...>>::serialize_versioned")]`.
+    fn serialize_versioned<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::Serialize;
+
+        self.convert()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(s)
+    }
+}
+
+impl<T> IntoVersion<Ver<Current>> for T
+where
+    T: serde::Serialize + Clone,
+{
+    type VersionType = Self;
+    type Error = std::convert::Infallible;
+
+    fn convert(&self) -> Result<Self::VersionType, Self::Error> {
+        Ok(self.clone())
+    }
+
+    fn serialize_versioned<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.serialize(s)
+    }
+}
+
+/// A type-erased conversion error, for converters combining several error types.
+pub struct ConvertError(Box<dyn std::fmt::Display + Send + Sync>);
+
+impl ConvertError {
+    /// Wraps any displayable error.
+    pub fn new<E: std::fmt::Display + Send + Sync + 'static>(e: E) -> Self {
+        ConvertError(Box::new(e))
+    }
+}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::fmt::Debug for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ConvertError")
+            .field(&self.0.to_string())
+            .finish()
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// Identifies the version data was deserialized from, both by its marker,
+/// as in `VersionErrors` and `VersionPolicy`, and by its tag, as in envelopes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VersionId {
+    index: usize,
+    name: String,
+    tag: String,
+}
+
+impl VersionId {
+    /// The position of the version in its `Versions`, the current version is 0
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The type name of the version marker, e.g. `Num<1>`, same as `marker`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type name of the version marker, e.g. `Num<1>`
+    pub fn marker(&self) -> &str {
+        &self.name
+    }
+
+    /// The tag of the version, e.g. `v1`, see `VersionInfo::name`.
+    /// It is `current` for the current version.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Whether the data was in the current version
+    pub fn is_current(&self) -> bool {
+        self.index == 0
+    }
+}
+
+impl std::fmt::Display for VersionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// The order in which `Versions` attempts its versions.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ProbeOrder<'a> {
+    /// The current version, then the old versions in declaration order
+    #[default]
+    NewestFirst,
+    /// The old versions in reverse declaration order, then the current version
+    OldestFirst,
+    /// Only the versions at these indices, where the current version is 0.
+    /// Indices past the last version are skipped.
+    Custom(&'a [usize]),
+}
+
+impl<'a> ProbeOrder<'a> {
+    fn indices(self, len: usize) -> impl Iterator<Item = usize> + 'a {
+        let (newest, oldest, custom) = match self {
+            ProbeOrder::NewestFirst => (0..len, 0..0, &[][..]),
+            ProbeOrder::OldestFirst => (0..0, 0..len, &[][..]),
+            ProbeOrder::Custom(indices) => (0..0, 0..0, indices),
+        };
+        let custom = custom.iter().copied().filter(move |&index| index < len);
+        newest.chain(oldest.rev()).chain(custom)
+    }
+}
+
+/// Which versions are attempted at runtime, by the names of their markers,
+/// e.g. to stop accepting an old shape without recompiling.
+/// Permits every version by default. Install one with `set_policy`, or pass
+/// one to `deserialize_with_policy`.
+#[derive(Debug, Clone, Default)]
+pub struct VersionPolicy {
+    allowed: Option<Vec<String>>,
+    denied: Vec<String>,
+    sunsets: Vec<(String, std::time::SystemTime)>,
+    on_sunset: Option<fn(&str)>,
+    now: Option<std::time::SystemTime>,
+}
+
+impl VersionPolicy {
+    pub const fn new() -> Self {
+        VersionPolicy {
+            allowed: None,
+            denied: Vec::new(),
+            sunsets: Vec::new(),
+            on_sunset: None,
+            now: None,
+        }
+    }
+
+    /// Parses a comma-separated list of marker names, e.g. `!Num<1>, !Sem<1, 2, 0>`.
+    /// Names prefixed with `!` are denied, and if any names aren't, only those are allowed.
+    /// Commas within angle brackets are part of the name.
+    pub fn parse(spec: &str) -> Self {
+        let mut depth = 0usize;
+        let names = spec.split(|c| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            c == ',' && depth == 0
+        });
+        let mut policy = VersionPolicy::new();
+        for name in names.map(str::trim).filter(|name| !name.is_empty()) {
+            match name.strip_prefix('!') {
+                Some(name) => policy = policy.deny(name.trim()),
+                None => policy = policy.allow(name),
+            }
+        }
+        policy
+    }
+
+    /// Parses the policy from the environment variable, see `parse`.
+    /// Permits every version if the variable isn't set.
+    pub fn from_env(var: &str) -> Self {
+        std::env::var(var).map_or_else(|_| VersionPolicy::new(), |spec| VersionPolicy::parse(&spec))
+    }
+
+    /// Allows the version, so versions that aren't allowed are denied.
+    pub fn allow(mut self, name: impl Into<String>) -> Self {
+        self.allowed.get_or_insert_with(Vec::new).push(name.into());
+        self
+    }
+
+    /// Denies the version.
+    pub fn deny(mut self, name: impl Into<String>) -> Self {
+        self.denied.push(name.into());
+        self
+    }
+
+    /// Sunsets the version at the time, after which it is denied, or only
+    /// reported to the `warn_after_sunset` callback.
+    pub fn sunset(mut self, name: impl Into<String>, at: std::time::SystemTime) -> Self {
+        self.sunsets.push((name.into(), at));
+        self
+    }
+
+    /// Keeps attempting versions past their sunset, calling `warn` with the
+    /// name of the version whenever data of one is read.
+    pub fn warn_after_sunset(mut self, warn: fn(&str)) -> Self {
+        self.on_sunset = Some(warn);
+        self
+    }
+
+    /// Checks sunsets against the time instead of the system clock, e.g. to
+    /// check what a policy will deny in the future.
+    pub fn at(mut self, now: std::time::SystemTime) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    /// Whether the version with the marker name is past its sunset.
+    pub fn is_past_sunset(&self, name: &str) -> bool {
+        let now = self.now.unwrap_or_else(std::time::SystemTime::now);
+        self.sunsets
+            .iter()
+            .any(|(sunset, at)| same_name(sunset, name) && *at <= now)
+    }
+
+    /// Whether the version with the marker name is attempted.
+    pub fn permits(&self, name: &str) -> bool {
+        let allowed = self
+            .allowed
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|allowed| same_name(allowed, name)));
+        let sunset = self.on_sunset.is_none() && self.is_past_sunset(name);
+        allowed && !sunset && !self.denied.iter().any(|denied| same_name(denied, name))
+    }
+
+    /// Whether every version is attempted.
+    pub fn permits_all(&self) -> bool {
+        self.allowed.is_none() && self.denied.is_empty() && self.sunsets.is_empty()
+    }
+
+    /// Reports the version data was read as to the `warn_after_sunset` callback,
+    /// if it is past its sunset.
+    fn read(&self, name: &str) {
+        if let Some(warn) = self.on_sunset {
+            if self.is_past_sunset(name) {
+                warn(name);
+            }
+        }
+    }
+}
+
+static POLICY: std::sync::RwLock<Option<&'static VersionPolicy>> = std::sync::RwLock::new(None);
+
+/// Installs the policy for every versioned type, replacing any installed before.
+/// It governs every way of reading versions, by attempting them or by a tag or
+/// index naming one, except `deserialize_with_policy`, which uses its own
+/// policy instead. Types implementing `DeserializeVersioned` by hand are only
+/// governed where they read through `Versions`.
+pub fn set_policy(policy: &'static VersionPolicy) {
+    *POLICY.write().unwrap_or_else(|err| err.into_inner()) = Some(policy);
+}
+
+/// The policy installed with `set_policy`, or else one permitting every version.
+pub(crate) fn installed_policy() -> &'static VersionPolicy {
+    static PERMIT_ALL: VersionPolicy = VersionPolicy::new();
+    POLICY
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .unwrap_or(&PERMIT_ALL)
+}
+
+/// Whether the marker names are equal, ignoring whitespace, e.g. `Sem<1,2,0>`
+/// and `Sem<1, 2, 0>`.
+pub(crate) fn same_name(a: &str, b: &str) -> bool {
+    a.chars()
+        .filter(|c| !c.is_whitespace())
+        .eq(b.chars().filter(|c| !c.is_whitespace()))
+}
+
+/// Why every version rejected the data, in the order they were attempted.
+#[derive(Debug, Default)]
+pub struct VersionErrors {
+    errors: Vec<(String, String)>,
+    denied: Vec<String>,
+}
+
+impl VersionErrors {
+    /// The error of deserializing from any version at once.
+    fn any_version(error: impl std::fmt::Display) -> Self {
+        VersionErrors {
+            errors: vec![(String::from("any version"), error.to_string())],
+            denied: Vec::new(),
+        }
+    }
+
+    /// The reason the version with the marker name, e.g. `Num<1>`, rejected the
+    /// data, if it was attempted.
+    pub fn get(&self, version: &str) -> Option<&str> {
+        self.iter()
+            .find(|(name, _)| *name == version)
+            .map(|(_, error)| error)
+    }
+
+    /// The number of versions that rejected the data
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The name of each rejected version with the reason it was rejected
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.errors
+            .iter()
+            .map(|(version, error)| (version.as_str(), error.as_str()))
+    }
+
+    /// The names of the versions the `VersionPolicy` denied, which weren't attempted
+    pub fn denied(&self) -> impl Iterator<Item = &str> {
+        self.denied.iter().map(String::as_str)
+    }
+}
+
+impl std::fmt::Display for VersionErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("data did not match any version type")?;
+        for (i, (version, error)) in self.iter().enumerate() {
+            let sep = if i == 0 { " (" } else { "; " };
+            write!(f, "{sep}{version}: {error}")?;
+        }
+        if !self.errors.is_empty() {
+            f.write_str(")")?;
+        }
+        if !self.denied.is_empty() {
+            write!(f, ", and the policy denies {}", self.denied.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VersionErrors {}
+
+/// The type name of a marker without module paths, e.g. `Num<1>`.
+fn marker_name<V: ?Sized>() -> String {
+    let delimiters = |c: char| "<>[](),;&* ".contains(c);
+    std::any::type_name::<V>()
+        .split_inclusive(delimiters)
+        .map(|part| {
+            let (path, delimiter) = match part.char_indices().last() {
+                Some((i, c)) if delimiters(c) => part.split_at(i),
+                _ => (part, ""),
+            };
+            let name = path.rsplit("::").next().unwrap_or(path);
+            format!("{name}{delimiter}")
+        })
+        .collect()
+}
+
+/// The names of a version, by its marker and by its tag, see `VersionId`.
+#[derive(Clone, Copy)]
+pub(crate) struct VersionNames {
+    pub(crate) marker: fn() -> String,
+    pub(crate) tag: fn() -> std::borrow::Cow<'static, str>,
+}
+
+impl VersionNames {
+    pub(crate) fn of<V: VersionInfo>() -> Self {
+        VersionNames {
+            marker: marker_name::<V>,
+            tag: V::name,
+        }
+    }
+
+    pub(crate) fn id(self, index: usize) -> VersionId {
+        VersionId {
+            index,
+            name: (self.marker)(),
+            tag: (self.tag)().into_owned(),
+        }
+    }
+}
+
+/// Different supported versions. Supports at most 32 simultaneous versions.
+/// Use `LabeledVersions` if you need to support multiple version handlers for the same type.
+pub type Versions<
+    V0 = (),
+    V1 = (),
+    V2 = (),
+    V3 = (),
+    V4 = (),
+    V5 = (),
+    V6 = (),
+    V7 = (),
+    V8 = (),
+    V9 = (),
+    V10 = (),
+    V11 = (),
+    V12 = (),
+    V13 = (),
+    V14 = (),
+    V15 = (),
+    V16 = (),
+    V17 = (),
+    V18 = (),
+    V19 = (),
+    V20 = (),
+    V21 = (),
+    V22 = (),
+    V23 = (),
+    V24 = (),
+    V25 = (),
+    V26 = (),
+    V27 = (),
+    V28 = (),
+    V29 = (),
+    V30 = (),
+    V31 = (),
+> = LabeledVersions<
+    (),
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+    V12,
+    V13,
+    V14,
+    V15,
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+    V21,
+    V22,
+    V23,
+    V24,
+    V25,
+    V26,
+    V27,
+    V28,
+    V29,
+    V30,
+    V31,
+>;
+
+/// Like `Versions`, but the old versions are converted with their
+/// `FromVersion<V, Label>` impls, so a type can have different sets of
+/// conversions, e.g. for different fields. The current version is unlabeled.
+#[allow(clippy::type_complexity)]
+pub struct LabeledVersions<
+    Label,
+    V0 = (),
+    V1 = (),
+    V2 = (),
+    V3 = (),
+    V4 = (),
+    V5 = (),
+    V6 = (),
+    V7 = (),
+    V8 = (),
+    V9 = (),
+    V10 = (),
+    V11 = (),
+    V12 = (),
+    V13 = (),
+    V14 = (),
+    V15 = (),
+    V16 = (),
+    V17 = (),
+    V18 = (),
+    V19 = (),
+    V20 = (),
+    V21 = (),
+    V22 = (),
+    V23 = (),
+    V24 = (),
+    V25 = (),
+    V26 = (),
+    V27 = (),
+    V28 = (),
+    V29 = (),
+    V30 = (),
+    V31 = (),
+>(
+    std::marker::PhantomData<(
+        Label,
+        V0,
+        V1,
+        V2,
+        V3,
+        V4,
+        V5,
+        V6,
+        V7,
+        V8,
+        V9,
+        V10,
+        V11,
+        V12,
+        V13,
+        V14,
+        V15,
+        V16,
+        V17,
+        V18,
+        V19,
+        V20,
+        V21,
+        V22,
+        V23,
+        V24,
+        V25,
+        V26,
+        V27,
+        V28,
+        V29,
+        V30,
+        V31,
+    )>,
+);
+
+/// Attempts the versions in `order` until one of them matches, skipping those
+/// the policy denies and reporting the attempts to `events`.
+#[allow(clippy::type_complexity)]
+fn probe_attempts<R, A, E: std::fmt::Display>(
+    attempts: &[(VersionNames, A)],
+    order: ProbeOrder<'_>,
+    policy: &VersionPolicy,
+    events: observe::Events<'_>,
+    mut attempt: impl FnMut(&A) -> Result<R, E>,
+) -> Result<(R, VersionId), VersionErrors> {
+    let mut errors = VersionErrors::default();
+    for index in order.indices(attempts.len()) {
+        let (names, ref f) = attempts[index];
+        let name = (names.marker)();
+        if !policy.permits(&name) {
+            errors.denied.push(name);
+            continue;
+        }
+        events.attempt(index, names);
+        match attempt(f) {
+            Ok(res) => {
+                policy.read(&name);
+                events.matched(index, names);
+                let tag = (names.tag)().into_owned();
+                return Ok((res, VersionId { index, name, tag }));
+            }
+            Err(err) => {
+                let err = err.to_string();
+                events.rejected(index, names, &err);
+                errors.errors.push((name, err));
+            }
+        }
+    }
+    events.exhausted(&errors);
+    Err(errors)
+}
+
+/// Implements `Versions` for every prefix of the slots. Each prefix is emitted
+/// next to the recursion, so the expansion depth stays linear in the slot count.
+macro_rules! peel {
+    (stack = { $($stack: tt,)* }; rest = { }) => {};
+    (stack = { $($stack: tt,)* }; rest = { $next: tt, $($rest: tt,)* }) => {
+        impl_versions!(impl { $($stack,)* $next, } for Versions);
+        peel!(stack = { $($stack,)* $next, }; rest = { $($rest,)* });
+    };
+}
+
+macro_rules! impl_versions {
+    (impl { } for Versions) => {};
+    (impl { $first: tt, } for Versions) => {};
+    (impl { $first: tt, $($versions: tt,)* } for Versions) => {
+        impl<L, $($versions: VersionInfo,)*> VersionList for LabeledVersions<L, Ver<Current>, $(Ver<$versions>,)*> {
+            fn versions() -> Vec<(String, u128)> {
+                vec![
+                    (marker_name::<Current>(), Current::ordinal()),
+                    $((marker_name::<$versions>(), $versions::ordinal()),)*
+                ]
+            }
+        }
+
+        impl<R, L, $($versions: VersionInfo,)*> VersionSet<R> for LabeledVersions<L, Ver<Current>, $(Ver<$versions>,)*>
+        where
+            R: FromVersion<Ver<Current>> $(+ FromVersion<Ver<$versions>, L>)*,
+        {
+            fn deserialize_versions<'de, Ds: serde::Deserializer<'de>>(d: Ds) -> Result<R, Ds::Error> {
+                Self::deserialize(d)
+            }
+
+            fn from_slice_versions<F: Format>(bytes: &[u8]) -> Result<R, VersionErrors> {
+                Self::from_slice::<F, R>(bytes)
+            }
+        }
+
+        impl<R, $($versions: VersionInfo,)*> VersionSet<R> for (Ver<Current>, $(Ver<$versions>,)*)
+        where
+            R: FromVersion<Ver<Current>> $(+ FromVersion<Ver<$versions>>)*,
+        {
+            fn deserialize_versions<'de, Ds: serde::Deserializer<'de>>(d: Ds) -> Result<R, Ds::Error> {
+                Versions::<Ver<Current>, $(Ver<$versions>,)*>::deserialize(d)
+            }
+
+            fn from_slice_versions<F: Format>(bytes: &[u8]) -> Result<R, VersionErrors> {
+                Versions::<Ver<Current>, $(Ver<$versions>,)*>::from_slice::<F, R>(bytes)
+            }
+        }
+
+        impl<L, $($versions: VersionInfo,)*> LabeledVersions<L, Ver<Current>, $(Ver<$versions>,)*> {
+            pub fn deserialize<'de, R, Ds: serde::Deserializer<'de>>(d: Ds) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                Self::deserialize_with_version(d).map(|(res, _)| res)
+            }
+
+            /// Like `deserialize`, but also returns which version the data matched.
+            pub fn deserialize_with_version<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<(R, VersionId), Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                Self::probe(d, ProbeOrder::NewestFirst, installed_policy(), None)?
+                    .map_err(serde::de::Error::custom)
+            }
+
+            /// Like `deserialize`, but also checks the value with its `Validate` impl,
+            /// whichever version it was converted from.
+            pub fn deserialize_validated<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<R, Ds::Error>
+            where
+                R: Validate + FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let (res, version) = Self::deserialize_with_version::<R, Ds>(d)?;
+                match res.validate() {
+                    Ok(()) => Ok(res),
+                    Err(err) => Err(serde::de::Error::custom(format_args!(
+                        "invalid data of version {version}: {err}"
+                    ))),
+                }
+            }
+
+            /// Like `deserialize`, but returns why every version rejected the data.
+            /// Errors reading the input itself are returned in the outer `Result`.
+            pub fn deserialize_detailed<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<Result<R, VersionErrors>, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                Ok(Self::probe(d, ProbeOrder::NewestFirst, installed_policy(), None)?.map(|(res, _)| res))
+            }
+
+            /// Like `deserialize_detailed`, but also checks the value with its `Validate` impl.
+            pub fn deserialize_detailed_validated<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<Result<R, VersionErrors>, Ds::Error>
+            where
+                R: Validate + FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let res = Self::probe::<R, Ds>(d, ProbeOrder::NewestFirst, installed_policy(), None)?;
+                Ok(res.and_then(|(res, version)| match res.validate() {
+                    Ok(()) => Ok(res),
+                    Err(err) => Err(VersionErrors {
+                        errors: vec![(version.name, format!("invalid data: {err}"))],
+                        denied: Vec::new(),
+                    }),
+                }))
+            }
+
+            /// Deserializes a sequence whose elements may each be of a different version,
+            /// e.g. an event log accumulated over years, reading each element as the
+            /// first version it matches. Elements are read one at a time, without
+            /// buffering the whole sequence. Fails with the index of the first element
+            /// no version matches.
+            pub fn deserialize_elements<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<Vec<R>, Ds::Error>
+            where
+                Self: VersionSet<R>,
+            {
+                d.deserialize_seq(Elements::<R, Self>(std::marker::PhantomData))
+            }
+
+            /// Deserializes a map whose keys may each be of a different version, e.g.
+            /// keys that used to be numeric ids, reading each key as the first version
+            /// it matches. Values are read as they are, use `Versioned` values to
+            /// migrate them too. Keys migrated to the same key keep the last value.
+            pub fn deserialize_keys<'de, K, V, M, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<M, Ds::Error>
+            where
+                Self: VersionSet<K>,
+                V: serde::Deserialize<'de>,
+                M: Default + Extend<(K, V)>,
+            {
+                d.deserialize_map(Keys::<K, V, M, Self>(std::marker::PhantomData))
+            }
+
+            /// Like `deserialize`, but attempts the versions in the given order.
+            pub fn deserialize_in_order<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                order: ProbeOrder<'_>,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                match Self::probe(d, order, installed_policy(), None)? {
+                    Ok((res, _)) => Ok(res),
+                    Err(errors) => Err(serde::de::Error::custom(errors)),
+                }
+            }
+
+            /// Like `deserialize`, but only attempts the versions the policy permits,
+            /// instead of those the one installed with `set_policy` permits.
+            /// Denied versions aren't attempted, and are listed in `VersionErrors::denied`.
+            pub fn deserialize_with_policy<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                policy: &VersionPolicy,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                match Self::probe(d, ProbeOrder::NewestFirst, policy, None)? {
+                    Ok((res, _)) => Ok(res),
+                    Err(errors) => Err(serde::de::Error::custom(errors)),
+                }
+            }
+
+            /// Like `deserialize`, but reports the attempts to the observer instead of
+            /// the one installed with `set_observer`.
+            pub fn deserialize_observed<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                observer: &dyn MigrationObserver,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                match Self::probe(d, ProbeOrder::NewestFirst, installed_policy(), Some(observer))? {
+                    Ok((res, _)) => Ok(res),
+                    Err(errors) => Err(serde::de::Error::custom(errors)),
+                }
+            }
+
+            /// Deserializes the data as the first version accepting `tag`, straight
+            /// from the deserializer, without attempting the others.
+            /// See `VersionInfo::accepts`.
+            #[allow(clippy::type_complexity)]
+            pub fn deserialize_tagged<'de, R, Ds: serde::Deserializer<'de>>(
+                tag: &str,
+                d: Ds,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let versions: &[(fn(&str) -> bool, VersionNames, fn(Ds) -> Result<R, Ds::Error>)] = &[
+                    (Current::accepts, VersionNames::of::<Current>(), deserialize_unbuffered::<R, Ver<Current>, (), Ds>),
+                    $(($versions::accepts, VersionNames::of::<$versions>(), deserialize_unbuffered::<R, Ver<$versions>, L, Ds>),)*
+                ];
+                match versions.iter().position(|(accepts, _, _)| accepts(tag)) {
+                    Some(index) => {
+                        let (_, names, deserialize) = versions[index];
+                        deserialize_selected(index, names, deserialize, d)
+                    }
+                    None => Err(serde::de::Error::custom(format_args!("unknown version `{tag}`"))),
+                }
+            }
+
+            /// Deserializes the data as the version at `position` in the list, the
+            /// current version being at 0, straight from the deserializer, without
+            /// attempting the others. Used for formats that aren't self-describing.
+            /// See `DeserializeVersioned::deserialize_version_index`.
+            #[allow(clippy::type_complexity)]
+            pub fn deserialize_at<'de, R, Ds: serde::Deserializer<'de>>(
+                position: usize,
+                d: Ds,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let versions: &[(VersionNames, fn(Ds) -> Result<R, Ds::Error>)] = &[
+                    (VersionNames::of::<Current>(), deserialize_unbuffered::<R, Ver<Current>, (), Ds>),
+                    $((VersionNames::of::<$versions>(), deserialize_unbuffered::<R, Ver<$versions>, L, Ds>),)*
+                ];
+                match versions.get(position) {
+                    Some(&(names, deserialize)) => deserialize_selected(position, names, deserialize, d),
+                    None => Err(serde::de::Error::custom(format_args!(
+                        "no version at position {position}"
+                    ))),
+                }
+            }
+
+            /// Like `deserialize`, but attempts each version with a clone of the
+            /// deserializer instead of buffering the data, for deserializers that
+            /// are cheap to clone, such as one over a CSV record.
+            #[allow(clippy::type_complexity)]
+            pub fn deserialize_cloned<'de, R, Ds>(d: Ds) -> Result<R, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+                Ds: serde::Deserializer<'de> + Clone,
+            {
+                let attempts: &[(VersionNames, fn(Ds) -> Result<R, Ds::Error>)] = &[
+                    (VersionNames::of::<Current>(), deserialize_unbuffered::<R, Ver<Current>, (), Ds>),
+                    $((VersionNames::of::<$versions>(), deserialize_unbuffered::<R, Ver<$versions>, L, Ds>),)*
+                ];
+                let events = observe::Events::new::<R>(None);
+                probe_attempts(attempts, ProbeOrder::NewestFirst, installed_policy(), events, |attempt| {
+                    attempt(d.clone())
+                })
+                .map(|(res, _)| res)
+                .map_err(serde::de::Error::custom)
+            }
+
+            /// Deserializes bytes encoded with `F`, decoding them again for each version
+            /// attempted instead of buffering the data, for formats that aren't
+            /// self-describing. `F` should reject trailing bytes, otherwise a version
+            /// could match a prefix of a longer one.
+            pub fn from_slice<'de, F: Format, R>(bytes: &'de [u8]) -> Result<R, VersionErrors>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                Self::decode_each::<F, R>(bytes).map(|(res, _)| res)
+            }
+
+            /// Decodes the bytes as each version in turn, returning the first match
+            /// with the name of its version.
+            #[allow(clippy::type_complexity)]
+            fn decode_each<'de, F: Format, R>(bytes: &'de [u8]) -> Result<(R, String), VersionErrors>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let attempts: &[(VersionNames, fn(&'de [u8]) -> Result<R, F::Error>)] = &[
+                    (VersionNames::of::<Current>(), from_slice_unbuffered::<F, R, Ver<Current>, ()>),
+                    $((VersionNames::of::<$versions>(), from_slice_unbuffered::<F, R, Ver<$versions>, L>),)*
+                ];
+                let events = observe::Events::new::<R>(None);
+                probe_attempts(attempts, ProbeOrder::NewestFirst, installed_policy(), events, |attempt| {
+                    attempt(bytes)
+                })
+                .map(|(res, version)| (res, version.name))
+            }
+
+            /// Like `from_slice`, but also checks the value with its `Validate` impl.
+            pub fn from_slice_validated<'de, F: Format, R>(bytes: &'de [u8]) -> Result<R, VersionErrors>
+            where
+                R: Validate + FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                let (res, version) = Self::decode_each::<F, R>(bytes)?;
+                match res.validate() {
+                    Ok(()) => Ok(res),
+                    Err(err) => Err(VersionErrors {
+                        errors: vec![(version, format!("invalid data: {err}"))],
+                        denied: Vec::new(),
+                    }),
+                }
+            }
+
+            #[allow(clippy::type_complexity)]
+            fn probe<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                order: ProbeOrder<'_>,
+                policy: &VersionPolicy,
+                observer: Option<&dyn MigrationObserver>,
+            ) -> Result<Result<(R, VersionId), VersionErrors>, Ds::Error>
+            where
+                R: FromVersionBorrowed<'de, Ver<Current>> $(+ FromVersionBorrowed<'de, Ver<$versions>, L>)*,
+            {
+                use serde::Deserialize;
+                use crate::content::Content;
+                let content = Content::deserialize(d)?;
+                let attempts: &[(VersionNames, fn(&Content<'de>) -> Result<R, Ds::Error>)] = &[
+                    (
+                        VersionNames::of::<Current>(),
+                        <R as FromVersionBorrowed<'de, Ver<Current>>>::deserialize_versioned_borrowed::<Ds>,
+                    ),
+                    $(
+                        (
+                            VersionNames::of::<$versions>(),
+                            <R as FromVersionBorrowed<'de, Ver<$versions>, L>>::deserialize_versioned_borrowed::<Ds>,
+                        ),
+                    )*
+                ];
+                let events = observe::Events::new::<R>(observer);
+                Ok(probe_attempts(attempts, order, policy, events, |attempt| attempt(&content)))
+            }
+
+            /// Like `deserialize`, but passes `ctx` to the converters.
+            pub fn deserialize_with_context<'de, R, Ctx: ?Sized, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                ctx: &Ctx,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionWith<Ver<Current>, Ctx> $(+ FromVersionWith<Ver<$versions>, Ctx, L>)*,
+            {
+                use serde::Deserialize;
+                use crate::content::Content;
+                let content = Content::deserialize(d)?;
+                let attempts: &[(VersionNames, fn(&Content<'de>, &Ctx) -> Result<R, Ds::Error>)] = &[
+                    (
+                        VersionNames::of::<Current>(),
+                        <R as FromVersionWith<Ver<Current>, Ctx>>::deserialize_versioned_with::<Ds>,
+                    ),
+                    $(
+                        (
+                            VersionNames::of::<$versions>(),
+                            <R as FromVersionWith<Ver<$versions>, Ctx, L>>::deserialize_versioned_with::<Ds>,
+                        ),
+                    )*
+                ];
+                let events = observe::Events::new::<R>(None);
+                probe_attempts(attempts, ProbeOrder::NewestFirst, installed_policy(), events, |attempt| {
+                    attempt(&content, ctx)
+                })
+                .map(|(res, _)| res)
+                .map_err(serde::de::Error::custom)
+            }
+
+            /// Like `deserialize`, but also returns the warnings the matching
+            /// version's converter reported. Warnings of rejected versions are dropped.
+            pub fn deserialize_with_warnings<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> Result<(R, Warnings), Ds::Error>
+            where
+                R: FromVersionWarn<Ver<Current>> $(+ FromVersionWarn<Ver<$versions>, L>)*,
+            {
+                use serde::Deserialize;
+                use crate::content::Content;
+                let content = Content::deserialize(d)?;
+                let attempts: &[(VersionNames, fn(&Content<'de>, &mut Warnings) -> Result<R, Ds::Error>)] = &[
+                    (
+                        VersionNames::of::<Current>(),
+                        <R as FromVersionWarn<Ver<Current>>>::deserialize_versioned_warn::<Ds>,
+                    ),
+                    $(
+                        (
+                            VersionNames::of::<$versions>(),
+                            <R as FromVersionWarn<Ver<$versions>, L>>::deserialize_versioned_warn::<Ds>,
+                        ),
+                    )*
+                ];
+                let events = observe::Events::new::<R>(None);
+                probe_attempts(attempts, ProbeOrder::NewestFirst, installed_policy(), events, |attempt| {
+                    let mut warnings = Warnings::default();
+                    attempt(&content, &mut warnings).map(|res| (res, warnings))
+                })
+                .map(|(res, _)| res)
+                .map_err(serde::de::Error::custom)
+            }
+
+            /// Like `deserialize`, but every version is deserialized through the seed
+            /// its `FromVersionSeed` impl creates from `state`.
+            pub fn deserialize_seed<'de, R, State: ?Sized, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+                state: &mut State,
+            ) -> Result<R, Ds::Error>
+            where
+                R: FromVersionSeed<Ver<Current>, State> $(+ FromVersionSeed<Ver<$versions>, State, L>)*,
+            {
+                use serde::Deserialize;
+                use crate::content::Content;
+                let content = Content::deserialize(d)?;
+                let attempts: &[(VersionNames, fn(&Content<'de>, &mut State) -> Result<R, Ds::Error>)] = &[
+                    (
+                        VersionNames::of::<Current>(),
+                        <R as FromVersionSeed<Ver<Current>, State>>::deserialize_versioned_seed::<Ds>,
+                    ),
+                    $(
+                        (
+                            VersionNames::of::<$versions>(),
+                            <R as FromVersionSeed<Ver<$versions>, State, L>>::deserialize_versioned_seed::<Ds>,
+                        ),
+                    )*
+                ];
+                let events = observe::Events::new::<R>(None);
+                probe_attempts(attempts, ProbeOrder::NewestFirst, installed_policy(), events, |attempt| {
+                    attempt(&content, &mut *state)
+                })
+                .map(|(res, _)| res)
+                .map_err(serde::de::Error::custom)
+            }
+
+            /// Like `deserialize`, but with asynchronous converters.
+            /// The data is buffered up front, so the deserializer is not held across awaits.
+            pub fn deserialize_async<'de, R, Ds: serde::Deserializer<'de>>(
+                d: Ds,
+            ) -> impl std::future::Future<Output = Result<R, Ds::Error>> + Send + use<'de, R, Ds, L, $($versions,)*>
+            where
+                R: Send + FromVersionAsync<Ver<Current>> $(+ FromVersionAsync<Ver<$versions>, L>)*,
+                Ds::Error: Send,
+            {
+                use serde::Deserialize;
+                use crate::content::Content;
+                // Kept free of `Ds` so the future's `Send` bound doesn't involve
+                // the deserializer's lifetime.
+                async fn probe<'de, R, E, L, $($versions: VersionInfo,)*>(
+                    content: Result<Content<'de>, E>,
+                ) -> Result<R, E>
+                where
+                    R: Send + FromVersionAsync<Ver<Current>> $(+ FromVersionAsync<Ver<$versions>, L>)*,
+                    E: serde::de::Error + Send,
+                {
+                    let content = content?;
+                    // Each version's future has its own type, so they can't be attempted
+                    // through `probe_attempts`. They are attempted newest first, and
+                    // denied by the installed policy, as there.
+                    let policy = installed_policy();
+                    let events = observe::Events::new::<R>(None);
+                    let mut errors = VersionErrors::default();
+                    let mut index = 0;
+                    let name = marker_name::<Current>();
+                    if policy.permits(&name) {
+                        let names = VersionNames::of::<Current>();
+                        events.attempt(index, names);
+                        let res = match deserialize_versioned_async::<R, Ver<Current>, (), E>(&content) {
+                            Ok(future) => future.await,
+                            Err(err) => Err(err),
+                        };
+                        match res {
+                            Ok(res) => {
+                                policy.read(&name);
+                                events.matched(index, names);
+                                return Ok(res);
+                            }
+                            Err(err) => {
+                                let err = err.to_string();
+                                events.rejected(index, names, &err);
+                                errors.errors.push((name, err));
+                            }
+                        }
+                    } else {
+                        errors.denied.push(name);
+                    }
+                    $(
+                        index += 1;
+                        let name = marker_name::<$versions>();
+                        if policy.permits(&name) {
+                            let names = VersionNames::of::<$versions>();
+                            events.attempt(index, names);
+                            let res = match deserialize_versioned_async::<R, Ver<$versions>, L, E>(&content) {
+                                Ok(future) => future.await,
+                                Err(err) => Err(err),
+                            };
+                            match res {
+                                Ok(res) => {
+                                    policy.read(&name);
+                                    events.matched(index, names);
+                                    return Ok(res);
+                                }
+                                Err(err) => {
+                                    let err = err.to_string();
+                                    events.rejected(index, names, &err);
+                                    errors.errors.push((name, err));
+                                }
+                            }
+                        } else {
+                            errors.denied.push(name);
+                        }
+                    )*
+                    events.exhausted(&errors);
+                    Err(serde::de::Error::custom(errors))
+                }
+
+                probe::<R, Ds::Error, L, $($versions,)*>(Content::deserialize(d))
+            }
+        }
+    }
+}
+
+peel!(stack = {}; rest = {
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+    V12,
+    V13,
+    V14,
+    V15,
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+    V21,
+    V22,
+    V23,
+    V24,
+    V25,
+    V26,
+    V27,
+    V28,
+    V29,
+    V30,
+    V31,
+});
+
+/// A type that can be deserialized from its current or any of its older versions.
+/// Usually implemented with `#[derive(Versioned)]`.
+pub trait DeserializeVersioned: Sized {
+    /// The `Versions` that are attempted when deserializing.
+    type Versions;
+
+    /// Deserializes the data from any of the supported versions.
+    fn deserialize_any_version<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error>;
+
+    /// Deserializes the data from the old version with the given version tag.
+    /// Falls back to attempting every version, unless the type declares its
+    /// current version with `#[versioned(current = Marker)]`.
+    fn deserialize_version<'de, D: serde::Deserializer<'de>>(
+        tag: &str,
+        d: D,
+    ) -> Result<Self, D::Error> {
+        let _ = tag;
+        Self::deserialize_any_version(d)
+    }
+
+    /// Like `deserialize_version`, but selects the version by its index and
+    /// deserializes it without buffering, for formats that aren't self-describing.
+    /// Versions are indexed from the oldest at 0 to the current version at
+    /// `CurrentVersion::INDEX`, so adding a version keeps the indices of the
+    /// others, while removing the oldest shifts them.
+    fn deserialize_version_index<'de, D: serde::Deserializer<'de>>(
+        index: u8,
+        d: D,
+    ) -> Result<Self, D::Error> {
+        let _ = index;
+        Self::deserialize_any_version(d)
+    }
+
+    /// Deserializes the data from any of the supported versions, attempting
+    /// each with a clone of the deserializer instead of buffering the data.
+    /// Falls back to `deserialize_any_version`, unless implemented with
+    /// `#[derive(Versioned)]`.
+    fn deserialize_cloned<'de, D>(d: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de> + Clone,
+    {
+        Self::deserialize_any_version(d)
+    }
+
+    /// Deserializes the data from the supported versions the policy permits.
+    /// Fails unless the policy permits every version, or the type is
+    /// implemented with `#[derive(Versioned)]`.
+    fn deserialize_with_policy<'de, D: serde::Deserializer<'de>>(
+        d: D,
+        policy: &VersionPolicy,
+    ) -> Result<Self, D::Error> {
+        match policy.permits_all() {
+            true => Self::deserialize_any_version(d),
+            false => Err(serde::de::Error::custom(
+                "the type doesn't support version policies",
+            )),
+        }
+    }
+
+    /// Deserializes bytes encoded with `F` from any of the supported versions,
+    /// decoding them again for each version instead of buffering, for formats
+    /// that aren't self-describing, such as bincode.
+    /// Falls back to decoding them once with `deserialize_any_version`, unless
+    /// implemented with `#[derive(Versioned)]`.
+    fn from_slice_any_version<F: Format>(bytes: &[u8]) -> Result<Self, VersionErrors> {
+        F::from_slice_seed(bytes, envelope::AnyVersionSeed(std::marker::PhantomData))
+            .map_err(VersionErrors::any_version)
+    }
+
+    /// Deserializes the data from any of the supported versions, reporting the
+    /// attempts to the observer instead of the one installed with `set_observer`.
+    /// Falls back to `deserialize_any_version`, unless implemented with
+    /// `#[derive(Versioned)]`.
+    fn deserialize_observed<'de, D: serde::Deserializer<'de>>(
+        d: D,
+        observer: &dyn MigrationObserver,
+    ) -> Result<Self, D::Error> {
+        let _ = observer;
+        Self::deserialize_any_version(d)
+    }
+
+    /// Deserializes the data from any of the supported versions, returning why
+    /// every version rejected it. Errors reading the input itself are returned
+    /// in the outer `Result`.
+    /// Falls back to the error of `deserialize_any_version` as a whole, unless
+    /// implemented with `#[derive(Versioned)]`.
+    fn deserialize_detailed<'de, D: serde::Deserializer<'de>>(
+        d: D,
+    ) -> Result<Result<Self, VersionErrors>, D::Error> {
+        let content = __private::deserialize_content(d)?;
+        let d = content::ContentRefDeserializer::<serde::de::value::Error>::new(&content);
+        Ok(Self::deserialize_any_version(d).map_err(VersionErrors::any_version))
+    }
+
+    /// Deserializes the data as the current version, e.g. once a version tag
+    /// named it. Also checks the value if derived with `#[versioned(validate)]`.
+    fn deserialize_current<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error>
+    where
+        Self: serde::Deserialize<'de>,
+    {
+        Self::deserialize(d)
+    }
+
+    /// Deserializes the data from any of the supported versions, and returns
+    /// which version it matched. Fails unless implemented with `#[derive(Versioned)]`.
+    fn deserialize_with_version<'de, D: serde::Deserializer<'de>>(
+        d: D,
+    ) -> Result<(Self, VersionId), D::Error> {
+        let _ = d;
+        Err(serde::de::Error::custom(
+            "the type doesn't report which version it matched",
+        ))
+    }
+}
+
+/// A type whose current version has a marker, so it can be stamped with
+/// its version tag, e.g. in an `Envelope`.
+/// Usually implemented with `#[versioned(current = Marker)]`.
+pub trait CurrentVersion {
+    /// The marker of the current version, e.g. `Num<3>`
+    type Marker: VersionInfo;
+
+    /// The index of the current version, see `DeserializeVersioned::deserialize_version_index`
+    const INDEX: u8;
+
+    /// How the type is laid out in an `Envelope`.
+    const ENVELOPE: EnvelopeLayout = EnvelopeLayout::Adjacent {
+        tag: "version",
+        content: "data",
+    };
+}
+
+/// A type whose versions are registered in a schema registry, so it can be
+/// written in the Confluent wire format with `Confluent`.
+/// Usually implemented with `#[versioned(schema_ids(Marker = id, ...))]`.
+pub trait SchemaIds: CurrentVersion {
+    /// The schema ids, each paired with the index of the version it was registered for,
+    /// see `DeserializeVersioned::deserialize_version_index`
+    const SCHEMA_IDS: &'static [(u32, u8)];
+}
+
+/// Deserializes any version of a `DeserializeVersioned` type.
+/// Use with `#[serde(deserialize_with = "serde_versioned::deserialize")]`.
+pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+where
+    T: DeserializeVersioned,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize_any_version(d)
+}
+
+/// Serializes the current value as the old version `V`, converted with its
+/// `IntoVersion` impl, e.g. for consumers still reading `V` during a rolling
+/// deployment: `serialize_as::<Ver<Num<2>>, _, _>(&value, s)`.
+pub fn serialize_as<V, T, S>(value: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    T: IntoVersion<V> + ?Sized,
+    S: serde::Serializer,
+{
+    value.serialize_versioned(s)
+}
+
+/// A set of versions `R` can be deserialized from, either `Versions<...>` or
+/// a tuple of the same markers, e.g. `(Ver<Current>, Ver<Num<1>>)`.
+pub trait VersionSet<R> {
+    fn deserialize_versions<'de, D: serde::Deserializer<'de>>(d: D) -> Result<R, D::Error>;
+
+    /// Decodes the bytes as each version in turn, see `Versions::from_slice`.
+    fn from_slice_versions<F: Format>(bytes: &[u8]) -> Result<R, VersionErrors>;
+}
+
+/// The versions `Self` accepts in the format or transport `F`, e.g. every
+/// version from a JSON API but only the recent ones from a bincode cache.
+/// `F` is any marker type, such as a `Format` impl.
+pub trait FormatVersions<F>: Sized {
+    /// `Versions`, a tuple of markers or `Merged`
+    type Versions: VersionSet<Self>;
+}
+
+/// Deserializes any of the versions `T` accepts in the format `F`, e.g.
+/// `deserialize_for::<JsonApi, Order, _>(d)`.
+pub fn deserialize_for<'de, F, T, D>(d: D) -> Result<T, D::Error>
+where
+    T: FormatVersions<F>,
+    D: serde::Deserializer<'de>,
+{
+    T::Versions::deserialize_versions(d)
+}
+
+/// Decodes the bytes as each of the versions `T` accepts in the format `F`.
+pub fn from_slice_for<F: Format, T: FormatVersions<F>>(bytes: &[u8]) -> Result<T, VersionErrors> {
+    T::Versions::from_slice_versions::<F>(bytes)
+}
+
+/// The versions of `A` followed by those of `B`, e.g. to attempt the versions
+/// a plugin crate declares after the core ones: `Merged<CoreVersions, PluginVersions>`.
+/// Both are `Versions`, tuples or `Merged`, so `B` attempts the current version
+/// again after `A`. Use `Merged::<A, B>::deserialize` with `deserialize_with`,
+/// or as the versions of a `Versioned` field.
+pub struct Merged<A, B>(std::marker::PhantomData<fn() -> (A, B)>);
+
+impl<A, B> Merged<A, B> {
+    pub fn deserialize<'de, R, D: serde::Deserializer<'de>>(d: D) -> Result<R, D::Error>
+    where
+        Self: VersionSet<R>,
+    {
+        Self::deserialize_versions(d)
+    }
+}
+
+impl<R, A: VersionSet<R>, B: VersionSet<R>> VersionSet<R> for Merged<A, B> {
+    fn deserialize_versions<'de, D: serde::Deserializer<'de>>(d: D) -> Result<R, D::Error> {
+        use crate::content::{Content, ContentRefDeserializer};
+        use serde::Deserialize;
+
+        let content = Content::deserialize(d)?;
+        let first = match A::deserialize_versions(ContentRefDeserializer::<D::Error>::new(&content))
+        {
+            Ok(res) => return Ok(res),
+            Err(err) => err,
+        };
+        B::deserialize_versions(ContentRefDeserializer::<D::Error>::new(&content))
+            .map_err(|err| serde::de::Error::custom(format_args!("{first}; {err}")))
+    }
+
+    fn from_slice_versions<F: Format>(bytes: &[u8]) -> Result<R, VersionErrors> {
+        let first = match A::from_slice_versions::<F>(bytes) {
+            Ok(res) => return Ok(res),
+            Err(errors) => errors,
+        };
+        B::from_slice_versions::<F>(bytes).map_err(|mut errors| {
+            errors.errors.splice(0..0, first.errors);
+            errors.denied.splice(0..0, first.denied);
+            errors
+        })
+    }
+}
+
+impl<A: VersionList, B: VersionList> VersionList for Merged<A, B> {
+    fn versions() -> Vec<(String, u128)> {
+        let mut versions = A::versions();
+        for version in B::versions() {
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        versions
+    }
+}
+
+/// A value deserialized from any of the versions `Vs`, so a field can be
+/// versioned by its type instead of a `deserialize_with` attribute, e.g.
+/// `Versioned<u32, (Ver<Current>, Ver<Num<1>>)>`, including map keys.
+/// Serializes as the value.
+pub struct Versioned<T, Vs> {
+    value: T,
+    versions: std::marker::PhantomData<fn() -> Vs>,
+}
+
+impl<T, Vs> Versioned<T, Vs> {
+    pub fn new(value: T) -> Self {
+        Versioned {
+            value,
+            versions: std::marker::PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, Vs> From<T> for Versioned<T, Vs> {
+    fn from(value: T) -> Self {
+        Versioned::new(value)
+    }
+}
+
+impl<T, Vs> std::ops::Deref for Versioned<T, Vs> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, Vs> std::ops::DerefMut for Versioned<T, Vs> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: std::fmt::Debug, Vs> std::fmt::Debug for Versioned<T, Vs> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Versioned").field(&self.value).finish()
+    }
+}
+
+impl<T: Clone, Vs> Clone for Versioned<T, Vs> {
+    fn clone(&self) -> Self {
+        Versioned::new(self.value.clone())
+    }
+}
+
+impl<T: Default, Vs> Default for Versioned<T, Vs> {
+    fn default() -> Self {
+        Versioned::new(T::default())
+    }
+}
+
+impl<T: PartialEq, Vs> PartialEq for Versioned<T, Vs> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, Vs> Eq for Versioned<T, Vs> {}
+
+impl<T: PartialOrd, Vs> PartialOrd for Versioned<T, Vs> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord, Vs> Ord for Versioned<T, Vs> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<T: std::hash::Hash, Vs> std::hash::Hash for Versioned<T, Vs> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+impl<T: serde::Serialize, Vs> serde::Serialize for Versioned<T, Vs> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(s)
+    }
+}
+
+impl<'de, T, Vs: VersionSet<T>> serde::Deserialize<'de> for Versioned<T, Vs> {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Vs::deserialize_versions(d).map(Versioned::new)
+    }
+}
+
+/// Reads the elements of a sequence, each from any of the versions `Vs`.
+struct Elements<R, Vs>(std::marker::PhantomData<fn() -> (R, Vs)>);
+
+impl<'de, R, Vs: VersionSet<R>> serde::de::Visitor<'de> for Elements<R, Vs> {
+    type Value = Vec<R>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<R>, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        loop {
+            match seq.next_element::<Versioned<R, Vs>>() {
+                Ok(Some(element)) => elements.push(element.into_inner()),
+                Ok(None) => return Ok(elements),
+                Err(err) => {
+                    return Err(serde::de::Error::custom(format_args!(
+                        "element {}: {err}",
+                        elements.len()
+                    )))
+                }
+            }
+        }
+    }
+}
+
+/// Reads the entries of a map, each key from any of the versions `Vs`.
+#[allow(clippy::type_complexity)]
+struct Keys<K, V, M, Vs>(std::marker::PhantomData<fn() -> (K, V, M, Vs)>);
+
+impl<'de, K, V, M, Vs> serde::de::Visitor<'de> for Keys<K, V, M, Vs>
+where
+    Vs: VersionSet<K>,
+    V: serde::Deserialize<'de>,
+    M: Default + Extend<(K, V)>,
+{
+    type Value = M;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<M, A::Error> {
+        let mut entries = M::default();
+        while let Some((key, value)) = map.next_entry::<Versioned<K, Vs>, V>()? {
+            entries.extend(Some((key.into_inner(), value)));
+        }
+        Ok(entries)
+    }
+}
+
+/// A type with an enum of all its versions, generated with
+/// `#[versioned(any_version = Name)]`.
+pub trait WithAnyVersion: Sized {
+    /// One variant per version, holding the deserialized but unconverted data.
+    type AnyVersion: for<'a> serde::Deserialize<'a>;
+
+    /// The error returned when the data can't be converted.
+    type Error: std::fmt::Display;
+
+    /// Converts any version into the current type.
+    fn from_any_version(v: Self::AnyVersion) -> Result<Self, Self::Error>;
+}
+
+/// The enum of all versions of `T`.
+pub type AnyVersion<T> = <T as WithAnyVersion>::AnyVersion;
+
+/// Declares the versions of a type once, as an alias of `Versions` that can be
+/// referenced from any number of fields with `deserialize_with = "Alias::deserialize"`.
+/// Every listed version is checked to have a `FromVersion` impl for the type,
+/// and if a shape is given, that it is the `VersionType` of that impl.
+/// The versions are attempted in the listed order, so list them newest first.
+/// Unlike with the derive, the order isn't checked, as a declarative macro
+/// can't compare the markers' arguments. With `label = L` after the type, the
+/// alias is a `LabeledVersions<L, ..>` and the `FromVersion<_, L>` impls are
+/// the ones checked and used.
+///
+/// ```
+/// use serde_versioned::{from_version, version_timeline, Num, Ver};
+///
+/// #[derive(Debug, PartialEq, serde::Deserialize)]
+/// struct Amount(u64);
+///
+/// from_version!(Amount, Ver<Num<2>>, String, |s| Ok(Amount(s.parse()?)));
+/// from_version!(Amount, Ver<Num<1>>, f64, |n| Ok(Amount(n as u64)));
+///
+/// version_timeline! {
+///     pub AmountVersions for Amount {
+///         Num<2> = String,
+///         Num<1>,
+///     }
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Invoice {
+///     #[serde(deserialize_with = "AmountVersions::deserialize")]
+///     total: Amount,
+/// }
+///
+/// let invoice: Invoice = serde_json::from_str(r#"{"total": "12"}"#).unwrap();
+/// assert_eq!(invoice.total, Amount(12));
+/// ```
+#[macro_export]
+macro_rules! version_timeline {
+    ($(#[$meta: meta])* $vis: vis $name: ident for $target: ty { $($versions: tt)* }) => {
+        $crate::version_timeline! {
+            $(#[$meta])* $vis $name for $target, label = () { $($versions)* }
+        }
+    };
+    ($(#[$meta: meta])* $vis: vis $name: ident for $target: ty, label = $label: ty { $($marker: ty $(= $shape: ty)?),* $(,)? }) => {
+        $(#[$meta])*
+        $vis type $name = $crate::LabeledVersions<$label, $crate::Ver<$crate::Current>, $($crate::Ver<$marker>,)*>;
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check_versions() {
+                $(
+                    $crate::__private::assert_has_version::<$target, $marker, $label>();
+                    $($crate::__private::assert_from_version::<$target, $marker, $label, $shape>();)?
+                )*
+            }
+        };
+    };
+}
+
+/// Declares a name for `Named` versions or a namespace for `Ns` versions.
+///
+/// ```
+/// use serde_versioned::{from_version, version_name, Current, Named, Ver, Versions};
+///
+/// version_name!(pub Billing2023 = "2023-billing");
+///
+/// #[derive(Debug, PartialEq, serde::Deserialize)]
+/// struct Invoice {
+///     total: u64,
+/// }
+///
+/// from_version!(Invoice, Ver<Named<Billing2023>>, u64, |total| Ok(Invoice { total }));
+///
+/// let mut d = serde_json::Deserializer::from_str("12");
+/// let invoice: Invoice =
+///     Versions::<Ver<Current>, Ver<Named<Billing2023>>>::deserialize(&mut d).unwrap();
+/// assert_eq!(invoice, Invoice { total: 12 });
+/// ```
+#[macro_export]
+macro_rules! version_name {
+    ($(#[$attr: meta])* $vis: vis $ident: ident = $name: literal) => {
+        $(#[$attr])*
+        $vis struct $ident;
+
+        impl $crate::VersionName for $ident {
+            const NAME: &'static str = $name;
+        }
+    };
+}
+
+/// The `Uuid` marker of a UUID literal, parsed at compile time.
+///
+/// ```
+/// use serde_versioned::{uuid_ver, Uuid};
+///
+/// type V2 = uuid_ver!("550e8400-e29b-41d4-a716-446655440000");
+/// let _: V2 = Uuid::<0x550e8400_e29b_41d4_a716_446655440000>;
+/// ```
+#[macro_export]
+macro_rules! uuid_ver {
+    ($uuid: literal) => {
+        $crate::Uuid<{ $crate::__private::parse_uuid($uuid) }>
+    };
+}
+
+/// Implements `FromVersion` with a closure-like converter.
+/// The error type defaults to `Box<dyn Error + Send + Sync>`.
+///
+/// ```
+/// use std::num::ParseIntError;
+///
+/// use serde_versioned::{from_version, FromVersion, Num, Uuid, Ver};
+///
+/// #[derive(Debug, PartialEq, serde::Deserialize)]
+/// struct Amount(u64);
+///
+/// from_version!(Amount, Ver<Num<1>>, String, |s| Ok(Amount(s.parse()?)));
+/// from_version!(Amount, Ver<Uuid<2>>, String, ParseIntError, |s| s.parse().map(Amount));
+///
+/// let amount = <Amount as FromVersion<Ver<Num<1>>>>::convert("3".into());
+/// assert_eq!(amount.unwrap(), Amount(3));
+/// ```
+#[macro_export]
+macro_rules! from_version {
+    ($target: ty, $version: ty, $shape: ty, |$v: pat_param| $convert: expr) => {
+        $crate::from_version!(
+            $target,
+            $version,
+            $shape,
+            ::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Send + ::std::marker::Sync>,
+            |$v| $convert
+        );
+    };
+    ($target: ty, $version: ty, $shape: ty, $error: ty, |$v: pat_param| $convert: expr) => {
+        impl $crate::FromVersion<$version> for $target {
+            type VersionType = $shape;
+            type Error = $error;
+
+            fn convert($v: Self::VersionType) -> ::std::result::Result<Self, Self::Error> {
+                $convert
+            }
+        }
+    };
+}
+
+/// Asserts that each JSON sample deserializes as the `VersionType` of its
+/// version and converts, optionally into an expected value. With `; roundtrip`
+/// the value is also converted back with `IntoVersion` and read again.
+/// Uses `serde_json`, for tests of the versions of a type, so it needs the
+/// `json` feature.
+///
+#[cfg_attr(feature = "json", doc = "```")]
+#[cfg_attr(not(feature = "json"), doc = "```ignore")]
+/// use serde_versioned::{assert_versions, from_version, Num, Ver};
+///
+/// #[derive(Debug, PartialEq, serde::Deserialize)]
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// from_version!(Point, Ver<Num<1>>, (i64, i64), |(x, y)| Ok(Point { x, y }));
+///
+/// assert_versions!(Point {
+///     Ver<Num<1>> => "[1, 2]" == Point { x: 1, y: 2 },
+/// });
+/// ```
+#[macro_export]
+macro_rules! assert_versions {
+    ($target: ty { $($version: ty => $sample: literal $(== $expected: expr)? $(; $roundtrip: ident)?),* $(,)? }) => {
+        $({
+            let v: <$target as $crate::FromVersion<$version>>::VersionType =
+                $crate::__private::serde_json::from_str($sample).unwrap_or_else(|err| {
+                    ::std::panic!(
+                        "sample of {} failed to deserialize: {err}",
+                        ::std::stringify!($version)
+                    )
+                });
+            let value = <$target as $crate::FromVersion<$version>>::convert(v).unwrap_or_else(|err| {
+                ::std::panic!("sample of {} failed to convert: {err}", ::std::stringify!($version))
+            });
+            $(::std::assert_eq!(value, $expected, "sample of {}", ::std::stringify!($version));)?
+            $($crate::assert_versions!(@$roundtrip $target, $version, value);)?
+        })*
+    };
+    (@roundtrip $target: ty, $version: ty, $value: ident) => {
+        let old = <$target as $crate::IntoVersion<$version>>::convert(&$value).unwrap_or_else(|err| {
+            ::std::panic!("value failed to convert into {}: {err}", ::std::stringify!($version))
+        });
+        let json = $crate::__private::serde_json::to_string(&old).unwrap_or_else(|err| {
+            ::std::panic!("{} failed to serialize: {err}", ::std::stringify!($version))
+        });
+        let v: <$target as $crate::FromVersion<$version>>::VersionType =
+            $crate::__private::serde_json::from_str(&json).unwrap_or_else(|err| {
+                ::std::panic!("{} failed to deserialize {json}: {err}", ::std::stringify!($version))
+            });
+        let again = <$target as $crate::FromVersion<$version>>::convert(v).unwrap_or_else(|err| {
+            ::std::panic!("{} failed to convert {json}: {err}", ::std::stringify!($version))
+        });
+        ::std::assert_eq!(again, $value, "round trip through {}", ::std::stringify!($version));
+    };
+}
+
+/// Derives `FromVersion` for the old versions listed in `#[versioned(from(...))]`,
+/// converting with the type's `From`/`TryFrom` impls, and `DeserializeVersioned`.
+#[cfg(feature = "derive")]
+pub use serde_versioned_derive::Versioned;
+
+/// Replaces the stringly `deserialize_with` paths of versioned fields.
+/// Add `#[versioned(from(Marker = Shape, ...))]` to a field and
+/// `#[serde_versioned::versioned]` above the `#[derive(Deserialize)]` of the type.
+#[cfg(feature = "derive")]
+pub use serde_versioned_derive::versioned;
+
+// Used by the derive macros. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::content::{Content, ContentRefDeserializer};
+    pub use serde;
+    #[cfg(feature = "json")]
+    pub use serde_json;
+
+    pub fn deserialize_content<'de, D>(d: D) -> Result<Content<'de>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde::Deserialize::deserialize(d)
+    }
+
+    pub fn from_content<'de, T, E>(content: &Content<'de>) -> Result<T, E>
+    where
+        T: serde::Deserialize<'de>,
+        E: serde::de::Error,
+    {
+        T::deserialize(ContentRefDeserializer::<E>::new(content))
+    }
+
+    /// Parses a hyphenated or plain hex UUID, panicking at compile time if it's invalid.
+    pub const fn parse_uuid(uuid: &str) -> u128 {
+        let bytes = uuid.as_bytes();
+        let hyphenated = bytes.len() == 36;
+        assert!(hyphenated || bytes.len() == 32, "invalid UUID length");
+
+        let mut value = 0u128;
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            i += 1;
+            if hyphenated && matches!(i, 9 | 14 | 19 | 24) {
+                assert!(byte == b'-', "expected a hyphen in the UUID");
+                continue;
+            }
+            let digit = match byte {
+                b'0'..=b'9' => byte - b'0',
+                b'a'..=b'f' => byte - b'a' + 10,
+                b'A'..=b'F' => byte - b'A' + 10,
+                _ => panic!("invalid hex digit in the UUID"),
+            };
+            value = value << 4 | digit as u128;
+        }
+        value
+    }
+
+    pub fn validated<T: crate::Validate, E: serde::de::Error>(value: T) -> Result<T, E> {
+        match value.validate() {
+            Ok(()) => Ok(value),
+            Err(err) => Err(E::custom(format_args!("invalid data: {err}"))),
+        }
+    }
+
+    pub fn no_matching_version<E: serde::de::Error>() -> E {
+        E::custom("data did not match any version type")
+    }
+
+    pub fn assert_marker<V: ?Sized>() {}
+
+    pub fn assert_has_version<T, V, L>()
+    where
+        T: crate::FromVersion<crate::Ver<V>, L>,
+    {
+    }
+
+    pub fn assert_from_version<T, V, L, S>()
+    where
+        T: crate::FromVersion<crate::Ver<V>, L, VersionType = S>,
+    {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(non_local_definitions)]
+    fn test_legacy() {
+        struct OldString;
+        impl VersionInfo for OldString {
+            fn name() -> std::borrow::Cow<'static, str> {
+                "old-string".into()
+            }
+
+            fn ordinal() -> u128 {
+                0
+            }
+        }
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Field3(u64);
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct New {
+            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Uuid<1>>>::deserialize")]
+            value: u32,
+
+            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<OldString>>::deserialize")]
+            value2: u32,
+
+            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Num<1>>>::deserialize")]
+            value3: Field3,
+
+            #[serde(deserialize_with = "Versions::<Ver<Current>, Ver<Sem<0, 0, 1>>>::deserialize")]
+            value4: u32,
+        }
+
+        impl FromVersion<Ver<Sem<0, 0, 1>>> for u32 {
+            type VersionType = String;
+            type Error = std::num::ParseIntError;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                Ok(v.parse::<u32>()? + 300)
+            }
+        }
+
+        impl FromVersion<Ver<Uuid<1>>> for u32 {
+            type VersionType = String;
+            type Error = std::num::ParseIntError;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                v.parse()
+            }
+        }
+
+        impl FromVersion<Ver<OldString>> for u32 {
+            type VersionType = String;
+            type Error = std::num::ParseIntError;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                Ok(v.parse::<Self>()? + 100)
+            }
+        }
+
+        impl FromVersion<Ver<Num<1>>> for Field3 {
+            type VersionType = String;
+            type Error = Box<dyn std::error::Error>;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                Ok(Self(v.parse::<u64>()? + 200))
+            }
+        }
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct LegacyData {
+            value: String,
+            value2: String,
+            value3: String,
+            value4: String,
+        }
+
+        let legacy = serde_json::to_string(&LegacyData {
+            value: String::from("100"),
+            value2: String::from("100"),
+            value3: String::from("100"),
+            value4: String::from("100"),
+        })
+        .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<New>(&legacy).unwrap(),
+            New {
+                value: 100,
+                value2: 200,
+                value3: Field3(300),
+                value4: 400,
+            }
+        );
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Field3(u64);
+
+    impl FromVersion<Ver<Num<1>>> for Field3 {
+        type VersionType = String;
+        type Error = Box<dyn std::error::Error>;
+
+        fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+            Ok(Self(v.parse::<u64>()? + 200))
+        }
+    }
+
+    #[test]
+    fn test_containers() {
+        use std::collections::{BTreeMap, HashMap};
+
+        fn parse<T>(json: &str) -> Result<T, serde_json::Error>
+        where
+            T: FromVersion<Ver<Num<1>>>,
+        {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize::<T, _>(&mut d)
+        }
+
+        assert_eq!(
+            parse::<Vec<Field3>>(r#"["1", "2"]"#).unwrap(),
+            [Field3(201), Field3(202)]
+        );
+        assert_eq!(parse::<Vec<Field3>>("[3]").unwrap(), [Field3(3)]);
+        assert_eq!(
+            parse::<Option<Field3>>(r#""1""#).unwrap(),
+            Some(Field3(201))
+        );
+        assert_eq!(parse::<Option<Field3>>("null").unwrap(), None);
+        assert_eq!(
+            parse::<Box<Field3>>(r#""1""#).unwrap(),
+            Box::new(Field3(201))
+        );
+        assert_eq!(
+            parse::<HashMap<String, Field3>>(r#"{"a": "1"}"#).unwrap(),
+            HashMap::from([(String::from("a"), Field3(201))])
+        );
+        assert_eq!(
+            parse::<BTreeMap<String, Field3>>(r#"{"b": "2"}"#).unwrap(),
+            BTreeMap::from([(String::from("b"), Field3(202))])
+        );
+        assert_eq!(
+            parse::<[Field3; 2]>(r#"["1", "2"]"#).unwrap(),
+            [Field3(201), Field3(202)]
+        );
+        assert!(parse::<Vec<Field3>>(r#"["1", "x"]"#).is_err());
+    }
+
+    #[test]
+    fn test_smart_pointers() {
+        use std::borrow::Cow;
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        fn parse<T: FromVersion<Ver<Num<1>>>>(json: &str) -> T {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize::<T, _>(&mut d).unwrap()
+        }
+
+        assert_eq!(parse::<Arc<Field3>>(r#""1""#), Arc::new(Field3(201)));
+        assert_eq!(parse::<Rc<Field3>>("2"), Rc::new(Field3(2)));
+        let cow = parse::<Cow<'_, Field3>>(r#""3""#);
+        assert!(matches!(cow, Cow::Owned(Field3(203))));
+    }
+
+    from_version!(Field3, Ver<Uuid<3>>, String, |v| Ok(Field3(
+        v.parse::<u64>()? * 2
+    )));
+
+    #[test]
+    fn test_from_version_macro() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Uuid<3>>>::deserialize::<Field3, _>(&mut d).unwrap()
+        };
+
+        assert_eq!(parse("2"), Field3(2));
+        assert_eq!(parse(r#""2""#), Field3(4));
+    }
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct ConfigV1 {
+        timeout: u32,
+    }
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct ConfigV2 {
+        timeout_ms: u64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct Config {
+        timeout_ms: u64,
+        retries: u32,
+    }
+
+    impl Upgrade<ConfigV1> for ConfigV2 {
+        type Error = std::convert::Infallible;
+
+        fn upgrade(prev: ConfigV1) -> Result<Self, Self::Error> {
+            Ok(ConfigV2 {
+                timeout_ms: u64::from(prev.timeout) * 1000,
+            })
+        }
+    }
+
+    impl Upgrade<ConfigV2> for Config {
+        type Error = &'static str;
+
+        fn upgrade(prev: ConfigV2) -> Result<Self, Self::Error> {
+            if prev.timeout_ms == 0 {
+                return Err("timeout must not be zero");
+            }
+            Ok(Config {
+                timeout_ms: prev.timeout_ms,
+                retries: 3,
+            })
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        type ConfigVersions =
+            Versions<Ver<Current>, Ver<Chain<ConfigV2>>, Ver<Chain<ConfigV1, Chain<ConfigV2>>>>;
+
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            ConfigVersions::deserialize::<Config, _>(&mut d)
+        };
+        let expected = Config {
+            timeout_ms: 2000,
+            retries: 3,
+        };
+
+        assert_eq!(
+            parse(r#"{"timeout_ms": 2000, "retries": 3}"#).unwrap(),
+            expected
+        );
+        assert_eq!(parse(r#"{"timeout_ms": 2000}"#).unwrap(), expected);
+        assert_eq!(parse(r#"{"timeout": 2}"#).unwrap(), expected);
+        assert!(parse(r#"{"timeout": 0}"#).is_err());
+    }
+
+    impl Downgrade<ConfigV2> for Config {
+        type Error = std::convert::Infallible;
+
+        fn downgrade(&self) -> Result<ConfigV2, Self::Error> {
+            Ok(ConfigV2 {
+                timeout_ms: self.timeout_ms,
+            })
+        }
+    }
+
+    impl Downgrade<ConfigV1> for ConfigV2 {
+        type Error = std::num::TryFromIntError;
+
+        fn downgrade(&self) -> Result<ConfigV1, Self::Error> {
+            Ok(ConfigV1 {
+                timeout: u32::try_from(self.timeout_ms / 1000)?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_downgrade_chain() {
+        let config = Config {
+            timeout_ms: 2000,
+            retries: 3,
+        };
+        let v2 = IntoVersion::<Ver<Chain<ConfigV2>>>::convert(&config).unwrap();
+        assert_eq!(v2.timeout_ms, 2000);
+
+        let mut v1 = Vec::new();
+        serialize_as::<Ver<Chain<ConfigV1, Chain<ConfigV2>>>, _, _>(
+            &config,
+            &mut serde_json::Serializer::new(&mut v1),
+        )
+        .unwrap();
+        assert_eq!(v1, br#"{"timeout":2}"#);
+
+        let long = Config {
+            timeout_ms: u64::MAX,
+            retries: 3,
+        };
+        assert!(IntoVersion::<Ver<Chain<ConfigV1, Chain<ConfigV2>>>>::convert(&long).is_err());
+    }
+
+    #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct Volume {
+        millilitres: u64,
+    }
+
+    #[derive(serde::Deserialize, serde::Serialize)]
+    struct VolumeV1 {
+        litres: u64,
+    }
+
+    impl VersionLens<VolumeV1> for Volume {
+        type Error = &'static str;
+
+        fn upgrade(prev: VolumeV1) -> Result<Self, Self::Error> {
+            Ok(Volume {
+                millilitres: prev.litres * 1000,
+            })
+        }
+
+        fn downgrade(&self) -> Result<VolumeV1, Self::Error> {
+            match self.millilitres % 1000 {
+                0 => Ok(VolumeV1 {
+                    litres: self.millilitres / 1000,
+                }),
+                _ => Err("not a whole number of litres"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lens() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Chain<VolumeV1>>>::deserialize::<Volume, _>(&mut d)
+                .unwrap()
+        };
+        let volume = Volume { millilitres: 2000 };
+        assert_eq!(parse(r#"{"litres": 2}"#), volume);
+
+        let old = IntoVersion::<Ver<Chain<VolumeV1>>>::convert(&volume).unwrap();
+        assert_eq!(serde_json::to_string(&old).unwrap(), r#"{"litres":2}"#);
+
+        let error = IntoVersion::<Ver<Chain<VolumeV1>>>::convert(&Volume { millilitres: 1500 });
+        assert!(error.is_err_and(|err| err.to_string() == "not a whole number of litres"));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Cents(u64);
+
+    struct Rates {
+        eur_to_cents: u64,
+    }
+
+    impl FromVersionWith<Ver<Num<1>>, Rates> for Cents {
+        type VersionType = String;
+        type Error = std::num::ParseIntError;
+
+        fn convert_with(v: Self::VersionType, ctx: &Rates) -> Result<Self, Self::Error> {
+            Ok(Cents(
+                v.trim_end_matches(" EUR").parse::<u64>()? * ctx.eur_to_cents,
+            ))
+        }
+    }
+
+    from_version!(Cents, Ver<Num<0>>, (u64,), |(v,)| Ok(Cents(v)));
+
+    #[test]
+    fn test_context() {
+        let rates = Rates { eur_to_cents: 110 };
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>, Ver<Num<0>>>::deserialize_with_context::<
+                Cents,
+                _,
+                _,
+            >(&mut d, &rates)
+            .unwrap()
+        };
+
+        assert_eq!(parse("5"), Cents(5));
+        assert_eq!(parse(r#""2 EUR""#), Cents(220));
+        assert_eq!(parse("[7]"), Cents(7));
+    }
+
+    #[test]
+    fn test_version_info() {
+        assert_eq!(Ver::<Num<3>>::name(), "v3");
+        assert_eq!(Sem::<1, 2, 0>::name(), "1.2.0");
+        assert_eq!(Sem::<1, 2, 0, Rc<1>>::name(), "1.2.0-rc.1");
+        assert_eq!(
+            Uuid::<0x67e5504410b1426f9247bb680e5fe0c8>::name(),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+        assert_eq!(Date::<2024, 3, 1>::name(), "2024-03-01");
+        assert_eq!(Current::name(), "current");
+        assert_eq!(Chain::<ConfigV1>::name(), std::any::type_name::<ConfigV1>());
+
+        assert!(Num::<1>::ordinal() < Num::<2>::ordinal());
+        assert!(Sem::<0, 9, 9>::ordinal() < Sem::<1, 0, 0>::ordinal());
+        assert!(Sem::<1, 0, 9>::ordinal() < Sem::<1, 1, 0>::ordinal());
+        assert!(Sem::<1, 1, 0, Rc<2>>::ordinal() < Sem::<1, 1, 0>::ordinal());
+        assert!(Sem::<1, 1, 0, Beta<3>>::ordinal() < Sem::<1, 1, 0, Rc<1>>::ordinal());
+        assert!(Sem::<1, 1, 0, Alpha<1>>::ordinal() < Sem::<1, 1, 0, Alpha<2>>::ordinal());
+        assert!(Sem::<1, 0, 9>::ordinal() < Sem::<1, 1, 0, Alpha<1>>::ordinal());
+        assert!(Date::<2023, 12, 31>::ordinal() < Date::<2024, 1, 1>::ordinal());
+        assert!(Date::<2024, 1, 31>::ordinal() < Date::<2024, 2, 1>::ordinal());
+        assert!(Chain::<ConfigV1, Chain<ConfigV2>>::ordinal() < Chain::<ConfigV2>::ordinal());
+        assert!(Chain::<ConfigV2>::ordinal() < Current::ordinal());
+    }
+
+    #[test]
+    fn test_sem_range() {
+        assert!(Sem::<1, 2, 0>::accepts("1.2.0"));
+        assert!(!Sem::<1, 2, 0>::accepts("1.2.1"));
+        assert!(SemMajor::<1>::accepts("1.7.3"));
+        assert!(SemMajor::<1>::accepts("1.0.0-rc.1"));
+        assert!(!SemMajor::<1>::accepts("2.0.0"));
+        assert!(!SemMajor::<1>::accepts("1.x"));
+        assert!(Ver::<SemMinor<1, 2>>::accepts("1.2.9"));
+        assert!(!SemMinor::<1, 2>::accepts("1.3.0"));
+        assert_eq!(SemMinor::<1, 2>::name(), "1.2.x");
+
+        assert!(Sem::<1, 9, 9>::ordinal() < SemMajor::<1>::ordinal());
+        assert!(SemMajor::<1>::ordinal() < Sem::<2, 0, 0>::ordinal());
+        assert!(Sem::<1, 2, 9>::ordinal() < SemMinor::<1, 2>::ordinal());
+    }
+
+    #[test]
+    fn test_epoch() {
+        type Cutover = Epoch<1700000000>;
+        assert_eq!(Cutover::name(), "2023-11-14T22:13:20Z");
+        assert_eq!(Epoch::<0>::name(), "1970-01-01T00:00:00Z");
+        assert_eq!(Epoch::<951782400>::name(), "2000-02-29T00:00:00Z");
+        assert!(Cutover::accepts("1700000000"));
+        assert!(Cutover::accepts("2023-11-14T22:13:20Z"));
+        assert!(Epoch::<1>::ordinal() < Cutover::ordinal());
+
+        assert!(Cutover::is_before(1699999999));
+        assert!(!Cutover::is_before(1700000000));
+        assert!(Cutover::is_before_time(std::time::UNIX_EPOCH));
+        assert!(!Cutover::is_before_time(std::time::SystemTime::now()));
+    }
+
+    #[test]
+    fn test_uuid_ver() {
+        type V = uuid_ver!("550e8400-e29b-41d4-a716-446655440000");
+        assert_eq!(V::ordinal(), 0x550e8400e29b41d4a716446655440000);
+        assert_eq!(V::name(), "550e8400-e29b-41d4-a716-446655440000");
+        assert_eq!(
+            <uuid_ver!("67E5504410B1426F9247BB680E5FE0C8")>::ordinal(),
+            0x67e5504410b1426f9247bb680e5fe0c8
+        );
+    }
+
+    version_name!(Billing = "billing");
+    version_name!(Shipping = "shipping");
+
+    from_version!(Cents, Ver<Ns<Billing, 1>>, String, |v| Ok(Cents(
+        v.parse()?
+    )));
+    from_version!(
+        Cents,
+        Ver<Ns<Shipping, 1>>,
+        f64,
+        std::convert::Infallible,
+        |v| Ok(Cents((v * 100.0) as u64))
+    );
+
+    #[test]
+    fn test_namespaced_version() {
+        assert_eq!(Ns::<Billing, 3>::name(), "billing/v3");
+        assert!(Ns::<Billing, 1>::ordinal() < Ns::<Billing, 3>::ordinal());
+
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Ns<Billing, 1>>, Ver<Ns<Shipping, 1>>>::deserialize_detailed::<
+                Cents,
+                _,
+            >(&mut d)
+            .unwrap()
+        };
+        assert_eq!(parse(r#""120""#).unwrap().0, 120);
+        assert_eq!(parse("1.5").unwrap().0, 150);
+
+        let errors = parse("null").unwrap_err();
+        let names: Vec<_> = errors.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Current", "Ns<Billing, 1>", "Ns<Shipping, 1>"]);
+    }
+
+    struct Quarantine;
+
+    impl RawHandler<Comment> for Quarantine {
+        type Error = serde_json::Error;
+
+        fn handle(content: Content<'static>) -> Result<Comment, Self::Error> {
+            Ok(Comment {
+                text: format!("[quarantined {}]", serde_json::to_string(&content)?),
+            })
+        }
+    }
+
+    #[test]
+    fn test_raw() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Raw<Quarantine>>>::deserialize::<Comment, _>(&mut d)
+                .unwrap()
+        };
+
+        assert_eq!(parse(r#"{"text": "hi"}"#).text, "hi");
+        assert_eq!(
+            parse(r#"{"body": ["hi", 1, null]}"#).text,
+            r#"[quarantined {"body":["hi",1,null]}]"#
+        );
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Comment {
+        text: String,
+    }
+
+    struct Removed;
+
+    impl FallbackValue<Comment> for Removed {
+        fn fallback() -> Comment {
+            Comment {
+                text: String::from("[removed]"),
+            }
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, serde::Deserialize)]
+    struct Count(u32);
+
+    #[test]
+    fn test_fallback() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Fallback<Removed>>>::deserialize::<Comment, _>(&mut d)
+                .unwrap()
+        };
+        assert_eq!(parse(r#"{"text": "hi"}"#).text, "hi");
+        assert_eq!(parse(r#"{"body": "hi"}"#).text, "[removed]");
+        assert_eq!(parse("3").text, "[removed]");
+
+        let mut d = serde_json::Deserializer::from_str(r#""seven""#);
+        let count = Versions::<Ver<Current>, Ver<Fallback>>::deserialize::<Count, _>(&mut d);
+        assert_eq!(count.unwrap(), Count(0));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Level(usize);
+
+    macro_rules! levels {
+        ($($n: literal),*) => {
+            $(
+                from_version!(Level, Ver<Num<$n>>, [u8; $n], std::convert::Infallible, |v| {
+                    Ok(Level(v.len()))
+                });
+            )*
+
+            /// Every slot of `Versions`: the current version and 31 old ones.
+            type Levels = Versions<Ver<Current>, $(Ver<Num<$n>>,)*>;
+        };
+    }
+
+    levels!(
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
+        26, 27, 28, 29, 30, 31
+    );
+
+    #[test]
+    fn test_many_versions() {
+        fn parse(json: &str) -> (Level, VersionId) {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Levels::deserialize_with_version::<Level, _>(&mut d).unwrap()
+        }
+
+        assert_eq!(parse("0").0, Level(0));
+        assert_eq!(parse("[0]").0, Level(1));
+        assert_eq!(parse(&format!("{:?}", [0; 15])).0, Level(15));
+
+        let (level, version) = parse(&format!("{:?}", [0; 31]));
+        assert_eq!(level, Level(31));
+        assert_eq!((version.index(), version.name()), (31, "Num<31>"));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Scene {
+        name: Option<String>,
+        shapes: Vec<Shape>,
+        tags: std::collections::BTreeMap<String, (u64, char)>,
+    }
+
+    #[test]
+    fn test_content() {
+        let json = r#"{
+            "name": null,
+            "shapes": ["Point", {"Circle": 1.5}, {"Rect": {"w": 2, "h": 3}}],
+            "tags": {"a": [1, "x"]}
+        }"#;
+        let content: __private::Content = serde_json::from_str(json).unwrap();
+        let scene: Scene = __private::from_content::<_, serde_json::Error>(&content).unwrap();
+        assert_eq!(
+            scene,
+            Scene {
+                name: None,
+                shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+                tags: [(String::from("a"), (1, 'x'))].into_iter().collect(),
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Person {
+        first: String,
+        last: String,
+    }
+
+    impl<'de> FromVersionBorrowed<'de, Ver<Num<1>>> for Person {
+        type VersionType = &'de str;
+        type Error = &'static str;
+
+        fn convert_borrowed(name: &'de str) -> Result<Self, Self::Error> {
+            let (first, last) = name.split_once(' ').ok_or("expected a full name")?;
+            Ok(Person {
+                first: first.to_string(),
+                last: last.to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_borrowed() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize::<Person, _>(&mut d)
+        };
+
+        let ada = Person {
+            first: String::from("Ada"),
+            last: String::from("Lovelace"),
+        };
+        assert_eq!(
+            parse(r#"{"first": "Ada", "last": "Lovelace"}"#).unwrap(),
+            ada
+        );
+        assert_eq!(parse(r#""Ada Lovelace""#).unwrap(), ada);
+        assert!(parse(r#""Ada""#).is_err());
+    }
+
+    #[test]
+    fn test_version_policy() {
+        let policy = VersionPolicy::parse(" !Num<1>,, Num<2>, Num<3> ");
+        assert!(!policy.permits("Num<1>"));
+        assert!(policy.permits("Num<2>"));
+        assert!(!policy.permits("Current"));
+        assert!(!policy.permits_all());
+
+        let policy = VersionPolicy::new().deny("Num<1>");
+        assert!(policy.permits("Current"));
+        assert!(!policy.permits("Num<1>"));
+        assert!(VersionPolicy::parse("").permits_all());
+
+        let policy = VersionPolicy::parse("!Sem<1,2,0>, !Date<2024, 3, 1>");
+        assert!(!policy.permits(&marker_name::<Sem<1, 2, 0>>()));
+        assert!(!policy.permits(&marker_name::<Date<2024, 3, 1>>()));
+        assert!(policy.permits(&marker_name::<Sem<1, 2, 1>>()));
+    }
+
+    #[test]
+    fn test_denied_not_attempted() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CONVERTED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Count(u32);
+
+        impl FromVersion<Ver<Num<1>>> for Count {
+            type VersionType = String;
+            type Error = std::num::ParseIntError;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                CONVERTED.fetch_add(1, Ordering::Relaxed);
+                v.parse().map(Count)
+            }
+        }
+
+        let policy = VersionPolicy::parse("!Num<1>");
+        let mut d = serde_json::Deserializer::from_str(r#""1""#);
+        let errors = Versions::<Ver<Current>, Ver<Num<1>>>::probe::<Count, _>(
+            &mut d,
+            ProbeOrder::NewestFirst,
+            &policy,
+            None,
+        )
+        .unwrap()
+        .unwrap_err();
+        assert_eq!(errors.denied().collect::<Vec<_>>(), ["Num<1>"]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(CONVERTED.load(Ordering::Relaxed), 0);
+
+        let mut d = serde_json::Deserializer::from_str(r#""1""#);
+        let count = Versions::<Ver<Current>, Ver<Num<1>>>::deserialize::<Count, _>(&mut d);
+        assert_eq!(count.unwrap().0, 1);
+        assert_eq!(CONVERTED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_sunset() {
+        use std::time::{Duration, SystemTime};
+
+        let sunset = SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000);
+        let policy = VersionPolicy::new().sunset("Num<1>", sunset);
+        assert!(policy
+            .clone()
+            .at(sunset - Duration::from_secs(1))
+            .permits("Num<1>"));
+        assert!(!policy.clone().at(sunset).permits("Num<1>"));
+        assert!(!policy.permits("Num<1>"));
+        assert!(policy.permits("Current"));
+
+        let policy = policy.warn_after_sunset(|_| {});
+        assert!(policy.permits("Num<1>"));
+        assert!(policy.is_past_sunset("Num<1>"));
+    }
+
+    #[test]
+    fn test_deprecation_log() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static LOGGED: AtomicUsize = AtomicUsize::new(0);
+        let log = DeprecationLog::with_logger(|message| {
+            assert_eq!(message, "legacy version Num<1> seen for type Person");
+            LOGGED.fetch_add(1, Ordering::Relaxed);
+        });
+        log.on_match("Person", &VersionNames::of::<Current>().id(0));
+        log.on_match("Person", &VersionNames::of::<Num<1>>().id(1));
+        log.on_match("Person", &VersionNames::of::<Num<1>>().id(1));
+        assert_eq!(LOGGED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_deprecation_log_interval() {
+        use std::sync::Mutex;
+
+        static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let log = DeprecationLog::with_logger(|message| {
+            LOGGED.lock().unwrap().push(message.to_owned());
+        })
+        .every(std::time::Duration::ZERO);
+        let v1 = VersionNames::of::<Num<1>>().id(1);
+
+        log.on_match("Person", &v1);
+        log.on_match("Person", &v1);
+        assert_eq!(
+            *LOGGED.lock().unwrap(),
+            [
+                "legacy version Num<1> seen for type Person",
+                "legacy version Num<1> seen for type Person again, 1x since last logged",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_errors() {
+        type PersonVersions = Versions<Ver<Current>, Ver<Num<1>>>;
+
+        let mut d = serde_json::Deserializer::from_str(r#""Ada""#);
+        let errors = PersonVersions::deserialize_detailed::<Person, _>(&mut d)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            errors.iter().collect::<Vec<_>>(),
+            [
+                (
+                    "Current",
+                    r#"invalid type: string "Ada", expected struct Person"#
+                ),
+                ("Num<1>", "expected a full name"),
+            ]
+        );
+
+        let mut d = serde_json::Deserializer::from_str(r#""Ada""#);
+        let error = PersonVersions::deserialize::<Person, _>(&mut d).unwrap_err();
+        assert_eq!(error.to_string(), errors.to_string());
+
+        let mut d = serde_json::Deserializer::from_str("[");
+        assert!(PersonVersions::deserialize_detailed::<Person, _>(&mut d).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_with_version() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_with_version::<Person, _>(&mut d)
+                .unwrap()
+        };
+
+        let (_, version) = parse(r#"{"first": "Ada", "last": "Lovelace"}"#);
+        assert!(version.is_current());
+        assert_eq!(version.name(), "Current");
+        assert_eq!(version.tag(), "current");
+
+        let (person, version) = parse(r#""Ada Lovelace""#);
+        assert_eq!(person.first, "Ada");
+        assert!(!version.is_current());
+        assert_eq!((version.index(), version.name()), (1, "Num<1>"));
+        assert_eq!((version.marker(), version.tag()), ("Num<1>", "v1"));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Celsius(f64);
+
+    from_version!(Celsius, Ver<Num<1>>, f64, std::convert::Infallible, |v| {
+        Ok(Celsius((v - 32.0) / 1.8))
+    });
+    from_version!(Celsius, Ver<Num<2>>, String, |v| Ok(Celsius(v.parse()?)));
+
+    #[test]
+    fn test_probe_order() {
+        type V = Versions<Ver<Current>, Ver<Num<1>>, Ver<Num<2>>>;
+        let parse = |json, order| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            V::deserialize_in_order::<Celsius, _>(&mut d, order)
+        };
+
+        assert_eq!(
+            parse("212", ProbeOrder::NewestFirst).unwrap(),
+            Celsius(212.0)
+        );
+        assert_eq!(
+            parse("212", ProbeOrder::OldestFirst).unwrap(),
+            Celsius(100.0)
+        );
+        assert_eq!(
+            parse("212", ProbeOrder::Custom(&[1, 0])).unwrap(),
+            Celsius(100.0)
+        );
+        assert_eq!(
+            parse(r#""21.5""#, ProbeOrder::Custom(&[2, 9])).unwrap(),
+            Celsius(21.5)
+        );
+        assert!(parse("212", ProbeOrder::Custom(&[2])).is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Account {
+        id: u64,
+    }
+
+    struct HasLegacyId;
+
+    impl VersionMatcher for HasLegacyId {
+        fn matches(content: &Content<'_>) -> bool {
+            content.get("legacy_id").is_some()
+        }
+    }
+
+    from_version!(
+        Account,
+        Ver<Num<1>>,
+        std::collections::BTreeMap<String, u64>,
+        |v| Ok(Account {
+            id: v.get("legacy_id").copied().ok_or("missing legacy_id")?
+        })
+    );
+    from_version!(Account, Ver<Num<2>>, String, |v| Ok(Account {
+        id: v.parse()?
+    }));
+
+    #[test]
+    fn test_version_matcher() {
+        type V = Versions<
+            Ver<Current>,
+            Ver<Matched<HasLegacyId, Num<1>>>,
+            Ver<Matched<IsString, Num<2>>>,
+        >;
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            V::deserialize_detailed::<Account, _>(&mut d).unwrap()
+        };
+
+        assert_eq!(parse(r#"{"id": 5}"#).unwrap(), Account { id: 5 });
+        assert_eq!(parse(r#"{"legacy_id": 7}"#).unwrap(), Account { id: 7 });
+        assert_eq!(parse(r#""9""#).unwrap(), Account { id: 9 });
+
+        let errors = parse(r#"{"uid": 3}"#).unwrap_err();
+        let reasons: Vec<_> = errors.iter().map(|(_, reason)| reason).collect();
+        assert_eq!(
+            reasons[1..],
+            ["data was rejected by the version matcher"; 2]
+        );
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Quantity(u64);
+
+    from_version!(Quantity, Ver<Num<1>>, OneOf<u64, String>, |v| match v {
+        OneOf::First(n) => Ok(Quantity(n)),
+        OneOf::Second(s) => Ok(Quantity(s.parse()?)),
+    });
+
+    #[test]
+    fn test_one_of() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize::<Quantity, _>(&mut d)
+        };
+
+        assert_eq!(parse("3").unwrap(), Quantity(3));
+        assert_eq!(parse(r#""4""#).unwrap(), Quantity(4));
+        let error = parse("true").unwrap_err().to_string();
+        assert!(error.contains("data did not match any shape (u64: "));
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Percent(u8);
+
+    from_version!(Percent, Ver<Num<1>>, f64, std::convert::Infallible, |v| {
+        Ok(Percent((v * 100.0) as u8))
+    });
+
+    impl Validate for Percent {
+        type Error = &'static str;
+
+        fn validate(&self) -> Result<(), Self::Error> {
+            match self.0 <= 100 {
+                true => Ok(()),
+                false => Err("more than 100%"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_validated::<Percent, _>(&mut d)
+        };
+
+        assert_eq!(parse("50").unwrap(), Percent(50));
+        assert_eq!(parse("0.5").unwrap(), Percent(50));
+        assert_eq!(
+            parse("120").unwrap_err().to_string(),
+            "invalid data of version Current: more than 100%"
+        );
+        assert_eq!(
+            parse("1.5").unwrap_err().to_string(),
+            "invalid data of version Num<1>: more than 100%"
+        );
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Rating {
+        stars: u8,
+    }
+
+    impl FromVersionWarn<Ver<Num<1>>> for Rating {
+        type VersionType = f64;
+        type Error = &'static str;
+
+        fn convert_warn(v: f64, warnings: &mut Warnings) -> Result<Self, Self::Error> {
+            if v.fract() != 0.0 {
+                warnings.push(format_args!("rounded {v} stars"));
+            }
+            match v.round() {
+                stars @ 0.0..=5.0 => Ok(Rating { stars: stars as u8 }),
+                _ => Err("out of range"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_warnings() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_with_warnings::<Rating, _>(&mut d)
+        };
+
+        let (rating, warnings) = parse(r#"{"stars": 4}"#).unwrap();
+        assert_eq!(rating, Rating { stars: 4 });
+        assert!(warnings.is_empty());
+
+        let (rating, warnings) = parse("3.6").unwrap();
+        assert_eq!(rating, Rating { stars: 4 });
+        assert_eq!(warnings.iter().collect::<Vec<_>>(), ["rounded 3.6 stars"]);
+
+        assert!(parse("7.5").is_err());
+    }
+
+    version_name!(Billing2023 = "2023-billing");
+
+    from_version!(Cents, Ver<Named<Billing2023>>, String, |v| Ok(Cents(
+        v.trim_start_matches('$').parse::<u64>()? * 100
+    )));
+
+    #[test]
+    fn test_named_version() {
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Named<Billing2023>>>::deserialize_with_version::<Cents, _>(
+                &mut d,
+            )
+            .unwrap()
+        };
+
+        let (cents, version) = parse(r#""$3""#);
+        assert_eq!(cents.0, 300);
+        assert_eq!(version.name(), "Named<Billing2023>");
+        assert_eq!(version.tag(), "2023-billing");
+        assert_eq!(<Ver<Named<Billing2023>>>::name(), "2023-billing");
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Line {
+        from: (i32, i32),
+        to: Option<(i32, i32)>,
+        tags: Vec<String>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Segment {
+        from: (i32, i32),
+        to: Option<(i32, i32)>,
+        tags: Vec<String>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Tree {
+        label: String,
+        children: Vec<Tree>,
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint::<Line>(), fingerprint::<Line>());
+        assert_eq!(fingerprint::<Line>(), fingerprint::<Segment>());
+        assert_ne!(fingerprint::<Line>(), fingerprint::<Person>());
+        assert_ne!(fingerprint::<u32>(), fingerprint::<u64>());
+        assert_ne!(fingerprint::<Vec<u8>>(), fingerprint::<Option<u8>>());
+        assert_ne!(fingerprint::<Tree>(), 0);
+        assert_eq!(Fingerprint::<0xab>::name(), "00000000000000ab");
+    }
+
+    #[derive(Default)]
+    struct Interner {
+        names: Vec<String>,
+    }
+
+    struct Intern<'a>(&'a mut Interner);
+
+    impl<'de> serde::de::DeserializeSeed<'de> for Intern<'_> {
+        type Value = usize;
+
+        fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<usize, D::Error> {
+            let name = <String as serde::Deserialize>::deserialize(d)?;
+            let names = &mut self.0.names;
+            Ok(match names.iter().position(|n| *n == name) {
+                Some(i) => i,
+                None => {
+                    names.push(name);
+                    names.len() - 1
+                }
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Symbol(usize);
+
+    impl FromVersionSeed<Ver<Current>, Interner> for Symbol {
+        type VersionType = usize;
+        type Seed<'s> = Intern<'s>;
+        type Error = std::convert::Infallible;
+
+        fn seed(state: &mut Interner) -> Intern<'_> {
+            Intern(state)
+        }
+
+        fn convert_seeded(v: usize) -> Result<Self, Self::Error> {
+            Ok(Symbol(v))
+        }
+    }
+
+    // The first version stored the interned index directly.
+    impl FromVersionSeed<Ver<Num<1>>, Interner> for Symbol {
+        type VersionType = usize;
+        type Seed<'s> = std::marker::PhantomData<usize>;
+        type Error = std::convert::Infallible;
+
+        fn seed(_state: &mut Interner) -> Self::Seed<'_> {
+            std::marker::PhantomData
+        }
+
+        fn convert_seeded(v: usize) -> Result<Self, Self::Error> {
+            Ok(Symbol(v))
+        }
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut interner = Interner::default();
+        let mut parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_seed::<Symbol, _, _>(
+                &mut d,
+                &mut interner,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(parse(r#""a""#), Symbol(0));
+        assert_eq!(parse(r#""b""#), Symbol(1));
+        assert_eq!(parse(r#""a""#), Symbol(0));
+        assert_eq!(parse("1"), Symbol(1));
+        assert_eq!(interner.names, ["a", "b"]);
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    impl FromVersionAsync<Ver<Num<1>>> for User {
+        type VersionType = u32;
+        type Error = String;
+
+        async fn convert_async(id: Self::VersionType) -> Result<Self, Self::Error> {
+            let name = std::future::ready(match id {
+                1 => Some("root"),
+                _ => None,
+            })
+            .await;
+            match name {
+                Some(name) => Ok(User {
+                    name: name.to_string(),
+                }),
+                None => Err(format!("unknown user {id}")),
+            }
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_async() {
+        fn assert_send<T: Send>(t: T) -> T {
+            t
+        }
+
+        let parse = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            block_on(assert_send(
+                Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_async::<User, _>(&mut d),
+            ))
+        };
+
+        let root = User {
+            name: String::from("root"),
+        };
+        assert_eq!(parse(r#"{"name": "root"}"#).unwrap(), root);
+        assert_eq!(parse("1").unwrap(), root);
+        assert!(parse("2").is_err());
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Retired(u64);
+
+    from_version!(Retired, Ver<Num<4242>>, String, |s| Ok(Retired(s.parse()?)));
+
+    #[test]
+    fn test_installed_policy() {
+        type RetiredVersions = Versions<Ver<Current>, Ver<Num<4242>>>;
+
+        static POLICY: VersionPolicy = VersionPolicy::new();
+        static DENY: std::sync::LazyLock<VersionPolicy> =
+            std::sync::LazyLock::new(|| VersionPolicy::new().deny("Num<4242>"));
+        let json = || serde_json::Deserializer::from_str(r#""1""#);
+        let denied = |err: &dyn std::fmt::Display| err.to_string().contains("denies");
+
+        assert_eq!(
+            RetiredVersions::deserialize::<Retired, _>(&mut json()).unwrap(),
+            Retired(1)
+        );
+        set_policy(&DENY);
+
+        let err = RetiredVersions::deserialize::<Retired, _>(&mut json()).unwrap_err();
+        assert!(denied(&err));
+        let err = RetiredVersions::deserialize_with_version::<Retired, _>(&mut json()).unwrap_err();
+        assert!(denied(&err));
+        let errors = RetiredVersions::deserialize_detailed::<Retired, _>(&mut json())
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(errors.denied().collect::<Vec<_>>(), ["Num<4242>"]);
+        let err = RetiredVersions::deserialize_in_order::<Retired, _>(
+            &mut json(),
+            ProbeOrder::OldestFirst,
+        )
+        .unwrap_err();
+        assert!(denied(&err));
+        let err = RetiredVersions::deserialize_observed::<Retired, _>(
+            &mut json(),
+            &MigrationStats::new(),
+        )
+        .unwrap_err();
+        assert!(denied(&err));
+        let err =
+            RetiredVersions::deserialize_cloned::<Retired, _>(serde_json::json!("1")).unwrap_err();
+        assert!(denied(&err));
+        let err =
+            RetiredVersions::from_slice::<format::tests::Json, Retired>(br#""1""#).unwrap_err();
+        assert!(denied(&err));
+        let err =
+            RetiredVersions::deserialize_tagged::<Retired, _>("v4242", &mut json()).unwrap_err();
+        assert!(denied(&err));
+        let err = RetiredVersions::deserialize_at::<Retired, _>(1, &mut json()).unwrap_err();
+        assert!(denied(&err));
+        let err = RetiredVersions::deserialize_with_context::<Retired, (), _>(&mut json(), &())
+            .unwrap_err();
+        assert!(denied(&err));
+        let err =
+            RetiredVersions::deserialize_with_warnings::<Retired, _>(&mut json()).unwrap_err();
+        assert!(denied(&err));
+        let err =
+            RetiredVersions::deserialize_seed::<Retired, (), _>(&mut json(), &mut ()).unwrap_err();
+        assert!(denied(&err));
+        let err =
+            block_on(RetiredVersions::deserialize_async::<Retired, _>(&mut json())).unwrap_err();
+        assert!(denied(&err));
+
+        // A policy passed in replaces the installed one.
+        assert_eq!(
+            RetiredVersions::deserialize_with_policy::<Retired, _>(&mut json(), &POLICY).unwrap(),
+            Retired(1)
+        );
+    }
+
+    impl IntoVersion<Ver<Num<1>>> for Field3 {
+        type VersionType = String;
+        type Error = std::convert::Infallible;
+
+        fn convert(&self) -> Result<Self::VersionType, Self::Error> {
+            Ok((self.0 - 200).to_string())
+        }
+    }
+
+    #[test]
+    fn test_into_version() {
+        #[derive(serde::Serialize)]
+        struct Output {
+            #[serde(serialize_with = "IntoVersion::<Ver<Num<1>>>::serialize_versioned")]
+            old: Field3,
+            #[serde(serialize_with = "IntoVersion::<Ver<Current>>::serialize_versioned")]
+            current: Field3,
+        }
+
+        let output = Output {
+            old: Field3(300),
+            current: Field3(300),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            r#"{"old":"100","current":300}"#
+        );
+    }
+
+    #[test]
+    fn test_serialize_as() {
+        let mut old = Vec::new();
+        serialize_as::<Ver<Num<1>>, _, _>(&Field3(300), &mut serde_json::Serializer::new(&mut old))
+            .unwrap();
+        assert_eq!(old, br#""100""#);
+
+        let mut current = Vec::new();
+        serialize_as::<Ver<Current>, _, _>(
+            &Field3(300),
+            &mut serde_json::Serializer::new(&mut current),
+        )
+        .unwrap();
+        assert_eq!(current, b"300");
+    }
+
+    #[test]
+    fn test_versioned_field() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct New {
+            value: Versioned<Field3, (Ver<Current>, Ver<Uuid<3>>)>,
+            value3: Versioned<Field3, Versions<Ver<Current>, Ver<Num<1>>>>,
+        }
+
+        let new: New = serde_json::from_str(r#"{"value": "7", "value3": "1"}"#).unwrap();
+        assert_eq!(*new.value, Field3(14));
+        assert_eq!(new.value3.into_inner(), Field3(201));
+
+        let new: New = serde_json::from_str(r#"{"value": 7, "value3": 3}"#).unwrap();
+        assert_eq!(new.value, Versioned::new(Field3(7)));
+        assert_eq!(
+            serde_json::to_string(&new).unwrap(),
+            r#"{"value":7,"value3":3}"#
+        );
+    }
+
+    #[test]
+    fn test_deserialize_elements() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Log {
+            #[serde(
+                deserialize_with = "Versions::<Ver<Current>, Ver<Num<1>>>::deserialize_elements"
+            )]
+            events: Vec<Field3>,
+        }
+
+        let log: Log = serde_json::from_str(r#"{"events": [1, "2", 3]}"#).unwrap();
+        assert_eq!(log.events, [Field3(1), Field3(202), Field3(3)]);
+
+        let err = serde_json::from_str::<Log>(r#"{"events": [1, "2", true]}"#).unwrap_err();
+        assert!(err.to_string().starts_with("element 2: "), "{err}");
+    }
+
+    #[test]
+    fn test_deserialize_keys() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Deserialize)]
+        #[serde(try_from = "String")]
+        struct UserKey(String);
+
+        impl TryFrom<String> for UserKey {
+            type Error = &'static str;
+
+            fn try_from(key: String) -> Result<Self, Self::Error> {
+                match key.starts_with("user-") {
+                    true => Ok(UserKey(key)),
+                    false => Err("not a user key"),
+                }
+            }
+        }
+
+        struct NumericId;
+        impl VersionInfo for NumericId {
+            fn name() -> std::borrow::Cow<'static, str> {
+                "numeric-id".into()
+            }
+
+            fn ordinal() -> u128 {
+                0
+            }
+        }
+        impl FromVersion<Ver<NumericId>> for UserKey {
+            type VersionType = String;
+            type Error = std::num::ParseIntError;
+
+            fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+                Ok(UserKey(format!("user-{}", v.parse::<u64>()?)))
+            }
+        }
+
+        type UserKeyVersions = (Ver<Current>, Ver<NumericId>);
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Scores {
+            #[serde(
+                deserialize_with = "Versions::<Ver<Current>, Ver<NumericId>>::deserialize_keys"
+            )]
+            scores: std::collections::BTreeMap<UserKey, u32>,
+            by_key: std::collections::HashMap<Versioned<UserKey, UserKeyVersions>, u32>,
+        }
+
+        let json = r#"{"scores": {"user-1": 10, "2": 20}, "by_key": {"3": 30}}"#;
+        let scores: Scores = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            scores.scores.into_iter().collect::<Vec<_>>(),
+            [
+                (UserKey("user-1".to_owned()), 10),
+                (UserKey("user-2".to_owned()), 20)
+            ]
+        );
+        let key = Versioned::new(UserKey("user-3".to_owned()));
+        assert_eq!(scores.by_key[&key], 30);
+
+        let json = r#"{"scores": {"admin": 10}, "by_key": {}}"#;
+        assert!(serde_json::from_str::<Scores>(json).is_err());
+    }
+
+    #[test]
+    fn test_merged() {
+        type Core = Versions<Ver<Current>, Ver<Num<1>>>;
+        type Plugin = (Ver<Current>, Ver<Uuid<3>>);
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Counter {
+            #[serde(deserialize_with = "Merged::<Core, Plugin>::deserialize")]
+            value: Field3,
+            other: Versioned<Field3, Merged<Plugin, Core>>,
+        }
+
+        let counter: Counter = serde_json::from_str(r#"{"value": "1", "other": "1"}"#).unwrap();
+        assert_eq!(counter.value, Field3(201));
+        assert_eq!(counter.other.into_inner(), Field3(2));
+
+        assert_eq!(
+            <Merged<Core, Versions<Ver<Current>, Ver<Uuid<3>>>>>::versions(),
+            [
+                (String::from("Current"), Current::ordinal()),
+                (String::from("Num<1>"), 1),
+                (String::from("Uuid<3>"), 3),
+            ]
+        );
+        assert!(serde_json::from_str::<Counter>(r#"{"value": true, "other": 1}"#).is_err());
+    }
+
+    version_timeline! {
+        Field3Versions for Field3 {
+            Uuid<3> = String,
+            Num<1>,
+        }
+    }
+
+    #[test]
+    fn test_timeline() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Counters {
+            #[serde(deserialize_with = "Field3Versions::deserialize")]
+            a: Field3,
+            #[serde(deserialize_with = "Field3Versions::deserialize")]
+            b: Field3,
+        }
+
+        assert_eq!(
+            serde_json::from_str::<Counters>(r#"{"a": 1, "b": "1"}"#).unwrap(),
+            Counters {
+                a: Field3(1),
+                b: Field3(2),
+            }
+        );
+    }
+
+    struct Legacy;
+
+    impl FromVersion<Ver<Num<1>>, Legacy> for Field3 {
+        type VersionType = String;
+        type Error = std::num::ParseIntError;
+
+        fn convert(v: String) -> Result<Self, Self::Error> {
+            Ok(Field3(v.parse::<u64>()? + 100))
+        }
+    }
+
+    version_timeline! {
+        LegacyField3Versions for Field3, label = Legacy {
+            Num<1> = String,
+        }
+    }
+
+    #[test]
+    fn test_labeled_timeline() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Counter {
+            #[serde(deserialize_with = "LegacyField3Versions::deserialize")]
+            a: Field3,
+        }
+
+        assert_eq!(
+            serde_json::from_str::<Counter>(r#"{"a": "1"}"#).unwrap(),
+            Counter { a: Field3(101) }
+        );
+        assert_eq!(
+            serde_json::from_str::<Counter>(r#"{"a": 1}"#).unwrap(),
+            Counter { a: Field3(1) }
         );
     }
 }
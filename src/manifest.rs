@@ -0,0 +1,262 @@
+//! Manifests listing the versions of a type, kept as the single source of
+//! truth across services and checked against the compiled converters.
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::format::Format;
+use crate::{same_name, DeserializeVersioned};
+
+/// The names and ordinals of the versions of a `Versions`, in the order they are attempted.
+pub trait VersionList {
+    fn versions() -> Vec<(String, u128)>;
+}
+
+/// The versions a type is expected to have, in the order they are attempted.
+/// Deserializable from any format, e.g. a TOML file read with `from_file`:
+///
+/// ```toml
+/// [[versions]]
+/// name = "Current"
+/// fixture = "fixtures/point_v3.json"
+///
+/// [[versions]]
+/// name = "Num<2>"
+/// id = 2
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Manifest {
+    versions: Vec<ManifestVersion>,
+    #[serde(skip)]
+    base_dir: Option<PathBuf>,
+}
+
+/// A version of a `Manifest`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestVersion {
+    /// The name of the marker, e.g. `Num<2>`, or `Current` for the current version
+    pub name: String,
+    /// The ordinal of the version, see `VersionInfo::ordinal`
+    #[serde(default)]
+    pub id: Option<u128>,
+    /// The path of a file with data of the version, relative to the directory
+    /// of the manifest, see `Manifest::with_base_dir`
+    #[serde(default)]
+    pub fixture: Option<PathBuf>,
+}
+
+impl Manifest {
+    pub fn new(versions: Vec<ManifestVersion>) -> Self {
+        Manifest {
+            versions,
+            base_dir: None,
+        }
+    }
+
+    /// Reads a manifest encoded with `F`, with the fixtures relative to the
+    /// directory of the file.
+    pub fn from_file<F: Format>(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let manifest = F::from_slice_seed(&bytes, PhantomData::<Manifest>)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(manifest.with_base_dir(base_dir))
+    }
+
+    /// Sets the directory the fixtures are relative to, which is the
+    /// working directory if the manifest wasn't read with `from_file`.
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    pub fn versions(&self) -> &[ManifestVersion] {
+        &self.versions
+    }
+
+    /// Checks that `T` has exactly the versions of the manifest, in its
+    /// order and with its ids, and that each fixture, encoded with `F`, is
+    /// read as `T`, e.g. at startup.
+    pub fn check<T, F>(&self) -> Result<(), ManifestErrors>
+    where
+        T: DeserializeVersioned,
+        T::Versions: VersionList,
+        F: Format,
+    {
+        let compiled = T::Versions::versions();
+        let mut errors = Vec::new();
+        for (i, version) in self.versions.iter().enumerate() {
+            let name = &version.name;
+            match compiled
+                .iter()
+                .position(|(compiled, _)| same_name(compiled, name))
+            {
+                None => errors.push(format!("{name} is not a version of the type")),
+                Some(index) if index != i => {
+                    errors.push(format!("{name} is attempted at position {index}, not {i}"))
+                }
+                Some(index) => match (version.id, compiled[index].1) {
+                    (Some(id), ordinal) if id != ordinal => {
+                        errors.push(format!("{name} has id {ordinal}, not {id}"))
+                    }
+                    _ => {}
+                },
+            }
+            if let Some(path) = &version.fixture {
+                let full_path = match &self.base_dir {
+                    Some(dir) => dir.join(path),
+                    None => path.clone(),
+                };
+                let res = std::fs::read(full_path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|bytes| {
+                        T::from_slice_any_version::<F>(&bytes).map_err(|err| err.to_string())
+                    });
+                if let Err(err) = res {
+                    errors.push(format!("fixture {} of {name}: {err}", path.display()));
+                }
+            }
+        }
+        for (name, _) in &compiled {
+            if !self
+                .versions
+                .iter()
+                .any(|version| same_name(&version.name, name))
+            {
+                errors.push(format!("{name} is missing from the manifest"));
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ManifestErrors { errors }),
+        }
+    }
+}
+
+/// How the compiled versions of a type differ from a `Manifest`.
+#[derive(Debug)]
+pub struct ManifestErrors {
+    errors: Vec<String>,
+}
+
+impl ManifestErrors {
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.errors.iter().map(String::as_str)
+    }
+}
+
+impl fmt::Display for ManifestErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the versions don't match the manifest: ")?;
+        f.write_str(&self.errors.join("; "))
+    }
+}
+
+impl std::error::Error for ManifestErrors {}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::format::tests::Json;
+    use crate::{Current, FromVersion, Num, Sem, Ver, Versions};
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Gain(f64);
+
+    impl FromVersion<Ver<Num<1>>> for Gain {
+        type VersionType = String;
+        type Error = std::num::ParseFloatError;
+
+        fn convert(v: String) -> Result<Self, Self::Error> {
+            Ok(Gain(v.parse()?))
+        }
+    }
+
+    impl FromVersion<Ver<Sem<0, 9, 0>>> for Gain {
+        type VersionType = (f64,);
+        type Error = Infallible;
+
+        fn convert((v,): (f64,)) -> Result<Self, Self::Error> {
+            Ok(Gain(v))
+        }
+    }
+
+    impl DeserializeVersioned for Gain {
+        type Versions = Versions<Ver<Current>, Ver<Num<1>>, Ver<Sem<0, 9, 0>>>;
+
+        fn deserialize_any_version<'de, D: serde::Deserializer<'de>>(
+            d: D,
+        ) -> Result<Self, D::Error> {
+            Self::Versions::deserialize(d)
+        }
+    }
+
+    fn version(name: &str, id: Option<u128>, fixture: Option<&str>) -> ManifestVersion {
+        ManifestVersion {
+            name: name.into(),
+            id,
+            fixture: fixture.map(PathBuf::from),
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let manifest = Manifest::new(vec![
+            version("Current", None, None),
+            version("Num<1>", Some(1), None),
+            version("Sem<0,9,0>", None, None),
+        ]);
+        manifest.check::<Gain, Json>().unwrap();
+
+        let manifest = Manifest::new(vec![
+            version("Num<1>", Some(2), None),
+            version("Current", None, None),
+        ]);
+        let errors = manifest.check::<Gain, Json>().unwrap_err();
+        assert_eq!(
+            errors.iter().collect::<Vec<_>>(),
+            [
+                "Num<1> is attempted at position 1, not 0",
+                "Current is attempted at position 0, not 1",
+                "Sem<0, 9, 0> is missing from the manifest",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let dir = std::env::temp_dir().join(format!("manifest-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("fixtures")).unwrap();
+        std::fs::write(dir.join("fixtures/v1.json"), r#""1.5""#).unwrap();
+        std::fs::write(dir.join("fixtures/v0.json"), "[1.5]").unwrap();
+        std::fs::write(
+            dir.join("manifest.json"),
+            r#"{"versions": [
+                {"name": "Current"},
+                {"name": "Num<1>", "fixture": "fixtures/v1.json"},
+                {"name": "Sem<0, 9, 0>", "fixture": "fixtures/v0.json"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let manifest = Manifest::from_file::<Json>(dir.join("manifest.json")).unwrap();
+        let res = manifest.check::<Gain, Json>();
+        let unrooted = Manifest::new(manifest.versions().to_vec()).check::<Gain, Json>();
+        let rooted = Manifest::new(manifest.versions().to_vec())
+            .with_base_dir(&dir)
+            .check::<Gain, Json>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        res.unwrap();
+        rooted.unwrap();
+        assert_eq!(unrooted.unwrap_err().iter().count(), 2);
+        assert!(Manifest::from_file::<Json>(dir.join("manifest.json")).is_err());
+    }
+}
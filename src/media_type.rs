@@ -0,0 +1,121 @@
+//! Media types naming versions, e.g. `application/vnd.myapi.v2+json`, to
+//! read request bodies as the version their `Content-Type` names.
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{CurrentVersion, DeserializeVersioned, VersionInfo};
+
+/// Maps media types of the form `application/vnd.{vendor}.{tag}+{suffix}` to
+/// version tags and back, e.g. `application/vnd.myapi.v2+json` for `Num<2>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaTypes {
+    vendor: &'static str,
+    suffix: &'static str,
+}
+
+impl MediaTypes {
+    /// Media types of the vendor and structured syntax suffix, e.g. `"myapi"` and `"json"`.
+    pub const fn new(vendor: &'static str, suffix: &'static str) -> Self {
+        MediaTypes { vendor, suffix }
+    }
+
+    /// The version tag named by the media type, e.g. `v2`.
+    /// Parameters such as `; charset=utf-8` are ignored.
+    /// `None` if the media type isn't one of the vendor's, or names no version.
+    pub fn version<'a>(&self, media_type: &'a str) -> Option<&'a str> {
+        let essence = media_type.split(';').next()?.trim();
+        let rest = strip_prefix_ignore_case(essence, "application/vnd.")?;
+        let rest = strip_prefix_ignore_case(rest, self.vendor)?.strip_prefix('.')?;
+        let (tag, suffix) = rest.rsplit_once('+')?;
+        match suffix.eq_ignore_ascii_case(self.suffix) && !tag.is_empty() {
+            true => Some(tag),
+            false => None,
+        }
+    }
+
+    /// The media type naming the version with the tag.
+    pub fn media_type(&self, tag: &str) -> String {
+        format!("application/vnd.{}.{tag}+{}", self.vendor, self.suffix)
+    }
+
+    /// The media type naming the version, e.g. for the `Content-Type` of a response.
+    pub fn media_type_of<V: VersionInfo>(&self) -> String {
+        self.media_type(&V::name())
+    }
+
+    /// Deserializes the body as the version named by its media type, or from
+    /// any version if the media type names none, e.g. `application/json`.
+    pub fn deserialize<'de, T, D>(&self, content_type: &str, d: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + DeserializeVersioned + CurrentVersion,
+        D: Deserializer<'de>,
+    {
+        match self.version(content_type) {
+            Some(tag) if T::Marker::accepts(tag) => T::deserialize(d),
+            Some(tag) => T::deserialize_version(tag, d),
+            None => T::deserialize_any_version(d),
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::tests::Adjacent;
+    use crate::Num;
+
+    const MEDIA_TYPES: MediaTypes = MediaTypes::new("test", "json");
+
+    #[test]
+    fn test_version() {
+        let version = |media_type| MEDIA_TYPES.version(media_type);
+        assert_eq!(version("application/vnd.test.v2+json"), Some("v2"));
+        assert_eq!(
+            version("Application/VND.Test.v2+JSON; charset=utf-8"),
+            Some("v2")
+        );
+        assert_eq!(
+            version("application/vnd.test.2023-10+json"),
+            Some("2023-10")
+        );
+        assert_eq!(version("application/json"), None);
+        assert_eq!(version("application/vnd.other.v2+json"), None);
+        assert_eq!(version("application/vnd.testing.v2+json"), None);
+        assert_eq!(version("application/vnd.test.v2+xml"), None);
+        assert_eq!(version("application/vnd.test.+json"), None);
+        assert_eq!(version("application/vnd.test+json"), None);
+
+        assert_eq!(MEDIA_TYPES.media_type("v1"), "application/vnd.test.v1+json");
+        assert_eq!(
+            MEDIA_TYPES.media_type_of::<Num<2>>(),
+            "application/vnd.test.v2+json"
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let read = |content_type, json| {
+            let d = &mut serde_json::Deserializer::from_str(json);
+            MEDIA_TYPES.deserialize::<Adjacent, _>(content_type, d)
+        };
+        let current = r#"{"value": 3}"#;
+        let old = r#"{"reading": "3"}"#;
+        let value = Adjacent { value: 3 };
+        assert_eq!(
+            read("application/vnd.test.v2+json", current).unwrap(),
+            value
+        );
+        assert_eq!(read("application/vnd.test.v1+json", old).unwrap(), value);
+        assert_eq!(read("application/json", old).unwrap(), value);
+        assert!(read("application/vnd.test.v1+json", current).is_err());
+        assert!(read("application/vnd.test.v2+json", old).is_err());
+        assert!(read("application/vnd.test.v3+json", current).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Hooks reporting which versions data is read as, e.g. to export counters
+//! of payloads by version and retire converters once their traffic stops,
+//! without depending on any telemetry framework.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{VersionErrors, VersionId, VersionNames};
+
+/// Observes the versions data is attempted and read as.
+/// Every callback does nothing by default.
+pub trait MigrationObserver: Send + Sync {
+    /// Called before data of the type, e.g. `Order`, is attempted as the version.
+    fn on_attempt(&self, type_name: &str, version: &VersionId) {
+        let _ = (type_name, version);
+    }
+
+    /// Called when data of the type is read as the version.
+    fn on_match(&self, type_name: &str, version: &VersionId) {
+        let _ = (type_name, version);
+    }
+
+    /// Called when the version rejects data of the type, because either the
+    /// data doesn't have its shape or its converter failed.
+    fn on_convert_error(&self, type_name: &str, version: &VersionId, error: &str) {
+        let _ = (type_name, version, error);
+    }
+
+    /// Called when every version rejected data of the type.
+    fn on_exhausted(&self, type_name: &str, errors: &VersionErrors) {
+        let _ = (type_name, errors);
+    }
+}
+
+static OBSERVER: RwLock<Option<&'static dyn MigrationObserver>> = RwLock::new(None);
+
+/// Installs the observer for every versioned type, replacing any installed before.
+pub fn set_observer(observer: &'static dyn MigrationObserver) {
+    *OBSERVER.write().unwrap_or_else(|err| err.into_inner()) = Some(observer);
+}
+
+fn observer() -> Option<&'static dyn MigrationObserver> {
+    *OBSERVER.read().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Reports the attempts of reading data of a type to the observer of a call,
+/// or the installed one. Names are only formatted if there is an observer.
+pub(crate) struct Events<'a> {
+    observer: Option<&'a dyn MigrationObserver>,
+    type_name: String,
+}
+
+impl<'a> Events<'a> {
+    pub(crate) fn new<R>(observer: Option<&'a dyn MigrationObserver>) -> Self {
+        let observer = observer.or_else(|| self::observer().map(|observer| observer as _));
+        Events {
+            observer,
+            type_name: observer.map_or_else(String::new, |_| crate::marker_name::<R>()),
+        }
+    }
+
+    fn report(
+        &self,
+        index: usize,
+        names: VersionNames,
+        f: impl FnOnce(&dyn MigrationObserver, &str, &VersionId),
+    ) {
+        if let Some(observer) = self.observer {
+            f(observer, &self.type_name, &names.id(index));
+        }
+    }
+
+    pub(crate) fn attempt(&self, index: usize, names: VersionNames) {
+        self.report(index, names, |observer, ty, version| {
+            observer.on_attempt(ty, version)
+        });
+    }
+
+    pub(crate) fn matched(&self, index: usize, names: VersionNames) {
+        self.report(index, names, |observer, ty, version| {
+            observer.on_match(ty, version)
+        });
+    }
+
+    pub(crate) fn rejected(&self, index: usize, names: VersionNames, error: &str) {
+        self.report(index, names, |observer, ty, version| {
+            observer.on_convert_error(ty, version, error)
+        });
+    }
+
+    pub(crate) fn exhausted(&self, errors: &VersionErrors) {
+        if let Some(observer) = self.observer {
+            observer.on_exhausted(&self.type_name, errors);
+        }
+    }
+}
+
+/// Logs the first time data of each type is read as each of its old versions,
+/// or once per interval with `every`, e.g. to find the producers still writing
+/// old data without a log line per record. Install it with `set_observer`.
+pub struct DeprecationLog {
+    seen: Mutex<Vec<Seen>>,
+    interval: Option<Duration>,
+    log: fn(&str),
+}
+
+struct Seen {
+    type_name: String,
+    version: String,
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+impl DeprecationLog {
+    /// Logs to stderr.
+    pub const fn new() -> Self {
+        Self::with_logger(|message| eprintln!("{message}"))
+    }
+
+    /// Logs with the function, e.g. one forwarding to your logger.
+    pub const fn with_logger(log: fn(&str)) -> Self {
+        DeprecationLog {
+            seen: Mutex::new(Vec::new()),
+            interval: None,
+            log,
+        }
+    }
+
+    /// Logs each old version again once the interval has passed since it was
+    /// last logged, with the number of times it was seen in between.
+    pub const fn every(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+}
+
+impl Default for DeprecationLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MigrationObserver for DeprecationLog {
+    fn on_match(&self, type_name: &str, version: &VersionId) {
+        if version.is_current() {
+            return;
+        }
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        let name = version.name();
+        let message = match seen
+            .iter_mut()
+            .find(|seen| seen.type_name == type_name && seen.version == name)
+        {
+            None => {
+                seen.push(Seen {
+                    type_name: type_name.to_owned(),
+                    version: name.to_owned(),
+                    logged_at: Instant::now(),
+                    suppressed: 0,
+                });
+                format!("legacy version {name} seen for type {type_name}")
+            }
+            Some(seen) => match self.interval {
+                Some(interval) if seen.logged_at.elapsed() >= interval => {
+                    let times = seen.suppressed + 1;
+                    seen.logged_at = Instant::now();
+                    seen.suppressed = 0;
+                    format!("legacy version {name} seen for type {type_name} again, {times}x since last logged")
+                }
+                _ => {
+                    seen.suppressed += 1;
+                    return;
+                }
+            },
+        };
+        drop(seen);
+        (self.log)(&message);
+    }
+}
+
+/// Counts the matches and rejections of each version of each type, and the
+/// time spent attempting them, e.g. for a summary of a backfill.
+/// Install it with `set_observer`, or pass it to `deserialize_observed`.
+#[derive(Default)]
+pub struct MigrationStats {
+    versions: Mutex<BTreeMap<(String, String), VersionStats>>,
+    exhausted: Mutex<BTreeMap<String, u64>>,
+}
+
+thread_local! {
+    static ATTEMPT_STARTED: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+impl MigrationStats {
+    pub const fn new() -> Self {
+        MigrationStats {
+            versions: Mutex::new(BTreeMap::new()),
+            exhausted: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The counts so far, serializable e.g. as JSON.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let versions = self.versions.lock().unwrap_or_else(|err| err.into_inner());
+        let exhausted = self.exhausted.lock().unwrap_or_else(|err| err.into_inner());
+        StatsSnapshot {
+            versions: versions
+                .iter()
+                .map(|((type_name, version), stats)| VersionSnapshot {
+                    type_name: type_name.clone(),
+                    version: version.clone(),
+                    matches: stats.matches,
+                    failures: stats.failures,
+                    duration_ns: stats.duration.as_nanos().try_into().unwrap_or(u64::MAX),
+                })
+                .collect(),
+            exhausted: exhausted.clone(),
+        }
+    }
+
+    fn record(&self, type_name: &str, version: &VersionId, matched: bool) {
+        let elapsed = ATTEMPT_STARTED
+            .take()
+            .map_or(Duration::ZERO, |started| started.elapsed());
+        let mut versions = self.versions.lock().unwrap_or_else(|err| err.into_inner());
+        let key = (type_name.to_owned(), version.name().to_owned());
+        let stats = versions.entry(key).or_default();
+        match matched {
+            true => stats.matches += 1,
+            false => stats.failures += 1,
+        }
+        stats.duration += elapsed;
+    }
+}
+
+#[derive(Default)]
+struct VersionStats {
+    matches: u64,
+    failures: u64,
+    duration: Duration,
+}
+
+impl MigrationObserver for MigrationStats {
+    fn on_attempt(&self, _type_name: &str, _version: &VersionId) {
+        ATTEMPT_STARTED.set(Some(Instant::now()));
+    }
+
+    fn on_match(&self, type_name: &str, version: &VersionId) {
+        self.record(type_name, version, true);
+    }
+
+    fn on_convert_error(&self, type_name: &str, version: &VersionId, _error: &str) {
+        self.record(type_name, version, false);
+    }
+
+    fn on_exhausted(&self, type_name: &str, _errors: &VersionErrors) {
+        let mut exhausted = self.exhausted.lock().unwrap_or_else(|err| err.into_inner());
+        *exhausted.entry(type_name.to_owned()).or_default() += 1;
+    }
+}
+
+/// The counts of a `MigrationStats` at some point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatsSnapshot {
+    /// The counts of each version of each type that was attempted
+    pub versions: Vec<VersionSnapshot>,
+    /// The number of times every version of the type rejected the data
+    pub exhausted: BTreeMap<String, u64>,
+}
+
+/// The counts of a version of a type in a `StatsSnapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionSnapshot {
+    pub type_name: String,
+    pub version: String,
+    /// The number of times data was read as the version
+    pub matches: u64,
+    /// The number of times the version rejected the data
+    pub failures: u64,
+    /// The total time spent attempting the version, in nanoseconds
+    pub duration_ns: u64,
+}
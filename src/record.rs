@@ -0,0 +1,396 @@
+//! Flat records of strings, such as CSV rows or query strings, read by parsing
+//! each field as the type the version asks for, so records with other layouts
+//! can be probed without buffering.
+
+use std::marker::PhantomData;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{self, DeserializeSeed, Deserializer, IntoDeserializer, Unexpected, Visitor};
+
+use crate::DeserializeVersioned;
+
+/// Deserializes a record from any version, e.g. a row of an old CSV export,
+/// with the headers of its file, or `None` if it has none.
+///
+/// Columns are matched by header, so reordered columns read the same, renamed
+/// headers can be handled by a version with the old names, and columns missing
+/// from a narrower layout are absent fields. Without headers, fields are read in order.
+pub fn from_record<'de, T, S>(
+    headers: Option<&'de [S]>,
+    fields: &'de [S],
+) -> Result<T, de::value::Error>
+where
+    T: DeserializeVersioned,
+    S: AsRef<str>,
+{
+    T::deserialize_cloned(RecordDeserializer::new(headers, fields))
+}
+
+/// Deserializes a URL query string or form body from any version, e.g.
+/// `name=ada&age=36`, with each value parsed as the type the version asks for.
+/// Parameters without a value are empty, and only the first of repeated parameters matters.
+pub fn from_query<T: DeserializeVersioned>(query: &str) -> Result<T, de::value::Error> {
+    let mut names = Vec::new();
+    let mut values = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let name = percent_decode(name);
+        if !names.contains(&name) {
+            names.push(name);
+            values.push(percent_decode(value));
+        }
+    }
+    from_record(Some(&names), &values)
+}
+
+/// Decodes `+` as a space and `%XX` as the byte `XX`, as in form bodies.
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(decoded) => {
+                        bytes.push(decoded);
+                        rest = &rest[2..];
+                    }
+                    None => bytes.push(b'%'),
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Deserializes a record as a map from header to field, or as a sequence of
+/// fields, parsing each field as the type asked for.
+pub struct RecordDeserializer<'de, S, E> {
+    headers: Option<&'de [S]>,
+    fields: &'de [S],
+    err: PhantomData<E>,
+}
+
+impl<'de, S, E> RecordDeserializer<'de, S, E> {
+    pub fn new(headers: Option<&'de [S]>, fields: &'de [S]) -> Self {
+        RecordDeserializer {
+            headers,
+            fields,
+            err: PhantomData,
+        }
+    }
+}
+
+impl<S, E> Clone for RecordDeserializer<'_, S, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S, E> Copy for RecordDeserializer<'_, S, E> {}
+
+impl<'de, S: AsRef<str>, E: de::Error> RecordDeserializer<'de, S, E> {
+    fn visit_fields<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        let mut seq = de::value::SeqDeserializer::new(
+            self.fields
+                .iter()
+                .map(|field| FieldDeserializer::new(field.as_ref())),
+        );
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+}
+
+impl<'de, S: AsRef<str>, E: de::Error> Deserializer<'de> for RecordDeserializer<'de, S, E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.headers {
+            Some(headers) => visitor.visit_map(Columns {
+                headers,
+                fields: self.fields,
+                index: 0,
+                err: PhantomData,
+            }),
+            None => self.visit_fields(visitor),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.visit_fields(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, E> {
+        self.visit_fields(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.visit_fields(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct map struct enum identifier ignored_any
+    }
+}
+
+/// The fields of a record, keyed by their headers.
+/// Fields past the headers, and headers past the fields, are skipped.
+struct Columns<'de, S, E> {
+    headers: &'de [S],
+    fields: &'de [S],
+    index: usize,
+    err: PhantomData<E>,
+}
+
+impl<'de, S: AsRef<str>, E: de::Error> de::MapAccess<'de> for Columns<'de, S, E> {
+    type Error = E;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, E> {
+        let (Some(header), Some(_)) = (self.headers.get(self.index), self.fields.get(self.index))
+        else {
+            return Ok(None);
+        };
+        let header: StrDeserializer<'de, E> = header.as_ref().into_deserializer();
+        seed.deserialize(header).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, E> {
+        let field = self.fields[self.index].as_ref();
+        self.index += 1;
+        seed.deserialize(FieldDeserializer::new(field))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.headers.len().min(self.fields.len()) - self.index)
+    }
+}
+
+/// Deserializes a field by parsing it as the type asked for.
+pub(crate) struct FieldDeserializer<'de, E> {
+    field: &'de str,
+    err: PhantomData<E>,
+}
+
+impl<'de, E> FieldDeserializer<'de, E> {
+    pub(crate) fn new(field: &'de str) -> Self {
+        FieldDeserializer {
+            field,
+            err: PhantomData,
+        }
+    }
+}
+
+impl<'de, E: de::Error> IntoDeserializer<'de, E> for FieldDeserializer<'de, E> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_field {
+    ($($method: ident => $visit: ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+                match self.field.trim().parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(self.field), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for FieldDeserializer<'de, E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_borrowed_str(self.field)
+    }
+
+    parse_field! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        let mut chars = self.field.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => visitor.visit_borrowed_str(self.field),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_borrowed_bytes(self.field.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.field.is_empty() {
+            true => visitor.visit_none(),
+            false => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.field.is_empty() {
+            true => visitor.visit_unit(),
+            false => Err(de::Error::invalid_value(
+                Unexpected::Str(self.field),
+                &visitor,
+            )),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        let variant: StrDeserializer<'de, E> = self.field.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Current, FromVersion, Num, Ver, Versions};
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Person {
+        name: String,
+        age: u8,
+        email: Option<String>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct PersonV1 {
+        full_name: String,
+        age: u8,
+    }
+
+    impl FromVersion<Ver<Num<1>>> for Person {
+        type VersionType = PersonV1;
+        type Error = std::convert::Infallible;
+
+        fn convert(v: PersonV1) -> Result<Self, Self::Error> {
+            Ok(Person {
+                name: v.full_name,
+                age: v.age,
+                email: None,
+            })
+        }
+    }
+
+    impl DeserializeVersioned for Person {
+        type Versions = Versions<Ver<Current>, Ver<Num<1>>>;
+
+        fn deserialize_any_version<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            Self::Versions::deserialize(d)
+        }
+
+        fn deserialize_cloned<'de, D>(d: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de> + Clone,
+        {
+            Self::Versions::deserialize_cloned(d)
+        }
+    }
+
+    fn ada(email: Option<&str>) -> Person {
+        Person {
+            name: "Ada".to_owned(),
+            age: 36,
+            email: email.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_from_record() {
+        let headers = ["age", "email", "name"];
+        let person: Person =
+            from_record(Some(&headers), &["36", "ada@example.com", "Ada"]).unwrap();
+        assert_eq!(person, ada(Some("ada@example.com")));
+        let person: Person = from_record(Some(&headers), &[" 36 ", "", "Ada"]).unwrap();
+        assert_eq!(person, ada(None));
+
+        // An old export, with other headers.
+        let person: Person = from_record(Some(&["full_name", "age"]), &["Ada", "36"]).unwrap();
+        assert_eq!(person, ada(None));
+
+        // Without headers, in order, and with a field too many.
+        let person: Person = from_record(None, &["Ada", "36", "ada@example.com"]).unwrap();
+        assert_eq!(person, ada(Some("ada@example.com")));
+        assert!(from_record::<Person, _>(None, &["Ada", "36", "", "extra"]).is_err());
+
+        let err = from_record::<Person, _>(Some(&headers), &["old", "", "Ada"]).unwrap_err();
+        assert!(err.to_string().contains("expected u8"), "{err}");
+    }
+
+    #[test]
+    fn test_from_query() {
+        let person: Person = from_query("name=Ada&age=36&email=ada%40example.com").unwrap();
+        assert_eq!(person, ada(Some("ada@example.com")));
+        let person: Person = from_query("full_name=Ada+L&&age=36&age=37").unwrap();
+        assert_eq!(person.name, "Ada L");
+        assert_eq!(person.age, 36);
+        let person: Person = from_query("name=Ada&age=36&email").unwrap();
+        assert_eq!(person, ada(None));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a+b%20c"), "a b c");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
@@ -0,0 +1,252 @@
+//! Versions registered at runtime, for plugins that add versions the
+//! compiled `Versions` can't know about.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Error as _};
+
+use crate::content::{Content, ContentRefDeserializer};
+use crate::{FromVersion, Ver, VersionErrors, VersionInfo};
+
+type Convert<T> = Box<dyn Fn(&Content<'_>) -> Result<T, String> + Send + Sync>;
+
+struct Entry<T> {
+    tag: String,
+    /// The name in errors: the marker of a compiled version, or else the tag.
+    name: String,
+    accepts: Box<dyn Fn(&str) -> bool + Send + Sync>,
+    convert: Convert<T>,
+}
+
+/// The versions of `T` registered at runtime, each with its tag and converter.
+/// Versions are attempted in the order they were registered.
+pub struct Registry<T> {
+    entries: Vec<Entry<T>>,
+    tenants: HashMap<String, Vec<String>>,
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Registry {
+            entries: Vec::new(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Registry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.tags()).finish()
+    }
+}
+
+impl<T> Registry<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the version with the tag, read as `V` and converted with `convert`.
+    pub fn register<V, F, E>(&mut self, tag: impl Into<String>, convert: F) -> &mut Self
+    where
+        V: for<'a> Deserialize<'a>,
+        F: Fn(V) -> Result<T, E> + Send + Sync + 'static,
+        E: fmt::Display,
+    {
+        let tag: String = tag.into();
+        let accepted = tag.clone();
+        self.entries.push(Entry {
+            name: tag.clone(),
+            tag,
+            accepts: Box::new(move |other| other == accepted),
+            convert: Box::new(move |content| {
+                let v = V::deserialize(ContentRefDeserializer::<de::value::Error>::new(content))
+                    .map_err(|err| err.to_string())?;
+                convert(v).map_err(|err| err.to_string())
+            }),
+        });
+        self
+    }
+
+    /// Registers a compiled version of `T`, accepting the tags `V` accepts.
+    pub fn register_version<V>(&mut self) -> &mut Self
+    where
+        V: VersionInfo + 'static,
+        T: FromVersion<Ver<V>> + for<'a> Deserialize<'a>,
+    {
+        self.entries.push(Entry {
+            tag: V::name().into_owned(),
+            name: crate::marker_name::<V>(),
+            accepts: Box::new(V::accepts),
+            convert: Box::new(|content| {
+                <T as FromVersion<Ver<V>>>::deserialize_versioned::<
+                    ContentRefDeserializer<'_, '_, de::value::Error>,
+                >(content)
+                .map_err(|err| err.to_string())
+            }),
+        });
+        self
+    }
+
+    /// The tags of the registered versions, in the order they are attempted.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.tag.as_str())
+    }
+
+    /// Restricts the tenant, e.g. a partition of a multi-tenant store, to the
+    /// registered versions with the tags, so its data is never read as the
+    /// versions of other tenants. Tenants without versions read none.
+    pub fn set_tenant_versions<I>(&mut self, tenant: impl Into<String>, tags: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let tags = tags.into_iter().map(Into::into).collect();
+        self.tenants.insert(tenant.into(), tags);
+        self
+    }
+
+    /// Deserializes the data as the registered version accepting the tag.
+    pub fn deserialize_dyn<'de, D: Deserializer<'de>>(
+        &self,
+        tag: &str,
+        d: D,
+    ) -> Result<T, D::Error> {
+        deserialize_dyn(self.entries.iter(), tag, d)
+    }
+
+    /// Deserializes the data of the tenant as the version accepting the tag,
+    /// if the tenant's versions include it.
+    pub fn deserialize_dyn_for<'de, D: Deserializer<'de>>(
+        &self,
+        tenant: &str,
+        tag: &str,
+        d: D,
+    ) -> Result<T, D::Error> {
+        deserialize_dyn(self.tenant_entries(tenant), tag, d)
+    }
+
+    /// Deserializes the data as the first registered version it matches.
+    pub fn deserialize_any_version<'de, D: Deserializer<'de>>(&self, d: D) -> Result<T, D::Error> {
+        deserialize_any_version(self.entries.iter(), d)
+    }
+
+    /// Deserializes the data of the tenant as the first of the tenant's versions it matches.
+    pub fn deserialize_any_version_for<'de, D: Deserializer<'de>>(
+        &self,
+        tenant: &str,
+        d: D,
+    ) -> Result<T, D::Error> {
+        deserialize_any_version(self.tenant_entries(tenant), d)
+    }
+
+    fn tenant_entries<'s>(&'s self, tenant: &str) -> impl Iterator<Item = &'s Entry<T>> + 's {
+        let tags = self.tenants.get(tenant).map_or(&[][..], Vec::as_slice);
+        self.entries
+            .iter()
+            .filter(move |entry| tags.contains(&entry.tag))
+    }
+}
+
+fn deserialize_dyn<'s, 'de, T: 's, D: Deserializer<'de>>(
+    mut entries: impl Iterator<Item = &'s Entry<T>>,
+    tag: &str,
+    d: D,
+) -> Result<T, D::Error> {
+    let Some(entry) = entries.find(|entry| (entry.accepts)(tag)) else {
+        return Err(D::Error::custom(format_args!("unknown version `{tag}`")));
+    };
+    if !crate::installed_policy().permits(&entry.name) {
+        return Err(D::Error::custom(format_args!(
+            "the policy denies version {}",
+            entry.name
+        )));
+    }
+    let content = Content::deserialize(d)?;
+    (entry.convert)(&content).map_err(D::Error::custom)
+}
+
+fn deserialize_any_version<'s, 'de, T: 's, D: Deserializer<'de>>(
+    entries: impl Iterator<Item = &'s Entry<T>>,
+    d: D,
+) -> Result<T, D::Error> {
+    let content = Content::deserialize(d)?;
+    let mut errors = VersionErrors::default();
+    let policy = crate::installed_policy();
+    for entry in entries {
+        if !policy.permits(&entry.name) {
+            errors.denied.push(entry.name.clone());
+            continue;
+        }
+        match (entry.convert)(&content) {
+            Ok(res) => return Ok(res),
+            Err(err) => errors.errors.push((entry.name.clone(), err)),
+        }
+    }
+    Err(D::Error::custom(errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Num;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Meters(f64);
+
+    impl FromVersion<Ver<Num<1>>> for Meters {
+        type VersionType = f64;
+        type Error = std::convert::Infallible;
+
+        fn convert(feet: f64) -> Result<Self, Self::Error> {
+            Ok(Meters(feet * 0.3048))
+        }
+    }
+
+    fn registry() -> Registry<Meters> {
+        let mut registry = Registry::new();
+        registry
+            .register("cm", |cm: u32| {
+                Ok::<_, String>(Meters(f64::from(cm) / 100.0))
+            })
+            .register_version::<Num<1>>();
+        registry
+    }
+
+    #[test]
+    fn test_tags() {
+        let registry = registry();
+        assert_eq!(registry.tags().collect::<Vec<_>>(), ["cm", "v1"]);
+        assert_eq!(format!("{registry:?}"), r#"["cm", "v1"]"#);
+    }
+
+    #[test]
+    fn test_deserialize_dyn() {
+        let registry = registry();
+        let dyn_version = |tag, json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            registry.deserialize_dyn(tag, &mut d)
+        };
+        assert_eq!(dyn_version("cm", "250").unwrap(), Meters(2.5));
+        assert_eq!(dyn_version("1", "10.0").unwrap(), Meters(3.048));
+        assert!(dyn_version("cm", "2.5").is_err());
+        let error = dyn_version("mm", "250").unwrap_err();
+        assert!(error.to_string().contains("unknown version `mm`"));
+    }
+
+    #[test]
+    fn test_deserialize_any_version() {
+        let registry = registry();
+        let any_version = |json| {
+            let mut d = serde_json::Deserializer::from_str(json);
+            registry.deserialize_any_version(&mut d)
+        };
+        assert_eq!(any_version("250").unwrap(), Meters(2.5));
+        assert_eq!(any_version("10.5").unwrap(), Meters(10.5 * 0.3048));
+
+        // Compiled versions are named by their marker, others by their tag.
+        let error = any_version(r#""ten""#).unwrap_err().to_string();
+        assert!(error.contains("cm: "), "{error}");
+        assert!(error.contains("Num<1>: "), "{error}");
+    }
+}
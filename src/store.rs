@@ -0,0 +1,293 @@
+//! Bulk migrations of key-value stores, upgrading every value under a prefix
+//! to the current version in batches.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::format::Format;
+use crate::{DeserializeVersioned, VersionErrors};
+
+/// A key-value store whose values can be upgraded with `migrate_store`.
+pub trait Store {
+    type Error;
+
+    /// The value of the key, if any.
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Sets the value of the key.
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), Self::Error>;
+
+    /// Up to `limit` entries whose keys start with `prefix`, in key order,
+    /// starting after the key `after` if given.
+    #[allow(clippy::type_complexity)]
+    fn scan(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error>;
+
+    /// Sets the values of a batch of keys, e.g. in one transaction.
+    /// Defaults to calling `put` for each key.
+    fn put_batch(&mut self, entries: &[(Vec<u8>, Vec<u8>)]) -> Result<(), Self::Error> {
+        for (key, value) in entries {
+            self.put(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// How far a `migrate_store` has come.
+#[derive(Debug, Default)]
+pub struct Progress {
+    scanned: u64,
+    upgraded: u64,
+    failed: Vec<(Vec<u8>, VersionErrors)>,
+}
+
+impl Progress {
+    /// The number of values read so far
+    pub fn scanned(&self) -> u64 {
+        self.scanned
+    }
+
+    /// The number of values rewritten as the current version so far
+    pub fn upgraded(&self) -> u64 {
+        self.upgraded
+    }
+
+    /// The keys of the values that matched no version, which are left as they were
+    pub fn failed(&self) -> impl Iterator<Item = (&[u8], &VersionErrors)> {
+        self.failed
+            .iter()
+            .map(|(key, errors)| (key.as_slice(), errors))
+    }
+}
+
+/// Upgrades every value under the prefix, encoded with `F`, to the current
+/// version of `T`, scanning and writing `batch_size` values at a time.
+/// Values already encoded as the current version aren't written again.
+///
+/// `on_progress` is called after each batch is written.
+/// Stops at the first error of the store, after the batches written so far.
+pub fn migrate_store<T, F, S>(
+    store: &mut S,
+    prefix: &[u8],
+    batch_size: usize,
+    mut on_progress: impl FnMut(&Progress),
+) -> Result<Progress, MigrateError<S::Error, F::Error>>
+where
+    T: DeserializeVersioned + Serialize,
+    F: Format,
+    S: Store + ?Sized,
+{
+    let batch_size = batch_size.max(1);
+    let mut progress = Progress::default();
+    let mut after = None;
+    loop {
+        let entries = store
+            .scan(prefix, after.as_deref(), batch_size)
+            .map_err(MigrateError::Store)?;
+        let mut upgrades = Vec::new();
+        for (key, value) in &entries {
+            progress.scanned += 1;
+            match read_repair::<T, F>(value) {
+                Ok((_, Some(upgraded))) => upgrades.push((key.clone(), upgraded)),
+                Ok((_, None)) => {}
+                Err(RepairError::Versions(errors)) => progress.failed.push((key.clone(), errors)),
+                Err(RepairError::Format(err)) => return Err(MigrateError::Format(err)),
+            }
+        }
+        store.put_batch(&upgrades).map_err(MigrateError::Store)?;
+        progress.upgraded += upgrades.len() as u64;
+        on_progress(&progress);
+
+        if entries.len() < batch_size {
+            return Ok(progress);
+        }
+        after = entries.into_iter().next_back().map(|(key, _)| key);
+    }
+}
+
+/// Reads the bytes encoded with `F` as any version of `T`, along with the
+/// value encoded as the current version if that differs from the bytes, so
+/// the caller can rewrite the record as it is read.
+#[allow(clippy::type_complexity)]
+pub fn read_repair<T, F>(bytes: &[u8]) -> Result<(T, Option<Vec<u8>>), RepairError<F::Error>>
+where
+    T: DeserializeVersioned + Serialize,
+    F: Format,
+{
+    let value = T::from_slice_any_version::<F>(bytes).map_err(RepairError::Versions)?;
+    let current = F::to_vec(&value).map_err(RepairError::Format)?;
+    let repaired = (current != bytes).then_some(current);
+    Ok((value, repaired))
+}
+
+/// The error of a `read_repair`.
+#[derive(Debug)]
+pub enum RepairError<F> {
+    /// The bytes matched no version
+    Versions(VersionErrors),
+    /// The current value couldn't be encoded
+    Format(F),
+}
+
+impl<F: fmt::Display> fmt::Display for RepairError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairError::Versions(e) => e.fmt(f),
+            RepairError::Format(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<F: fmt::Debug + fmt::Display> std::error::Error for RepairError<F> {}
+
+/// The error of a `migrate_store`.
+#[derive(Debug)]
+pub enum MigrateError<S, F> {
+    /// The store failed to scan or write values
+    Store(S),
+    /// A current value couldn't be encoded
+    Format(F),
+}
+
+impl<S: fmt::Display, F: fmt::Display> fmt::Display for MigrateError<S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::Store(e) => e.fmt(f),
+            MigrateError::Format(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<S: fmt::Debug + fmt::Display, F: fmt::Debug + fmt::Display> std::error::Error
+    for MigrateError<S, F>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::format::tests::Json;
+    use crate::{Current, FromVersion, Num, Ver, Versions};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Temperature {
+        celsius: f64,
+    }
+
+    impl FromVersion<Ver<Num<1>>> for Temperature {
+        type VersionType = f64;
+        type Error = Infallible;
+
+        fn convert(fahrenheit: f64) -> Result<Self, Self::Error> {
+            Ok(Temperature {
+                celsius: (fahrenheit - 32.0) / 1.8,
+            })
+        }
+    }
+
+    impl DeserializeVersioned for Temperature {
+        type Versions = Versions<Ver<Current>, Ver<Num<1>>>;
+
+        fn deserialize_any_version<'de, D: serde::Deserializer<'de>>(
+            d: D,
+        ) -> Result<Self, D::Error> {
+            Self::Versions::deserialize(d)
+        }
+
+        fn from_slice_any_version<F: Format>(bytes: &[u8]) -> Result<Self, VersionErrors> {
+            Self::Versions::from_slice::<F, Self>(bytes)
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStore {
+        values: BTreeMap<Vec<u8>, Vec<u8>>,
+        batches: usize,
+    }
+
+    impl Store for MemoryStore {
+        type Error = Infallible;
+
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+            Ok(self.values.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), Self::Error> {
+            self.values.insert(key.to_vec(), value.to_vec());
+            Ok(())
+        }
+
+        fn scan(
+            &self,
+            prefix: &[u8],
+            after: Option<&[u8]>,
+            limit: usize,
+        ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+            Ok(self
+                .values
+                .iter()
+                .filter(|(key, _)| {
+                    key.starts_with(prefix) && after.is_none_or(|after| key.as_slice() > after)
+                })
+                .take(limit)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect())
+        }
+
+        fn put_batch(&mut self, entries: &[(Vec<u8>, Vec<u8>)]) -> Result<(), Self::Error> {
+            self.batches += 1;
+            for (key, value) in entries {
+                self.put(key, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_repair() {
+        let (value, repaired) = read_repair::<Temperature, Json>(b"212.0").unwrap();
+        assert_eq!(value, Temperature { celsius: 100.0 });
+        assert_eq!(repaired.as_deref(), Some(&br#"{"celsius":100.0}"#[..]));
+
+        let (_, repaired) = read_repair::<Temperature, Json>(br#"{"celsius":100.0}"#).unwrap();
+        assert_eq!(repaired, None);
+
+        let error = read_repair::<Temperature, Json>(br#""hot""#).unwrap_err();
+        assert!(matches!(error, RepairError::Versions(errors) if errors.len() == 2));
+    }
+
+    #[test]
+    fn test_migrate_store() {
+        let mut store = MemoryStore::default();
+        store.put(b"t/1", b"32.0").unwrap();
+        store.put(b"t/2", br#"{"celsius":10.0}"#).unwrap();
+        store.put(b"t/3", br#""hot""#).unwrap();
+        store.put(b"t/4", b"212.0").unwrap();
+        store.put(b"u/1", b"32.0").unwrap();
+
+        let mut reported = Vec::new();
+        let progress = migrate_store::<Temperature, Json, _>(&mut store, b"t/", 2, |progress| {
+            reported.push((progress.scanned(), progress.upgraded()));
+        })
+        .unwrap();
+
+        assert_eq!(reported, [(2, 1), (4, 2), (4, 2)]);
+        assert_eq!(store.batches, 3);
+        assert_eq!(
+            progress.failed().map(|(key, _)| key).collect::<Vec<_>>(),
+            [b"t/3"]
+        );
+        assert_eq!(store.get(b"t/1").unwrap().unwrap(), br#"{"celsius":0.0}"#);
+        assert_eq!(store.get(b"t/4").unwrap().unwrap(), br#"{"celsius":100.0}"#);
+        assert_eq!(store.get(b"t/3").unwrap().unwrap(), br#""hot""#);
+        assert_eq!(store.get(b"u/1").unwrap().unwrap(), b"32.0");
+    }
+}
@@ -0,0 +1,2038 @@
+#![cfg(feature = "derive")]
+
+use serde::Deserialize;
+use serde_versioned::{Num, Uuid, Versioned};
+
+#[derive(Deserialize)]
+struct PointV1 {
+    coords: (i64, i64),
+}
+
+#[derive(Deserialize)]
+struct PointV2 {
+    x: String,
+    y: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = PointV1, Uuid<2> = PointV2), any_version = AnyPoint)]
+#[cfg_attr(
+    feature = "json",
+    versioned(fixtures(
+        Num<1> = "tests/fixtures/point_v1.json",
+        Uuid<2> = "tests/fixtures/point_v2.json",
+    ))
+)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+impl From<PointV1> for Point {
+    fn from(v: PointV1) -> Self {
+        Point {
+            x: v.coords.0,
+            y: v.coords.1,
+        }
+    }
+}
+
+impl TryFrom<PointV2> for Point {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(v: PointV2) -> Result<Self, Self::Error> {
+        Ok(Point {
+            x: v.x.parse()?,
+            y: v.y.parse()?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Shape {
+    #[serde(deserialize_with = "serde_versioned::deserialize")]
+    origin: Point,
+}
+
+#[test]
+fn test_derive_versions() {
+    let current: Shape = serde_json::from_str(r#"{"origin": {"x": 1, "y": 2}}"#).unwrap();
+    let v1: Shape = serde_json::from_str(r#"{"origin": {"coords": [1, 2]}}"#).unwrap();
+    let v2: Shape = serde_json::from_str(r#"{"origin": {"x": "1", "y": "2"}}"#).unwrap();
+
+    assert_eq!(current.origin, Point { x: 1, y: 2 });
+    assert_eq!(v1, current);
+    assert_eq!(v2, current);
+
+    assert!(serde_json::from_str::<Shape>(r#"{"origin": {"x": "a", "y": "2"}}"#).is_err());
+}
+
+#[test]
+fn test_any_version() {
+    let parse = |json| serde_json::from_str::<serde_versioned::AnyVersion<Point>>(json).unwrap();
+
+    assert!(matches!(
+        parse(r#"{"x": 1, "y": 2}"#),
+        AnyPoint::Current(Point { x: 1, y: 2 })
+    ));
+    assert!(matches!(
+        parse(r#"{"coords": [1, 2]}"#),
+        AnyPoint::Num1(PointV1 { coords: (1, 2) })
+    ));
+
+    let v2 = parse(r#"{"x": "1", "y": "a"}"#);
+    assert!(matches!(v2, AnyPoint::Uuid2(_)));
+    assert!(v2.convert().is_err());
+    assert_eq!(
+        parse(r#"{"x": "1", "y": "2"}"#).convert().unwrap(),
+        Point { x: 1, y: 2 }
+    );
+}
+
+#[test]
+fn test_version_policy() {
+    use serde_versioned::{DeserializeVersioned, VersionPolicy};
+
+    let parse = |json, policy: &VersionPolicy| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        Point::deserialize_with_policy(&mut d, policy)
+    };
+    let v1 = r#"{"coords": [1, 2]}"#;
+    let v2 = r#"{"x": "1", "y": "2"}"#;
+
+    let policy = VersionPolicy::parse("!Num<1>");
+    assert_eq!(parse(v2, &policy).unwrap(), Point { x: 1, y: 2 });
+    let error = parse(v1, &policy).unwrap_err();
+    assert!(error.to_string().contains("the policy denies Num<1>"));
+    assert!(parse(r#"{"x": "a"}"#, &policy)
+        .unwrap_err()
+        .to_string()
+        .contains("did not match any version"));
+
+    let policy = VersionPolicy::parse("Current, Num<1>");
+    assert!(parse(v1, &policy).is_ok());
+    assert!(parse(v2, &policy).is_err());
+    assert!(parse(v2, &VersionPolicy::from_env("SERDE_VERSIONED_TEST_UNSET")).is_ok());
+}
+
+#[test]
+fn test_sunset() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    use serde_versioned::{DeserializeVersioned, VersionPolicy};
+
+    static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+    let parse = |json, policy: &VersionPolicy| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        Point::deserialize_with_policy(&mut d, policy)
+    };
+    let v1 = r#"{"coords": [1, 2]}"#;
+    let policy = VersionPolicy::new().sunset("Num<1>", SystemTime::UNIX_EPOCH);
+    assert!(parse(v1, &policy)
+        .unwrap_err()
+        .to_string()
+        .contains("the policy denies Num<1>"));
+
+    let policy = policy.warn_after_sunset(|_| {
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+    });
+    assert_eq!(parse(v1, &policy).unwrap(), Point { x: 1, y: 2 });
+    assert!(parse(r#"{"x": 1, "y": 2}"#, &policy).is_ok());
+    assert_eq!(WARNINGS.load(Ordering::Relaxed), 1);
+}
+
+#[derive(Deserialize)]
+struct GaugeV1(String);
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(current = Num<4302>, from(Num<4301> = GaugeV1))]
+struct Gauge(u64);
+
+impl TryFrom<GaugeV1> for Gauge {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(v: GaugeV1) -> Result<Self, Self::Error> {
+        Ok(Gauge(v.0.parse()?))
+    }
+}
+
+#[test]
+fn test_installed_policy() {
+    use serde_versioned::{
+        set_policy, BinaryEnvelope, DeserializeVersioned, Envelope, MigrationStats, Registry,
+        VersionPolicy,
+    };
+
+    static DENY: std::sync::LazyLock<VersionPolicy> =
+        std::sync::LazyLock::new(|| VersionPolicy::new().deny("Num<4301>"));
+    let json = || serde_json::Deserializer::from_str(r#""1""#);
+    let denied = |err: &dyn std::fmt::Display| err.to_string().contains("denies");
+
+    assert_eq!(
+        Gauge::deserialize_any_version(&mut json()).unwrap(),
+        Gauge(1)
+    );
+    set_policy(&DENY);
+
+    assert!(denied(
+        &Gauge::deserialize_any_version(&mut json()).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::deserialize_with_version(&mut json()).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::deserialize_cloned(serde_json::json!("1")).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::from_slice_any_version::<Json>(br#""1""#).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::deserialize_observed(&mut json(), &MigrationStats::new()).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::deserialize_version("v4301", &mut json()).unwrap_err()
+    ));
+    assert!(denied(
+        &Gauge::deserialize_version_index(0, &mut json()).unwrap_err()
+    ));
+    assert!(denied(
+        &serde_json::from_str::<Envelope<Gauge>>(r#"{"version": "v4301", "data": "1"}"#)
+            .unwrap_err()
+    ));
+    assert!(denied(
+        &serde_json::from_str::<BinaryEnvelope<Gauge>>(r#"[0, "1"]"#).unwrap_err()
+    ));
+
+    let mut registry = Registry::<Gauge>::new();
+    registry.register_version::<Num<4301>>();
+    assert!(denied(
+        &registry.deserialize_any_version(&mut json()).unwrap_err()
+    ));
+    assert!(denied(
+        &registry.deserialize_dyn("v4301", &mut json()).unwrap_err()
+    ));
+
+    let mut d = serde_json::Deserializer::from_str("2");
+    assert_eq!(Gauge::deserialize_any_version(&mut d).unwrap(), Gauge(2));
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Meters(u32);
+
+impl serde_versioned::FromVersion<serde_versioned::Ver<Num<1>>> for Meters {
+    type VersionType = String;
+    type Error = std::num::ParseIntError;
+
+    fn convert(v: Self::VersionType) -> Result<Self, Self::Error> {
+        Ok(Meters(v.trim_end_matches("km").parse::<u32>()? * 1000))
+    }
+}
+
+#[serde_versioned::versioned]
+#[derive(Debug, PartialEq, Deserialize)]
+struct Route {
+    #[versioned(from(Num<1> = PointV1, Uuid<2> = PointV2))]
+    start: Point,
+    #[versioned(from(Num<1> = String), rename_from(Num<1> = "len"))]
+    length: Meters,
+    #[versioned(rename_from(Num<1> = "stops", Uuid<2> = "stops"))]
+    #[serde(default)]
+    waypoints: u32,
+}
+
+#[test]
+fn test_field_attribute() {
+    let current: Route =
+        serde_json::from_str(r#"{"start": {"x": 1, "y": 2}, "length": 3000}"#).unwrap();
+    let legacy: Route =
+        serde_json::from_str(r#"{"start": {"coords": [1, 2]}, "len": "3km", "stops": 4}"#).unwrap();
+
+    assert_eq!(
+        current,
+        Route {
+            start: Point { x: 1, y: 2 },
+            length: Meters(3000),
+            waypoints: 0,
+        }
+    );
+    assert_eq!(
+        legacy,
+        Route {
+            waypoints: 4,
+            ..current
+        }
+    );
+}
+
+#[cfg(any())]
+#[derive(Deserialize)]
+struct RateV2 {
+    per_mille: u32,
+}
+
+#[derive(Deserialize)]
+struct RateV1 {
+    percent: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(#[cfg(any())] Num<2> = RateV2, #[cfg(all())] Num<1> = RateV1))]
+struct Rate {
+    basis_points: u32,
+}
+
+#[cfg(any())]
+impl From<RateV2> for Rate {
+    fn from(v: RateV2) -> Self {
+        Rate {
+            basis_points: v.per_mille * 10,
+        }
+    }
+}
+
+impl From<RateV1> for Rate {
+    fn from(v: RateV1) -> Self {
+        Rate {
+            basis_points: v.percent * 100,
+        }
+    }
+}
+
+#[test]
+fn test_gated_versions() {
+    let parse = |json| {
+        serde_versioned::deserialize::<Rate, _>(&mut serde_json::Deserializer::from_str(json))
+    };
+    assert_eq!(
+        parse(r#"{"percent": 2}"#).unwrap(),
+        Rate { basis_points: 200 }
+    );
+    assert!(parse(r#"{"per_mille": 2}"#).is_err());
+}
+
+#[serde_versioned::versioned]
+#[derive(Deserialize)]
+struct SegmentV1 {
+    #[versioned(nested)]
+    from: Point,
+    #[versioned(nested)]
+    to: Point,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = SegmentV1))]
+struct Segment {
+    points: [Point; 2],
+}
+
+impl From<SegmentV1> for Segment {
+    fn from(v: SegmentV1) -> Self {
+        Segment {
+            points: [v.from, v.to],
+        }
+    }
+}
+
+#[test]
+fn test_nested() {
+    let json = r#"{"from": {"coords": [1, 2]}, "to": {"x": "3", "y": "4"}}"#;
+    let segment: Segment =
+        serde_versioned::deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+    assert_eq!(segment.points, [Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]);
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = String), fallback)]
+struct Priority(u8);
+
+impl TryFrom<String> for Priority {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(v: String) -> Result<Self, Self::Error> {
+        v.parse().map(Priority)
+    }
+}
+
+struct Unassigned;
+
+impl serde_versioned::FallbackValue<String> for Unassigned {
+    fn fallback() -> String {
+        String::from("nobody")
+    }
+}
+
+#[serde_versioned::versioned]
+#[derive(Debug, PartialEq, Deserialize)]
+struct Ticket {
+    #[serde(deserialize_with = "serde_versioned::deserialize")]
+    priority: Priority,
+    #[versioned(fallback = Unassigned)]
+    assignee: String,
+}
+
+#[test]
+fn test_fallback() {
+    let ticket: Ticket = serde_json::from_str(r#"{"priority": "2", "assignee": "ada"}"#).unwrap();
+    assert_eq!(ticket.priority, Priority(2));
+    assert_eq!(ticket.assignee, "ada");
+
+    let ticket: Ticket =
+        serde_json::from_str(r#"{"priority": "high", "assignee": {"id": 7}}"#).unwrap();
+    assert_eq!(ticket.priority, Priority(0));
+    assert_eq!(ticket.assignee, "nobody");
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = String), validate)]
+struct Username {
+    name: String,
+}
+
+impl From<String> for Username {
+    fn from(v: String) -> Self {
+        Username {
+            name: v.trim().to_string(),
+        }
+    }
+}
+
+impl serde_versioned::Validate for Username {
+    type Error = &'static str;
+
+    fn validate(&self) -> Result<(), Self::Error> {
+        match self.name.is_empty() {
+            true => Err("empty username"),
+            false => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_validate() {
+    let parse = |json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        serde_versioned::deserialize::<Username, _>(&mut d)
+    };
+
+    let ada = Username { name: "ada".into() };
+    assert_eq!(parse(r#"{"name": "ada"}"#).unwrap(), ada);
+    assert_eq!(parse(r#"" ada ""#).unwrap(), ada);
+    assert!(parse(r#"{"name": ""}"#).is_err());
+    assert!(parse(r#""  ""#).is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(from(Num<1> = String), validate, current = Num<2>)]
+struct Handle {
+    name: String,
+}
+
+impl From<String> for Handle {
+    fn from(name: String) -> Self {
+        Handle { name }
+    }
+}
+
+impl serde_versioned::Validate for Handle {
+    type Error = &'static str;
+
+    fn validate(&self) -> Result<(), Self::Error> {
+        match self.name.is_empty() {
+            true => Err("empty handle"),
+            false => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_validate_tagged() {
+    use serde_versioned::{BinaryEnvelope, Envelope, VersionStamped};
+
+    let envelope = |json| serde_json::from_str::<Envelope<Handle>>(json).map(Envelope::into_inner);
+    let ada = Handle { name: "ada".into() };
+    assert_eq!(
+        envelope(r#"{"version": "v2", "data": {"name": "ada"}}"#).unwrap(),
+        ada
+    );
+    assert_eq!(
+        envelope(r#"{"version": "v1", "data": "ada"}"#).unwrap(),
+        ada
+    );
+    let err = envelope(r#"{"version": "v2", "data": {"name": ""}}"#).unwrap_err();
+    assert!(err.to_string().contains("empty handle"), "{err}");
+    assert!(envelope(r#"{"version": "v1", "data": ""}"#).is_err());
+
+    let stamped = r#"{"version": "v2", "data": {"name": ""}}"#;
+    assert!(serde_json::from_str::<VersionStamped<Handle>>(stamped).is_err());
+    assert!(serde_json::from_str::<BinaryEnvelope<Handle>>(r#"[2, {"name": ""}]"#).is_err());
+    assert!(serde_json::from_str::<BinaryEnvelope<Handle>>(r#"[1, ""]"#).is_err());
+}
+
+/// Old limits were stored as strings in KiB, or MiB for memory.
+struct Kib;
+struct Mib;
+
+impl serde_versioned::FromVersion<serde_versioned::Ver<Num<1>>, Kib> for u64 {
+    type VersionType = String;
+    type Error = std::num::ParseIntError;
+
+    fn convert(v: String) -> Result<Self, Self::Error> {
+        Ok(v.parse::<u64>()? * 1024)
+    }
+}
+
+impl serde_versioned::FromVersion<serde_versioned::Ver<Num<1>>, Mib> for u64 {
+    type VersionType = String;
+    type Error = std::num::ParseIntError;
+
+    fn convert(v: String) -> Result<Self, Self::Error> {
+        Ok(v.parse::<u64>()? * 1024 * 1024)
+    }
+}
+
+#[serde_versioned::versioned]
+#[derive(Debug, PartialEq, Deserialize)]
+struct Limits {
+    #[versioned(from(Num<1> = String), label = Kib)]
+    disk: u64,
+    #[versioned(from(Num<1> = String), label = Mib)]
+    memory: u64,
+}
+
+#[test]
+fn test_labeled_versions() {
+    let current: Limits = serde_json::from_str(r#"{"disk": 2048, "memory": 1048576}"#).unwrap();
+    let legacy: Limits = serde_json::from_str(r#"{"disk": "2", "memory": "1"}"#).unwrap();
+    assert_eq!(
+        current,
+        Limits {
+            disk: 2048,
+            memory: 1048576,
+        }
+    );
+    assert_eq!(legacy, current);
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(generate(Num<2> = ServerV2, Num<1> = ServerV1))]
+#[serde(rename_all = "camelCase")]
+struct Server {
+    #[versioned(renamed(Num<1> = "host"))]
+    host_name: String,
+    #[versioned(changed(Num<2> = u16, Num<1> = u16))]
+    port: u32,
+    #[versioned(added(Num<1>))]
+    #[serde(default)]
+    max_connections: u64,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Deployment {
+    #[serde(deserialize_with = "serde_versioned::deserialize")]
+    server: Server,
+}
+
+#[test]
+fn test_generated_legacy_structs() {
+    let current: Deployment =
+        serde_json::from_str(r#"{"server": {"hostName": "a", "port": 80, "maxConnections": 10}}"#)
+            .unwrap();
+    let v1: Deployment = serde_json::from_str(r#"{"server": {"host": "a", "port": 80}}"#).unwrap();
+
+    assert_eq!(
+        current.server,
+        Server {
+            host_name: String::from("a"),
+            port: 80,
+            max_connections: 10,
+        }
+    );
+    assert_eq!(
+        v1.server,
+        Server {
+            max_connections: 0,
+            ..current.server
+        }
+    );
+
+    let v2: ServerV2 = serde_json::from_str(r#"{"hostName": "a", "port": 80}"#).unwrap();
+    assert_eq!(v2.port, 80u16);
+    assert!(serde_json::from_str::<ServerV1>(r#"{"host": "a", "port": 70000}"#).is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(generate(Num<1> = SoundingV1))]
+struct Sounding {
+    #[versioned(changed(Num<1> = i16))]
+    #[serde(rename = "temp", default)]
+    temperature: i32,
+    #[versioned(renamed(Num<1> = "lvl"))]
+    #[versioned(changed(Num<1> = u8))]
+    #[serde(alias = "depth")]
+    level: u32,
+}
+
+#[test]
+fn test_legacy_keeps_serde_attributes() {
+    let v1: SoundingV1 = serde_json::from_str(r#"{"temp": -4, "lvl": 3}"#).unwrap();
+    assert_eq!((v1.temperature, v1.level), (-4i16, 3u8));
+    let v1: SoundingV1 = serde_json::from_str(r#"{"depth": 3}"#).unwrap();
+    assert_eq!((v1.temperature, v1.level), (0, 3));
+    let v1: SoundingV1 = serde_json::from_str(r#"{"temperature": -4, "lvl": 3}"#).unwrap();
+    assert_eq!(v1.temperature, 0);
+
+    let sounding: Sounding = serde_versioned::deserialize(&mut serde_json::Deserializer::from_str(
+        r#"{"temp": -4, "lvl": 3}"#,
+    ))
+    .unwrap();
+    assert_eq!(
+        sounding,
+        Sounding {
+            temperature: -4,
+            level: 3,
+        }
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(
+    generate(Num<1> = ContactV1),
+    dropped(Num<1> = full_name: String => full_name_of),
+    downgrade(Num<1>)
+)]
+struct Contact {
+    #[versioned(added(Num<1>))]
+    #[serde(default)]
+    first: String,
+    #[versioned(added(Num<1>))]
+    #[serde(default)]
+    last: String,
+    #[versioned(changed(Num<1> = u8))]
+    age: u32,
+}
+
+fn full_name_of(contact: &Contact) -> String {
+    format!("{} {}", contact.first, contact.last)
+}
+
+#[test]
+fn test_downgrade() {
+    use serde_versioned::{serialize_as, IntoVersion, Ver};
+
+    let ada = Contact {
+        first: "Ada".into(),
+        last: "Lovelace".into(),
+        age: 36,
+    };
+    let mut json = Vec::new();
+    serialize_as::<Ver<Num<1>>, _, _>(&ada, &mut serde_json::Serializer::new(&mut json)).unwrap();
+    assert_eq!(json, br#"{"age":36,"full_name":"Ada Lovelace"}"#);
+
+    let old = IntoVersion::<Ver<Num<1>>>::convert(&Contact { age: 300, ..ada });
+    assert!(old.is_err_and(|err| err.to_string().contains("out of range")));
+
+    let v1: Contact = serde_versioned::deserialize(&mut serde_json::Deserializer::from_str(
+        r#"{"age": 36, "full_name": "Ada Lovelace"}"#,
+    ))
+    .unwrap();
+    assert_eq!(v1.age, 36);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_assert_versions() {
+    use serde_versioned::{assert_versions, Current, Ver};
+
+    assert_versions!(Point {
+        Ver<Num<1>> => r#"{"coords": [1, 2]}"#,
+        Ver<Uuid<2>> => r#"{"x": "1", "y": "2"}"# == Point { x: 1, y: 2 },
+    });
+    assert_versions!(Contact {
+        Ver<Current> => r#"{"first": "Ada", "last": "Lovelace", "age": 36}"#; roundtrip,
+        Ver<Num<1>> => r#"{"age": 36, "full_name": "Ada Lovelace"}"#; roundtrip,
+    });
+}
+
+#[cfg(feature = "json")]
+#[test]
+#[should_panic(expected = "sample of Ver<Uuid<2>> failed to convert")]
+fn test_assert_versions_fails() {
+    use serde_versioned::{assert_versions, Ver};
+
+    assert_versions!(Point {
+        Ver<Uuid<2>> => r#"{"x": "a", "y": "2"}"#,
+    });
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(generate(Num<1> = FigureV1))]
+#[versioned(removed(Num<1> = Polygon { sides: u32, length: u32 } => polygon_from_v1))]
+enum Figure {
+    #[versioned(renamed(Num<1> = "Dot"))]
+    Point,
+    #[versioned(changed(Num<1> = u16))]
+    Circle(u32),
+    Rect {
+        #[versioned(renamed(Num<1> = "w"))]
+        width: u32,
+        height: u32,
+    },
+    #[versioned(added(Num<1>))]
+    Triangle(u32),
+    Square(u32),
+}
+
+fn polygon_from_v1(sides: u32, length: u32) -> Result<Figure, String> {
+    match sides {
+        3 => Ok(Figure::Triangle(length)),
+        4 => Ok(Figure::Square(length)),
+        _ => Err(format!("unsupported polygon with {sides} sides")),
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Drawing {
+    #[serde(deserialize_with = "serde_versioned::deserialize")]
+    figure: Figure,
+}
+
+#[test]
+fn test_generated_legacy_enums() {
+    let parse = |json: &str| {
+        serde_json::from_str::<Drawing>(&format!(r#"{{"figure": {json}}}"#)).map(|d| d.figure)
+    };
+
+    assert_eq!(parse(r#""Point""#).unwrap(), Figure::Point);
+    assert_eq!(parse(r#""Dot""#).unwrap(), Figure::Point);
+    assert_eq!(
+        parse(r#"{"Circle": 70000}"#).unwrap(),
+        Figure::Circle(70000)
+    );
+    assert_eq!(
+        parse(r#"{"Rect": {"w": 1, "height": 2}}"#).unwrap(),
+        Figure::Rect {
+            width: 1,
+            height: 2,
+        }
+    );
+    assert_eq!(
+        parse(r#"{"Polygon": {"sides": 3, "length": 5}}"#).unwrap(),
+        Figure::Triangle(5)
+    );
+    assert_eq!(
+        parse(r#"{"Polygon": {"sides": 4, "length": 5}}"#).unwrap(),
+        Figure::Square(5)
+    );
+    assert!(parse(r#"{"Polygon": {"sides": 5, "length": 5}}"#).is_err());
+
+    let v1: FigureV1 = serde_json::from_str(r#"{"Circle": 10}"#).unwrap();
+    assert!(matches!(v1, FigureV1::Circle(10u16)));
+    assert!(serde_json::from_str::<FigureV1>(r#"{"Triangle": 1}"#).is_err());
+}
+
+#[derive(Deserialize, Versioned)]
+#[serde(transparent)]
+#[versioned(remote = "std::time::Duration", from(Num<1> = u64))]
+struct DurationDef(std::time::Duration);
+
+impl From<u64> for DurationDef {
+    fn from(millis: u64) -> Self {
+        DurationDef(std::time::Duration::from_millis(millis))
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Timeout {
+    #[serde(deserialize_with = "DurationDef::deserialize")]
+    after: std::time::Duration,
+}
+
+#[test]
+fn test_remote() {
+    let current: Timeout =
+        serde_json::from_str(r#"{"after": {"secs": 1, "nanos": 500000000}}"#).unwrap();
+    let v1: Timeout = serde_json::from_str(r#"{"after": 1500}"#).unwrap();
+
+    assert_eq!(current.after, std::time::Duration::from_millis(1500));
+    assert_eq!(v1, current);
+}
+
+#[derive(Deserialize)]
+struct ColorV1(String);
+
+#[derive(Deserialize)]
+struct ColorV2 {
+    hex: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(chain(ColorV2, ColorV1), any_version = AnyColor)]
+struct Color {
+    rgb: u32,
+}
+
+impl serde_versioned::Upgrade<ColorV1> for ColorV2 {
+    type Error = std::convert::Infallible;
+
+    fn upgrade(prev: ColorV1) -> Result<Self, Self::Error> {
+        Ok(ColorV2 {
+            hex: prev.0.trim_start_matches('#').to_string(),
+        })
+    }
+}
+
+impl serde_versioned::Upgrade<ColorV2> for Color {
+    type Error = std::num::ParseIntError;
+
+    fn upgrade(prev: ColorV2) -> Result<Self, Self::Error> {
+        Ok(Color {
+            rgb: u32::from_str_radix(&prev.hex, 16)?,
+        })
+    }
+}
+
+#[test]
+fn test_chain() {
+    let parse = |json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        serde_versioned::deserialize::<Color, _>(&mut d)
+    };
+
+    assert_eq!(parse(r#"{"rgb": 255}"#).unwrap(), Color { rgb: 255 });
+    assert_eq!(parse(r#"{"hex": "ff"}"#).unwrap(), Color { rgb: 255 });
+    assert_eq!(parse(r##""#ff""##).unwrap(), Color { rgb: 255 });
+    assert!(parse(r#""zz""#).is_err());
+
+    let v1 = serde_json::from_str::<serde_versioned::AnyVersion<Color>>(r#""ff""#).unwrap();
+    assert!(matches!(v1, AnyColor::ColorV1(_)));
+}
+
+#[derive(Deserialize)]
+struct TemperatureV2(f64);
+
+#[derive(Deserialize)]
+struct TemperatureV1(f64);
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(
+    current = Num<3>,
+    from(Num<2> = TemperatureV2, Num<1> = TemperatureV1),
+    schema_ids(Num<3> = 101, Num<2> = 57)
+)]
+struct Temperature {
+    celsius: f64,
+}
+
+impl From<TemperatureV2> for Temperature {
+    fn from(v: TemperatureV2) -> Self {
+        Temperature { celsius: v.0 }
+    }
+}
+
+impl From<TemperatureV1> for Temperature {
+    fn from(v: TemperatureV1) -> Self {
+        Temperature {
+            celsius: (v.0 - 32.0) / 1.8,
+        }
+    }
+}
+
+#[test]
+fn test_envelope() {
+    use serde_versioned::Envelope;
+
+    let parse = |json| {
+        serde_json::from_str::<Envelope<Temperature>>(json)
+            .unwrap()
+            .into_inner()
+    };
+
+    let boiling = Temperature { celsius: 100.0 };
+    assert_eq!(
+        parse(r#"{"version": "v3", "data": {"celsius": 100.0}}"#),
+        boiling
+    );
+    assert_eq!(parse(r#"{"data": 100.0, "version": "v2"}"#), boiling);
+    assert_eq!(parse(r#"{"version": 1, "data": 212.0}"#), boiling);
+
+    let error = serde_json::from_str::<Envelope<Temperature>>(r#"{"version": 4, "data": 1.0}"#);
+    assert!(error
+        .unwrap_err()
+        .to_string()
+        .contains("unknown version `4`"));
+
+    let json = serde_json::to_string(&Envelope(boiling)).unwrap();
+    assert_eq!(json, r#"{"version":"v3","data":{"celsius":100.0}}"#);
+}
+
+#[test]
+fn test_version_stamped() {
+    use serde_versioned::VersionStamped;
+
+    let parse = |json| serde_json::from_str::<VersionStamped<Temperature>>(json).unwrap();
+    let boiling = Temperature { celsius: 100.0 };
+    assert_eq!(parse(r#"{"version": "v2", "data": 100.0}"#).0, boiling);
+    assert_eq!(parse(r#"{"celsius": 100.0}"#).0, boiling);
+    assert_eq!(parse("100.0").celsius, 100.0);
+
+    let error = serde_json::from_str::<VersionStamped<Temperature>>(r#"{"version": 4}"#);
+    assert!(error.is_err());
+
+    let json = serde_json::to_string(&VersionStamped(boiling)).unwrap();
+    assert_eq!(json, r#"{"version":"v3","data":{"celsius":100.0}}"#);
+}
+
+impl serde_versioned::IntoVersion<serde_versioned::Ver<Num<2>>> for Temperature {
+    type VersionType = f64;
+    type Error = std::convert::Infallible;
+
+    fn convert(&self) -> Result<f64, Self::Error> {
+        Ok(self.celsius)
+    }
+}
+
+#[test]
+fn test_dual_write() {
+    use serde_versioned::DualWrite;
+
+    type Dual = DualWrite<Temperature, Num<2>>;
+
+    let boiling = Temperature { celsius: 100.0 };
+    let json = serde_json::to_string(&Dual::new(boiling)).unwrap();
+    assert_eq!(json, r#"{"v3":{"celsius":100.0},"v2":100.0}"#);
+
+    let parse = |json| serde_json::from_str::<Dual>(json).unwrap().into_inner();
+    let boiling = Temperature { celsius: 100.0 };
+    assert_eq!(parse(&json), boiling);
+    assert_eq!(parse(r#"{"v2": 100.0, "v3": {"celsius": 100.0}}"#), boiling);
+    assert_eq!(parse(r#"{"v4": [], "v2": 100.0}"#), boiling);
+
+    let error = serde_json::from_str::<Dual>(r#"{"v4": []}"#).unwrap_err();
+    assert!(error.to_string().contains("unknown version `v4`"));
+    assert!(serde_json::from_str::<Dual>("{}").is_err());
+}
+
+#[test]
+fn test_canonical() {
+    use serde_versioned::{Canonical, IntoVersion, Ver};
+    use std::collections::HashMap;
+
+    #[derive(serde::Serialize)]
+    struct Signed {
+        zone: &'static str,
+        offset: f64,
+        labels: HashMap<&'static str, u32>,
+    }
+
+    let signed = Signed {
+        zone: "eu",
+        offset: -0.0,
+        labels: HashMap::from([("b", 2), ("c", 3), ("a", 1)]),
+    };
+    assert_eq!(
+        serde_json::to_string(&Canonical(&signed)).unwrap(),
+        r#"{"labels":{"a":1,"b":2,"c":3},"offset":0.0,"zone":"eu"}"#
+    );
+
+    let boiling = Temperature { celsius: 100.0 };
+    let old = IntoVersion::<Ver<Num<2>>>::convert(&boiling).unwrap();
+    assert_eq!(serde_json::to_string(&Canonical(&old)).unwrap(), "100.0");
+
+    let error = serde_json::to_string(&Canonical(&f64::NAN)).unwrap_err();
+    assert!(error.to_string().contains("no canonical representation"));
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(current = Num<2>, from(Num<1> = u64))]
+struct Counter {
+    total: u128,
+}
+
+impl From<u64> for Counter {
+    fn from(v: u64) -> Self {
+        Counter { total: v.into() }
+    }
+}
+
+#[test]
+fn test_envelope_streaming() {
+    use serde_versioned::Envelope;
+
+    let parse = |json| serde_json::from_str::<Envelope<Counter>>(json).map(Envelope::into_inner);
+
+    // Data after the tag is read straight from the input, which handles `u128`.
+    let counter = parse(r#"{"version": "v2", "data": {"total": 1180591620717411303424}}"#);
+    assert_eq!(counter.unwrap().total, 1 << 70);
+    assert_eq!(parse(r#"{"version": "v1", "data": 7}"#).unwrap().total, 7);
+
+    // Data before the tag is buffered first.
+    assert!(parse(r#"{"data": {"total": 1180591620717411303424}, "version": "v2"}"#).is_err());
+    assert_eq!(parse(r#"{"data": 7, "version": "v1"}"#).unwrap().total, 7);
+}
+
+#[test]
+fn test_binary_envelope() {
+    use serde_versioned::BinaryEnvelope;
+
+    let parse = |json| {
+        serde_json::from_str::<BinaryEnvelope<Temperature>>(json)
+            .unwrap()
+            .into_inner()
+    };
+
+    let boiling = Temperature { celsius: 100.0 };
+    assert_eq!(parse(r#"[2, {"celsius": 100.0}]"#), boiling);
+    assert_eq!(parse("[1, 100.0]"), boiling);
+    assert_eq!(parse("[0, 212.0]"), boiling);
+    assert!(serde_json::from_str::<BinaryEnvelope<Temperature>>("[3, 1.0]").is_err());
+
+    let json = serde_json::to_string(&BinaryEnvelope(boiling)).unwrap();
+    assert_eq!(json, r#"[2,{"celsius":100.0}]"#);
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct BookV1 {
+    name: String,
+}
+
+/// Fingerprints all have the same ordinal.
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(
+    current = serde_versioned::Fingerprint<0xb2>,
+    from(serde_versioned::Fingerprint<0xb1> = BookV1)
+)]
+struct Book {
+    title: String,
+}
+
+impl From<BookV1> for Book {
+    fn from(v1: BookV1) -> Self {
+        Book { title: v1.name }
+    }
+}
+
+/// Versions of different kinds, with the same ordinal.
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(current = serde_versioned::Epoch<1>, from(Num<1> = BookV1))]
+struct Pamphlet {
+    title: String,
+}
+
+impl From<BookV1> for Pamphlet {
+    fn from(v1: BookV1) -> Self {
+        Pamphlet { title: v1.name }
+    }
+}
+
+#[test]
+fn test_binary_envelope_indices() {
+    use serde_versioned::BinaryEnvelope;
+
+    let book = Book { title: "x".into() };
+    let parse =
+        |json| serde_json::from_str::<BinaryEnvelope<Book>>(json).map(BinaryEnvelope::into_inner);
+    assert_eq!(parse(r#"[0, {"name": "x"}]"#).unwrap(), book);
+    assert_eq!(parse(r#"[1, {"title": "x"}]"#).unwrap(), book);
+    assert!(parse(r#"[1, {"name": "x"}]"#).is_err());
+    let json = serde_json::to_string(&BinaryEnvelope(book)).unwrap();
+    assert_eq!(json, r#"[1,{"title":"x"}]"#);
+
+    let pamphlet = Pamphlet { title: "x".into() };
+    let parse = |json| {
+        serde_json::from_str::<BinaryEnvelope<Pamphlet>>(json).map(BinaryEnvelope::into_inner)
+    };
+    assert_eq!(parse(r#"[0, {"name": "x"}]"#).unwrap(), pamphlet);
+    assert_eq!(parse(r#"[1, {"title": "x"}]"#).unwrap(), pamphlet);
+    let json = serde_json::to_string(&BinaryEnvelope(pamphlet)).unwrap();
+    assert_eq!(json, r#"[1,{"title":"x"}]"#);
+}
+
+/// Nests payloads as JSON bytes.
+struct Json;
+
+impl serde_versioned::Format for Json {
+    type Error = serde_json::Error;
+
+    fn to_vec<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn from_slice_seed<'de, S: serde::de::DeserializeSeed<'de>>(
+        bytes: &'de [u8],
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        seed.deserialize(&mut serde_json::Deserializer::from_slice(bytes))
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore(std::collections::BTreeMap<Vec<u8>, Vec<u8>>);
+
+impl serde_versioned::Store for MemoryStore {
+    type Error = std::convert::Infallible;
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.0.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), Self::Error> {
+        self.0.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn scan(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+        let entries = self.0.iter().filter(|(key, _)| key.starts_with(prefix));
+        Ok(entries
+            .filter(|(key, _)| after.is_none_or(|after| key.as_slice() > after))
+            .take(limit)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(
+    current = Num<2>,
+    from(Num<1> = u32),
+    envelope(tag = "schemaVersion", content = "payload")
+)]
+struct Quota {
+    limit: u32,
+}
+
+impl From<u32> for Quota {
+    fn from(limit: u32) -> Self {
+        Quota { limit }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(current = Num<2>, from(Num<1> = u32), envelope(wrapper))]
+struct Seats {
+    count: u32,
+}
+
+impl From<u32> for Seats {
+    fn from(count: u32) -> Self {
+        Seats { count }
+    }
+}
+
+#[test]
+fn test_envelope_layout() {
+    use serde_versioned::Envelope;
+
+    let quota: Envelope<Quota> =
+        serde_json::from_str(r#"{"schemaVersion": "v1", "payload": 5}"#).unwrap();
+    assert_eq!(quota.0, Quota { limit: 5 });
+    assert_eq!(
+        serde_json::to_string(&quota).unwrap(),
+        r#"{"schemaVersion":"v2","payload":{"limit":5}}"#
+    );
+    assert!(serde_json::from_str::<Envelope<Quota>>(r#"{"version": "v1", "data": 5}"#).is_err());
+
+    let seats: Envelope<Seats> = serde_json::from_str(r#"{"v1": 3}"#).unwrap();
+    assert_eq!(seats.0, Seats { count: 3 });
+    assert_eq!(
+        serde_json::to_string(&seats).unwrap(),
+        r#"{"v2":{"count":3}}"#
+    );
+    assert!(serde_json::from_str::<Envelope<Seats>>(r#"{"v1": 3, "v2": {"count": 3}}"#).is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+struct AccountV1 {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(current = Num<2>, from(Num<1> = AccountV1), envelope(internal))]
+#[serde(deny_unknown_fields)]
+struct Account {
+    first: String,
+    last: String,
+}
+
+impl From<AccountV1> for Account {
+    fn from(v: AccountV1) -> Self {
+        let (first, last) = v.name.split_once(' ').unwrap_or((&v.name, ""));
+        Account {
+            first: first.into(),
+            last: last.into(),
+        }
+    }
+}
+
+#[test]
+fn test_internal_envelope() {
+    use serde_versioned::Envelope;
+
+    let ada = Account {
+        first: "Ada".into(),
+        last: "Lovelace".into(),
+    };
+    let parse = |json| serde_json::from_str::<Envelope<Account>>(json).unwrap().0;
+    assert_eq!(parse(r#"{"version": 1, "name": "Ada Lovelace"}"#), ada);
+    assert_eq!(
+        parse(r#"{"first": "Ada", "version": "v2", "last": "Lovelace"}"#),
+        ada
+    );
+
+    assert_eq!(
+        serde_json::to_string(&Envelope(ada)).unwrap(),
+        r#"{"version":"v2","first":"Ada","last":"Lovelace"}"#
+    );
+}
+
+#[derive(Deserialize)]
+struct InvoiceV1 {
+    total: u64,
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize, Versioned)]
+#[versioned(current = Num<2>, from(Num<1> = InvoiceV1))]
+struct Invoice {
+    schema_version: serde_versioned::VersionStamp<Self>,
+    total_cents: u64,
+}
+
+impl From<InvoiceV1> for Invoice {
+    fn from(v: InvoiceV1) -> Self {
+        Invoice {
+            schema_version: Default::default(),
+            total_cents: v.total * 100,
+        }
+    }
+}
+
+#[test]
+fn test_version_stamp() {
+    use serde_versioned::DeserializeVersioned;
+
+    let invoice = Invoice {
+        schema_version: Default::default(),
+        total_cents: 500,
+    };
+    let json = serde_json::to_string(&invoice).unwrap();
+    assert_eq!(json, r#"{"schema_version":"v2","total_cents":500}"#);
+
+    let parse = |json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        Invoice::deserialize_any_version(&mut d).unwrap()
+    };
+    assert_eq!(parse(&json), invoice);
+    assert_eq!(
+        parse(r#"{"schema_version": 2, "total_cents": 500}"#),
+        invoice
+    );
+    assert_eq!(
+        parse(r#"{"schema_version": 1, "total_cents": 500, "total": 5}"#),
+        invoice
+    );
+
+    let error = serde_json::from_str::<Invoice>(r#"{"schema_version": "v1", "total_cents": 5}"#);
+    assert!(error
+        .unwrap_err()
+        .to_string()
+        .contains("expected version `v2`, found `v1`"));
+}
+
+#[test]
+fn test_framing() {
+    use serde_versioned::{FrameError, Framing};
+
+    const FRAMING: Framing<Json> = Framing::new(b"TEMP");
+
+    let boiling = Temperature { celsius: 100.0 };
+    let mut file = Vec::new();
+    FRAMING.write(&mut file, &boiling).unwrap();
+    assert_eq!(file, b"TEMP\x02{\"celsius\":100.0}");
+    assert_eq!(FRAMING.read::<Temperature, _>(&file[..]).unwrap(), boiling);
+
+    // Written by an old release.
+    let v1 = b"TEMP\x00212.0";
+    assert_eq!(FRAMING.read::<Temperature, _>(&v1[..]).unwrap(), boiling);
+
+    let error = FRAMING.read::<Temperature, _>(&b"JSON\x01212.0"[..]);
+    assert!(matches!(error, Err(FrameError::Magic)));
+    let error = FRAMING.read::<Temperature, _>(&b"TEMP\x81"[..]);
+    assert!(matches!(error, Err(FrameError::VersionId)));
+}
+
+#[test]
+fn test_confluent() {
+    use serde_versioned::{Confluent, FrameError};
+
+    let boiling = Temperature { celsius: 100.0 };
+    let record = Confluent::<Json>::to_vec(&boiling).unwrap();
+    assert_eq!(record, b"\0\0\0\0\x65{\"celsius\":100.0}");
+    assert_eq!(
+        Confluent::<Json>::from_slice::<Temperature>(&record).unwrap(),
+        boiling
+    );
+
+    // Produced with an old schema.
+    let v2 = b"\0\0\0\0\x39100.0";
+    assert_eq!(
+        Confluent::<Json>::from_slice::<Temperature>(v2).unwrap(),
+        boiling
+    );
+
+    let error = Confluent::<Json>::from_slice::<Temperature>(b"\0\0\0\0\x0c212.0");
+    assert!(matches!(error, Err(FrameError::SchemaId(12))));
+    let error = Confluent::<Json>::from_slice::<Temperature>(b"\x01\0\0\0\x39100.0");
+    assert!(matches!(error, Err(FrameError::Magic)));
+}
+
+#[test]
+fn test_media_types() {
+    use serde_versioned::MediaTypes;
+
+    const MEDIA_TYPES: MediaTypes = MediaTypes::new("weather", "json");
+
+    let parse = |content_type, json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        MEDIA_TYPES.deserialize::<Temperature, _>(content_type, &mut d)
+    };
+
+    let boiling = Temperature { celsius: 100.0 };
+    let v3 = "application/vnd.weather.v3+json; charset=utf-8";
+    assert_eq!(parse(v3, r#"{"celsius": 100.0}"#).unwrap(), boiling);
+    assert_eq!(
+        parse("application/vnd.weather.v1+json", "212.0").unwrap(),
+        boiling
+    );
+    assert_eq!(parse("application/json", "100.0").unwrap(), boiling);
+    assert!(parse("application/vnd.weather.v4+json", "100.0").is_err());
+
+    assert_eq!(
+        MEDIA_TYPES.version("Application/VND.weather.v2+JSON"),
+        Some("v2")
+    );
+    assert_eq!(MEDIA_TYPES.version("application/vnd.weather.v2+xml"), None);
+    assert_eq!(MEDIA_TYPES.version("application/vnd.other.v2+json"), None);
+    assert_eq!(
+        MEDIA_TYPES.media_type_of::<Num<3>>(),
+        "application/vnd.weather.v3+json"
+    );
+}
+
+#[test]
+fn test_from_slice() {
+    use serde_versioned::DeserializeVersioned;
+
+    let boiling = Temperature { celsius: 100.0 };
+    let parse = |bytes: &[u8]| Temperature::from_slice_any_version::<Json>(bytes);
+    assert_eq!(parse(br#"{"celsius":100.0}"#).unwrap(), boiling);
+    assert_eq!(parse(b"100.0").unwrap(), boiling);
+
+    let error = parse(b"\"hot\"").unwrap_err();
+    assert_eq!(error.iter().count(), 3);
+
+    let ada = Username { name: "ada".into() };
+    assert_eq!(
+        Username::from_slice_any_version::<Json>(b"\" ada \"").unwrap(),
+        ada
+    );
+    assert!(Username::from_slice_any_version::<Json>(b"\"  \"").is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+struct Reading {
+    #[serde(with = "serde_versioned::binary")]
+    temperature: Temperature,
+}
+
+#[test]
+fn test_binary_field() {
+    let boiling = Temperature { celsius: 100.0 };
+    let reading: Reading = serde_json::from_str(r#"{"temperature": [0, 212.0]}"#).unwrap();
+    assert_eq!(reading.temperature, boiling);
+
+    let json = serde_json::to_string(&reading).unwrap();
+    assert_eq!(json, r#"{"temperature":[2,{"celsius":100.0}]}"#);
+}
+
+#[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+struct TaggedReading {
+    #[serde(with = "serde_versioned::tagged")]
+    temperature: Temperature,
+}
+
+#[test]
+fn test_tagged_field() {
+    let reading = TaggedReading {
+        temperature: Temperature { celsius: 100.0 },
+    };
+    let json = serde_json::to_string(&reading).unwrap();
+    assert_eq!(
+        json,
+        r#"{"temperature":{"version":"v3","data":{"celsius":100.0}}}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<TaggedReading>(&json).unwrap(),
+        reading
+    );
+
+    let old: TaggedReading =
+        serde_json::from_str(r#"{"temperature": {"version": "v1", "data": 212.0}}"#).unwrap();
+    assert_eq!(old, reading);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_raw_value() {
+    use serde_json::value::RawValue;
+    use serde_versioned::json::from_raw_value;
+
+    let records: Vec<&RawValue> = serde_json::from_str(r#"[{"celsius": 100.0}, 100.0]"#).unwrap();
+    for record in records {
+        let temperature: Temperature = from_raw_value(record).unwrap();
+        assert_eq!(temperature, Temperature { celsius: 100.0 });
+    }
+
+    let raw = RawValue::from_string(String::from(r#""hot""#)).unwrap();
+    assert!(from_raw_value::<Temperature>(&raw).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_upgrade_value() {
+    use serde_json::json;
+    use serde_versioned::json::{from_value, upgrade_value};
+
+    let current = json!({"celsius": 100.0});
+    assert_eq!(
+        upgrade_value::<Temperature>(&json!(100.0)).unwrap(),
+        current
+    );
+    assert_eq!(upgrade_value::<Temperature>(&current).unwrap(), current);
+
+    let temperature: Temperature = from_value(&json!(100.0)).unwrap();
+    assert_eq!(temperature, Temperature { celsius: 100.0 });
+    assert!(upgrade_value::<Temperature>(&json!("hot")).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_upgrade_lines() {
+    use serde_versioned::json::upgrade_lines;
+
+    let input = "100.0\n\n{\"celsius\": 100.0}\n\"hot\"\n37.5\n";
+    let mut output = Vec::new();
+    let mut failed = Vec::new();
+    let written = upgrade_lines::<Temperature, _, _>(input.as_bytes(), &mut output, |line, _| {
+        failed.push(line)
+    })
+    .unwrap();
+
+    assert_eq!(written, 3);
+    assert_eq!(failed, [4]);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "{\"celsius\":100.0}\n{\"celsius\":100.0}\n{\"celsius\":37.5}\n"
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_upgrade_path() {
+    use serde_versioned::json::upgrade_path;
+
+    let dir = std::env::temp_dir().join(format!("serde-versioned-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("a.json"), "100.0").unwrap();
+    std::fs::write(dir.join("nested/b.json"), r#""hot""#).unwrap();
+    std::fs::write(dir.join("notes.txt"), "100.0").unwrap();
+    std::fs::write(dir.join("current.json"), r#"{"celsius":100.0}"#).unwrap();
+
+    let mut failed = Vec::new();
+    let upgraded = upgrade_path::<Temperature>(&dir, |path, _| failed.push(path.to_owned()));
+    assert_eq!(upgraded.unwrap(), 1);
+    assert_eq!(failed, [dir.join("nested/b.json")]);
+
+    let read = |name| std::fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("a.json"), "{\n  \"celsius\": 100.0\n}\n");
+    assert_eq!(read("notes.txt"), "100.0");
+    assert_eq!(read("current.json"), r#"{"celsius":100.0}"#);
+    assert_eq!(
+        upgrade_path::<Temperature>(&dir.join("a.json"), |_, _| {}).unwrap(),
+        0
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[serde_versioned::versioned]
+#[derive(Debug, PartialEq, Deserialize)]
+struct ServiceConfig {
+    #[versioned(from(serde_versioned::Quoted = String))]
+    port: u16,
+    #[versioned(from(serde_versioned::YesNo = String))]
+    verbose: bool,
+    #[versioned(from(serde_versioned::HumanDuration = String))]
+    timeout: std::time::Duration,
+}
+
+#[test]
+fn test_coercions() {
+    let current: ServiceConfig = serde_json::from_str(
+        r#"{"port": 8080, "verbose": true, "timeout": {"secs": 90, "nanos": 0}}"#,
+    )
+    .unwrap();
+    let legacy: ServiceConfig =
+        serde_json::from_str(r#"{"port": "8080", "verbose": "Yes", "timeout": "1m30s"}"#).unwrap();
+    assert_eq!(current.port, 8080);
+    assert!(current.verbose);
+    assert_eq!(legacy, current);
+
+    let parse = |json| {
+        serde_json::from_str::<ServiceConfig>(json)
+            .unwrap_err()
+            .to_string()
+    };
+    let error = parse(r#"{"port": 1, "verbose": "maybe", "timeout": "5m"}"#);
+    assert!(
+        error.contains("expected a boolean, found `maybe`"),
+        "{error}"
+    );
+    let error = parse(r#"{"port": 1, "verbose": "no", "timeout": "5 minutes"}"#);
+    assert!(error.contains("expected a duration"), "{error}");
+}
+
+/// Exported as `sku,qty`, before items had names.
+#[derive(Deserialize)]
+struct ItemV2 {
+    sku: String,
+    qty: u32,
+}
+
+/// Exported without headers, as `quantity,sku`.
+#[derive(Deserialize)]
+struct ItemV1(u32, String);
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<2> = ItemV2, Num<1> = ItemV1))]
+struct Item {
+    sku: String,
+    name: Option<String>,
+    quantity: u32,
+}
+
+impl From<ItemV2> for Item {
+    fn from(v: ItemV2) -> Self {
+        Item {
+            sku: v.sku,
+            name: None,
+            quantity: v.qty,
+        }
+    }
+}
+
+impl From<ItemV1> for Item {
+    fn from(ItemV1(quantity, sku): ItemV1) -> Self {
+        Item {
+            sku,
+            name: None,
+            quantity,
+        }
+    }
+}
+
+#[test]
+fn test_from_record() {
+    use serde_versioned::from_record;
+
+    let current: Item = from_record(
+        Some(&["name", "quantity", "sku"][..]),
+        &["Widget", "3", "A1"],
+    )
+    .unwrap();
+    assert_eq!(
+        current,
+        Item {
+            sku: "A1".into(),
+            name: Some("Widget".into()),
+            quantity: 3,
+        }
+    );
+
+    let unnamed = Item {
+        name: None,
+        ..current
+    };
+    let v2: Item = from_record(Some(&["sku", "qty"][..]), &["A1", "3"]).unwrap();
+    assert_eq!(v2, unnamed);
+    let v1: Item = from_record(None, &["3", "A1"]).unwrap();
+    assert_eq!(v1, unnamed);
+
+    // A column missing from a narrower layout is an absent field, and an empty
+    // field of an optional column is `None`.
+    let narrow: Item = from_record(Some(&["sku", "quantity"][..]), &["A1", "3"]).unwrap();
+    assert_eq!(narrow, unnamed);
+    let blank: Item =
+        from_record(Some(&["sku", "name", "quantity"][..]), &["A1", "", "3"]).unwrap();
+    assert_eq!(blank, unnamed);
+
+    assert!(from_record::<Item, _>(Some(&["sku", "qty"][..]), &["A1", "many"]).is_err());
+}
+
+/// Search parameters of old clients, `q` and `max` as a string like `"20"`.
+#[derive(Deserialize)]
+struct SearchV1 {
+    q: String,
+    max: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = SearchV1))]
+struct Search {
+    query: String,
+    limit: Option<u32>,
+}
+
+impl TryFrom<SearchV1> for Search {
+    type Error = std::num::ParseIntError;
+
+    fn try_from(v: SearchV1) -> Result<Self, Self::Error> {
+        Ok(Search {
+            query: v.q,
+            limit: v.max.map(|max| max.parse()).transpose()?,
+        })
+    }
+}
+
+#[test]
+fn test_from_query() {
+    use serde_versioned::from_query;
+
+    let search = Search {
+        query: "black tea".into(),
+        limit: Some(20),
+    };
+    assert_eq!(
+        from_query::<Search>("query=black+tea&limit=20").unwrap(),
+        search
+    );
+    assert_eq!(
+        from_query::<Search>("q=black%20tea&max=20").unwrap(),
+        search
+    );
+
+    let unlimited: Search = from_query("q=black+tea&max=").unwrap();
+    assert_eq!(unlimited.limit, None);
+    assert!(from_query::<Search>("query=tea&limit=many").is_err());
+}
+
+#[test]
+fn test_registry() {
+    use serde::de::IntoDeserializer;
+    use serde_versioned::Registry;
+
+    let mut registry = Registry::<Temperature>::new();
+    registry
+        .register_version::<Num<2>>()
+        .register("kelvin", |kelvin: f64| match kelvin < 0.0 {
+            true => Err("below absolute zero"),
+            false => Ok(Temperature {
+                celsius: kelvin - 273.0,
+            }),
+        });
+    assert_eq!(registry.tags().collect::<Vec<_>>(), ["v2", "kelvin"]);
+
+    let deserialize_dyn = |tag, json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        registry.deserialize_dyn(tag, &mut d)
+    };
+    let boiling = Temperature { celsius: 100.0 };
+    assert_eq!(deserialize_dyn("v2", "100.0").unwrap(), boiling);
+    assert_eq!(deserialize_dyn("2", "100.0").unwrap(), boiling);
+    assert_eq!(deserialize_dyn("kelvin", "373.0").unwrap(), boiling);
+
+    let error = deserialize_dyn("kelvin", "-1.0").unwrap_err();
+    assert!(error.to_string().contains("below absolute zero"));
+    let error = deserialize_dyn("rankine", "671.6").unwrap_err();
+    assert!(error.to_string().contains("unknown version `rankine`"));
+
+    let any: Result<_, serde::de::value::Error> =
+        registry.deserialize_any_version("373".into_deserializer());
+    let error = any.unwrap_err().to_string();
+    assert!(error.contains("Num<2>: ") && error.contains("kelvin: "));
+}
+
+#[test]
+fn test_migrate_store() {
+    use serde_versioned::{migrate_store, Store};
+
+    let mut store = MemoryStore::default();
+    for (key, value) in [
+        ("temp/a", "100.0"),
+        ("temp/b", r#"{"celsius":100.0}"#),
+        ("temp/c", r#""hot""#),
+        ("temp/d", "37.5"),
+        ("temp/e", "0.0"),
+        ("other", "1.0"),
+    ] {
+        store.put(key.as_bytes(), value.as_bytes()).unwrap();
+    }
+
+    let mut batches = Vec::new();
+    let progress = migrate_store::<Temperature, Json, _>(&mut store, b"temp/", 2, |progress| {
+        batches.push(progress.scanned())
+    })
+    .unwrap();
+
+    assert_eq!(batches, [2, 4, 5]);
+    assert_eq!(progress.upgraded(), 3);
+    let failed: Vec<_> = progress.failed().map(|(key, _)| key).collect();
+    assert_eq!(failed, [b"temp/c"]);
+
+    let get = |key: &str| String::from_utf8(store.get(key.as_bytes()).unwrap().unwrap()).unwrap();
+    assert_eq!(get("temp/a"), r#"{"celsius":100.0}"#);
+    assert_eq!(get("temp/d"), r#"{"celsius":37.5}"#);
+    assert_eq!(get("temp/c"), r#""hot""#);
+    assert_eq!(get("other"), "1.0");
+}
+
+#[test]
+fn test_read_repair() {
+    use serde_versioned::{read_repair, RepairError};
+
+    let (value, repaired) = read_repair::<Temperature, Json>(b"100.0").unwrap();
+    assert_eq!(value, Temperature { celsius: 100.0 });
+    assert_eq!(repaired.as_deref(), Some(&br#"{"celsius":100.0}"#[..]));
+
+    let (_, repaired) = read_repair::<Temperature, Json>(br#"{"celsius":100.0}"#).unwrap();
+    assert_eq!(repaired, None);
+
+    let error = read_repair::<Temperature, Json>(br#""hot""#).unwrap_err();
+    assert!(matches!(error, RepairError::Versions(errors) if errors.len() == 3));
+}
+
+#[test]
+fn test_registry_tenants() {
+    use serde_versioned::Registry;
+
+    let mut registry = Registry::<Temperature>::new();
+    registry
+        .register_version::<Num<2>>()
+        .register_version::<Num<1>>()
+        .set_tenant_versions("celsius", ["v2"])
+        .set_tenant_versions("fahrenheit", ["v1"]);
+
+    let deserialize_for = |tenant, json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        registry.deserialize_any_version_for(tenant, &mut d)
+    };
+    assert_eq!(deserialize_for("celsius", "100.0").unwrap().celsius, 100.0);
+    assert_eq!(
+        deserialize_for("fahrenheit", "212.0").unwrap().celsius,
+        100.0
+    );
+    assert!(deserialize_for("unknown", "100.0").is_err());
+
+    let mut d = serde_json::Deserializer::from_str("212.0");
+    let error = registry
+        .deserialize_dyn_for("celsius", "v1", &mut d)
+        .unwrap_err();
+    assert!(error.to_string().contains("unknown version `v1`"));
+}
+
+#[test]
+fn test_manifest() {
+    use serde_versioned::Manifest;
+
+    let manifest: Manifest = serde_json::from_str(
+        r#"{"versions": [
+            {"name": "Current"},
+            {"name": "Num<1>", "id": 1, "fixture": "tests/fixtures/point_v1.json"},
+            {"name": "Uuid<2>", "id": 2, "fixture": "tests/fixtures/point_v2.json"}
+        ]}"#,
+    )
+    .unwrap();
+    manifest.check::<Point, Json>().unwrap();
+
+    let manifest: Manifest = serde_json::from_str(
+        r#"{"versions": [
+            {"name": "Current"},
+            {"name": "Num<1>", "id": 3},
+            {"name": "Num<4>", "fixture": "tests/fixtures/missing.json"}
+        ]}"#,
+    )
+    .unwrap();
+    let errors = manifest.check::<Point, Json>().unwrap_err();
+    let errors: Vec<_> = errors.iter().collect();
+    assert_eq!(errors[0], "Num<1> has id 1, not 3");
+    assert_eq!(errors[1], "Num<4> is not a version of the type");
+    assert!(errors[2].starts_with("fixture tests/fixtures/missing.json of Num<4>: "));
+    assert_eq!(errors[3], "Uuid<2> is missing from the manifest");
+}
+
+#[test]
+fn test_manifest_file() {
+    use serde_versioned::Manifest;
+
+    let manifest = Manifest::from_file::<Json>("tests/fixtures/point_manifest.json").unwrap();
+    manifest.check::<Point, Json>().unwrap();
+
+    let manifest = Manifest::new(manifest.versions().to_vec());
+    let errors = manifest.check::<Point, Json>().unwrap_err();
+    let errors: Vec<_> = errors.iter().collect();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("fixture point_v1.json of Num<1>: "));
+    assert!(errors[1].starts_with("fixture point_v2.json of Uuid< 2 >: "));
+    manifest
+        .with_base_dir("tests/fixtures")
+        .check::<Point, Json>()
+        .unwrap();
+}
+
+#[test]
+fn test_deserialize_detailed() {
+    use serde_versioned::DeserializeVersioned;
+
+    let detailed = |json| {
+        let mut d = serde_json::Deserializer::from_str(json);
+        Point::deserialize_detailed(&mut d).unwrap()
+    };
+    assert_eq!(
+        detailed(r#"{"coords": [1, 2]}"#).unwrap(),
+        Point { x: 1, y: 2 }
+    );
+
+    let errors = detailed(r#"{"x": "1", "y": "a"}"#).unwrap_err();
+    assert_eq!(errors.len(), 3);
+    assert!(errors.get("Current").unwrap().contains("invalid type"));
+    assert!(errors
+        .get("Num<1>")
+        .unwrap()
+        .contains("missing field `coords`"));
+    assert_eq!(errors.get("Uuid<2>"), Some("invalid digit found in string"));
+
+    let mut d = serde_json::Deserializer::from_str("{");
+    assert!(Point::deserialize_detailed(&mut d).is_err());
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Profile {
+    timeout: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Settings {
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = Settings))]
+struct Preferences {
+    settings: Settings,
+}
+
+impl From<Settings> for Preferences {
+    fn from(settings: Settings) -> Self {
+        Preferences { settings }
+    }
+}
+
+#[test]
+fn test_error_paths() {
+    use serde_versioned::DeserializeVersioned;
+
+    let json = r#"{"settings": {"profiles": [{"timeout": 1}, {"timeout": "1s"}]}}"#;
+    let mut d = serde_json::Deserializer::from_str(json);
+    let errors = Preferences::deserialize_detailed(&mut d)
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(
+        errors.get("Current"),
+        Some(r#"settings.profiles[1].timeout: invalid type: string "1s", expected u32"#)
+    );
+    assert_eq!(errors.get("Num<1>"), Some("missing field `profiles`"));
+
+    // The same failure again is given the same path, not a nested one.
+    let mut d = serde_json::Deserializer::from_str(json);
+    let again = Preferences::deserialize_detailed(&mut d)
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(again.get("Current"), errors.get("Current"));
+
+    let mut d = serde_json::Deserializer::from_str(r#"{"settings": {"profiles": [1]}}"#);
+    assert!(Preferences::deserialize_any_version(&mut d)
+        .unwrap_err()
+        .to_string()
+        .contains("Current: settings.profiles[0]: invalid type"));
+}
+
+#[test]
+fn test_observed() {
+    use std::sync::Mutex;
+
+    use serde_versioned::{DeserializeVersioned, MigrationObserver, VersionErrors, VersionId};
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<String>>);
+
+    impl MigrationObserver for Events {
+        fn on_attempt(&self, type_name: &str, version: &VersionId) {
+            let event = format!("attempt {type_name} {}", version.name());
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn on_match(&self, type_name: &str, version: &VersionId) {
+            let event = format!("match {type_name} {}", version.name());
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn on_convert_error(&self, _type_name: &str, version: &VersionId, _error: &str) {
+            let event = format!("error {}", version.name());
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn on_exhausted(&self, type_name: &str, errors: &VersionErrors) {
+            let event = format!("exhausted {type_name} {}", errors.len());
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    let observed = |json| {
+        let events = Events::default();
+        let mut d = serde_json::Deserializer::from_str(json);
+        let res = Point::deserialize_observed(&mut d, &events);
+        (res.is_ok(), events.0.into_inner().unwrap())
+    };
+    assert_eq!(
+        observed(r#"{"coords": [1, 2]}"#),
+        (
+            true,
+            vec![
+                "attempt Point Current".into(),
+                "error Current".into(),
+                "attempt Point Num<1>".into(),
+                "match Point Num<1>".into(),
+            ]
+        )
+    );
+    let (ok, events) = observed("1");
+    assert!(!ok);
+    assert_eq!(events.last().unwrap(), "exhausted Point 3");
+}
+
+#[test]
+fn test_migration_stats() {
+    use serde_versioned::{DeserializeVersioned, MigrationStats};
+
+    let stats = MigrationStats::new();
+    for json in [
+        r#"{"x": 1, "y": 2}"#,
+        r#"{"coords": [1, 2]}"#,
+        r#"{"coords": [3, 4]}"#,
+        "1",
+    ] {
+        let mut d = serde_json::Deserializer::from_str(json);
+        let _ = Point::deserialize_observed(&mut d, &stats);
+    }
+
+    let snapshot = stats.snapshot();
+    let counts: Vec<_> = snapshot
+        .versions
+        .iter()
+        .map(|v| {
+            (
+                v.type_name.as_str(),
+                v.version.as_str(),
+                v.matches,
+                v.failures,
+            )
+        })
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            ("Point", "Current", 1, 3),
+            ("Point", "Num<1>", 2, 1),
+            ("Point", "Uuid<2>", 0, 1),
+        ]
+    );
+    assert_eq!(snapshot.exhausted["Point"], 1);
+
+    let json = serde_json::to_value(&snapshot).unwrap();
+    assert_eq!(json["versions"][1]["version"], "Num<1>");
+    assert_eq!(json["exhausted"]["Point"], 1);
+}
+
+/// Only reads the current and first version of a `Point`.
+struct PointCache;
+
+impl serde_versioned::FormatVersions<Json> for Point {
+    type Versions = <Point as serde_versioned::DeserializeVersioned>::Versions;
+}
+
+impl serde_versioned::FormatVersions<PointCache> for Point {
+    type Versions = (
+        serde_versioned::Ver<serde_versioned::Current>,
+        serde_versioned::Ver<Num<1>>,
+    );
+}
+
+#[test]
+fn test_format_versions() {
+    use serde_versioned::{deserialize_for, from_slice_for};
+
+    let v2 = r#"{"x": "1", "y": "2"}"#;
+    let point = deserialize_for::<Json, Point, _>(&mut serde_json::Deserializer::from_str(v2));
+    assert_eq!(point.unwrap(), Point { x: 1, y: 2 });
+    assert_eq!(
+        from_slice_for::<Json, Point>(v2.as_bytes()).unwrap(),
+        Point { x: 1, y: 2 }
+    );
+
+    let mut d = serde_json::Deserializer::from_str(v2);
+    assert!(deserialize_for::<PointCache, Point, _>(&mut d).is_err());
+    let mut d = serde_json::Deserializer::from_str(r#"{"coords": [3, 4]}"#);
+    assert_eq!(
+        deserialize_for::<PointCache, Point, _>(&mut d).unwrap(),
+        Point { x: 3, y: 4 }
+    );
+}
+
+#[test]
+fn test_migrated() {
+    use serde_versioned::Migrated;
+
+    let points: Vec<Migrated<Point>> =
+        serde_json::from_str(r#"[{"x": 1, "y": 2}, {"coords": [3, 4]}]"#).unwrap();
+    assert!(!points[0].was_migrated());
+    assert!(points[1].was_migrated());
+    assert_eq!(points[1].source_version().name(), "Num<1>");
+    assert_eq!(points[1].x, 3);
+    let point = points.into_iter().nth(1).unwrap().into_inner();
+    assert_eq!(point, Point { x: 3, y: 4 });
+}
+
+#[test]
+fn test_audited() {
+    use serde_versioned::Audited;
+
+    let audited: Audited<Point> = serde_json::from_str(r#"{"coords": [1, 2]}"#).unwrap();
+    assert_eq!(*audited.value(), Point { x: 1, y: 2 });
+    assert_eq!(audited.version().name(), "Num<1>");
+    assert_eq!(
+        serde_json::to_string(&audited).unwrap(),
+        r#"{"coords":[1,2]}"#
+    );
+
+    let audited: Audited<Point> = serde_json::from_str(r#"{"x": 3, "y": 4}"#).unwrap();
+    assert!(audited.version().is_current());
+    let (point, _, original) = audited.into_parts();
+    assert_eq!(point, Point { x: 3, y: 4 });
+    assert_eq!(serde_json::to_value(original).unwrap()["x"], 3);
+
+    assert!(serde_json::from_str::<Audited<Point>>(r#"{"coords": 1}"#).is_err());
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "derive")]
+
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_versioned::{
+    DeserializeVersioned, MigrationObserver, Num, VersionId, Versioned, Warnings,
+};
+
+#[derive(Deserialize)]
+struct OrderV1(u32);
+
+#[derive(Debug, PartialEq, Deserialize, Versioned)]
+#[versioned(from(Num<1> = OrderV1))]
+struct Order {
+    quantity: u32,
+}
+
+impl From<OrderV1> for Order {
+    fn from(v: OrderV1) -> Self {
+        Order { quantity: v.0 }
+    }
+}
+
+#[derive(Deserialize)]
+struct Shipment {
+    #[serde(deserialize_with = "serde_versioned::deserialize")]
+    order: Order,
+}
+
+type OrderVersions = <Order as DeserializeVersioned>::Versions;
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+struct Counter(Mutex<Vec<(String, String)>>);
+
+impl MigrationObserver for Counter {
+    fn on_match(&self, type_name: &str, version: &VersionId) {
+        let mut seen = self.0.lock().unwrap();
+        seen.push((type_name.to_owned(), version.name().to_owned()));
+    }
+}
+
+#[test]
+fn test_observer() {
+    static COUNTER: Counter = Counter(Mutex::new(Vec::new()));
+    serde_versioned::set_observer(&COUNTER);
+
+    for json in [
+        r#"{"order": {"quantity": 3}}"#,
+        r#"{"order": 3}"#,
+        r#"{"order": 3}"#,
+    ] {
+        let shipment = serde_json::from_str::<Shipment>(json).unwrap();
+        assert_eq!(shipment.order, Order { quantity: 3 });
+    }
+    assert!(serde_json::from_str::<Shipment>(r#"{"order": "3"}"#).is_err());
+
+    // The variants taking context, warnings, a seed state or async converters
+    // are reported the same way.
+    let d = || serde_json::Deserializer::from_str("3");
+    let order: Order = OrderVersions::deserialize_with_context(&mut d(), &()).unwrap();
+    assert_eq!(order, Order { quantity: 3 });
+    let (order, _): (Order, Warnings) = OrderVersions::deserialize_with_warnings(&mut d()).unwrap();
+    assert_eq!(order, Order { quantity: 3 });
+    let order: Order = OrderVersions::deserialize_seed(&mut d(), &mut ()).unwrap();
+    assert_eq!(order, Order { quantity: 3 });
+    let order: Order = block_on(OrderVersions::deserialize_async(&mut d())).unwrap();
+    assert_eq!(order, Order { quantity: 3 });
+
+    let seen = COUNTER.0.lock().unwrap();
+    let seen: Vec<_> = seen
+        .iter()
+        .map(|(ty, version)| (ty.as_str(), version.as_str()))
+        .collect();
+    assert_eq!(
+        seen,
+        [
+            ("Order", "Current"),
+            ("Order", "Num<1>"),
+            ("Order", "Num<1>"),
+            ("Order", "Num<1>"),
+            ("Order", "Num<1>"),
+            ("Order", "Num<1>"),
+            ("Order", "Num<1>"),
+        ]
+    );
+}